@@ -9,7 +9,7 @@ pub fn build(
     public_dir: Option<PathBuf>,
 ) -> Result<()> {
     let (root, function_config) = resolve_path(path, client, public_dir)?;
-    let (index, assets) = bundle_function(&function_config, &root, true)?;
+    let (index, source_map, assets) = bundle_function(&function_config, &root, true)?;
 
     let end_progress = print_progress("Writting files");
     let root = root.join(".lagon");
@@ -17,6 +17,10 @@ pub fn build(
     fs::create_dir_all(&root)?;
     fs::write(root.join("index.js"), index)?;
 
+    if let Some(source_map) = source_map {
+        fs::write(root.join("index.js.map"), source_map)?;
+    }
+
     for (path, content) in assets {
         let dir = root.join("public").join(
             PathBuf::from(&path)
@@ -33,7 +37,9 @@ pub fn build(
     println!(" {} Build successful!", style("◼").magenta());
     println!(
         "   {}",
-        style(format!("You can find it in {:?}", root)).black().bright()
+        style(format!("You can find it in {:?}", root))
+            .black()
+            .bright()
     );
 
     Ok(())
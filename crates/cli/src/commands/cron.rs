@@ -0,0 +1,68 @@
+use crate::utils::{get_root, get_theme, print_progress, Config, FunctionConfig, TrpcClient};
+use anyhow::{anyhow, Result};
+use dialoguer::{console::style, Confirm};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TriggerCronRequest {
+    function_id: String,
+    schedule: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TriggerCronResponse {
+    #[allow(dead_code)]
+    ok: bool,
+}
+
+pub async fn cron_trigger(schedule: String, directory: Option<PathBuf>) -> Result<()> {
+    let config = Config::new()?;
+
+    if config.token.is_none() {
+        return Err(anyhow!(
+            "You are not logged in. Please log in with `lagon login`",
+        ));
+    }
+
+    let root = get_root(directory);
+    let function_config = FunctionConfig::load(&root, None, None)?;
+
+    match Confirm::with_theme(get_theme())
+        .with_prompt(format!(
+            "Do you really want to run the Cron schedule {schedule} now?"
+        ))
+        .default(true)
+        .interact()?
+    {
+        true => {
+            println!();
+            let end_progress = print_progress("Triggering Cron schedule");
+            TrpcClient::new(config)
+                .set_organization_id(function_config.organization_id.clone())
+                .mutation::<TriggerCronRequest, TriggerCronResponse>(
+                    "functionCronTrigger",
+                    TriggerCronRequest {
+                        function_id: function_config.function_id,
+                        schedule,
+                    },
+                )
+                .await?;
+            end_progress();
+
+            println!();
+            println!(
+                " {} Cron schedule triggered! Check the logs of your Function to see its execution.",
+                style("◼").magenta()
+            );
+
+            Ok(())
+        }
+        false => {
+            println!();
+            println!("{} Trigger aborted", style("✕").red());
+            Ok(())
+        }
+    }
+}
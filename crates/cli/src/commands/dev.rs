@@ -1,5 +1,6 @@
 use crate::utils::{bundle_function, resolve_path, Assets};
 use anyhow::{anyhow, Error, Result};
+use async_trait::async_trait;
 use chrono::offset::Local;
 use dialoguer::console::style;
 use envfile::EnvFile;
@@ -7,26 +8,41 @@ use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server};
 use lagon_runtime::{options::RuntimeOptions, Runtime};
+use lagon_runtime_cache::{CacheStorage, CacheStore, CacheStoreOptions};
+use lagon_runtime_db::Database;
 use lagon_runtime_http::{
     RunResult, X_FORWARDED_FOR, X_FORWARDED_HOST, X_FORWARDED_PROTO, X_LAGON_ID, X_LAGON_REGION,
     X_REAL_IP,
 };
+use lagon_runtime_isolate::fetch_handler::{FetchHandler, ReqwestFetchHandler};
+use lagon_runtime_isolate::fetch_policy::FetchPolicy;
 use lagon_runtime_isolate::{options::IsolateOptions, Isolate};
-use lagon_runtime_isolate::{IsolateEvent, IsolateRequest};
+use lagon_runtime_isolate::{IsolateEvent, IsolateQueue, IsolateRequest};
+use lagon_runtime_kv::{DiskKvStore, KvNamespace, KvStore, MemoryKvStore};
+use lagon_runtime_queue::{MemoryQueueStore, Queue};
 use lagon_runtime_utils::assets::{find_asset, handle_asset};
-use lagon_runtime_utils::response::{handle_response, ResponseEvent, FAVICON_URL};
-use lagon_runtime_utils::Deployment;
+use lagon_runtime_utils::request::{
+    is_body_limit_reached, is_body_too_large, limit_body, DEFAULT_MAX_REQUEST_BODY_SIZE,
+};
+use lagon_runtime_utils::response::{handle_run_result, ResponseEvent, FAVICON_URL, PAGE_413};
+use lagon_runtime_utils::websocket::{handle_websocket, WebSocketOptions, WebSocketUpgrade};
+use lagon_runtime_utils::{Deployment, RESERVED_BINDINGS};
 use notify::event::ModifyKind;
 use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::runtime::Handle;
 use tokio::sync::Mutex;
 
 const LOCAL_REGION: &str = "local";
+const LOCAL_KV_NAMESPACE: &str = "local";
+const LOCAL_QUEUE: &str = "local";
+const QUEUE_BATCH_SIZE: usize = 10;
+const LOCAL_DEPLOYMENT_ID: &str = "local";
 
 fn parse_environment_variables(
     path: Option<PathBuf>,
@@ -69,17 +85,136 @@ fn parse_environment_variables(
     Ok(environment_variables)
 }
 
+// Service bindings can't call other Functions in-process like in production,
+// so we send their requests to another local dev server instead
+struct DevServiceBinding {
+    url: String,
+    handler: ReqwestFetchHandler,
+}
+
+#[async_trait]
+impl FetchHandler for DevServiceBinding {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let (mut parts, body) = request.into_parts();
+
+        let path = parts
+            .uri
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str());
+        parts.uri = format!("{}{}", self.url, path).parse()?;
+
+        self.handler.fetch(Request::from_parts(parts, body)).await
+    }
+}
+
+fn parse_service_bindings(services: Vec<String>) -> Result<HashMap<String, Arc<dyn FetchHandler>>> {
+    let policy = Arc::new(FetchPolicy::default());
+    let mut service_bindings = HashMap::new();
+
+    for service in services {
+        let (name, url) = service
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Service binding {} should be NAME=URL", service))?;
+
+        if RESERVED_BINDINGS.contains(&name) {
+            return Err(anyhow!(
+                "Service binding {} conflicts with a reserved binding",
+                name
+            ));
+        }
+
+        let service_binding: Arc<dyn FetchHandler> = Arc::new(DevServiceBinding {
+            url: url.trim_end_matches('/').to_string(),
+            handler: ReqwestFetchHandler::new(Arc::clone(&policy)),
+        });
+
+        service_bindings.insert(name.to_string(), service_binding);
+    }
+
+    Ok(service_bindings)
+}
+
+// Consume the messages sent to `ctx.env.QUEUE` on the dev isolate,
+// like the serverless node does but without persisting them
+async fn consume_queue(queue: Queue, isolate_tx: flume::Sender<IsolateEvent>) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let messages = match queue.receive(QUEUE_BATCH_SIZE).await {
+            Ok(messages) if !messages.is_empty() => messages,
+            _ => continue,
+        };
+
+        println!(
+            "{} {} {}",
+            style(format!("{}", Local::now().time())).black().bright(),
+            style("QUEUE").blue(),
+            style(format!("{} message(s)", messages.len()))
+                .black()
+                .bright()
+        );
+
+        let (sender, receiver) = flume::unbounded();
+        let (outcomes_sender, outcomes_receiver) = flume::unbounded();
+
+        isolate_tx
+            .send_async(IsolateEvent::Queue(IsolateQueue {
+                queue: queue.name().to_string(),
+                messages: messages.clone(),
+                outcomes: outcomes_sender,
+                sender,
+            }))
+            .await
+            .unwrap_or(());
+
+        let run_result = receiver
+            .recv_async()
+            .await
+            .unwrap_or(RunResult::Error("Isolate didn't send a response".into()));
+
+        match &run_result {
+            RunResult::Scheduled(_) => {}
+            RunResult::Timeout => println!("{} Function execution timed out", style("✕").red()),
+            RunResult::MemoryLimit => println!(
+                "{} Function execution reached memory limit",
+                style("✕").red()
+            ),
+            RunResult::Error(error) => println!("{} {}", style("✕").red(), error),
+            _ => println!("{} Unexpected queue result", style("✕").red()),
+        }
+
+        // Wait for the promises passed to `ctx.waitUntil()`
+        while receiver.recv_async().await.is_ok() {}
+
+        let outcomes = outcomes_receiver.drain().collect();
+        let success = matches!(run_result, RunResult::Scheduled(_));
+
+        match queue.settle(messages, &outcomes, success).await {
+            Ok(result) if result.dead_lettered > 0 => println!(
+                "{} {} message(s) moved to the dead-letter queue",
+                style("✕").red(),
+                result.dead_lettered
+            ),
+            Ok(_) => {}
+            Err(error) => println!("{} {}", style("✕").red(), error),
+        }
+    }
+}
+
 // This function is similar to packages/serverless/src/main.rs,
 // except that we don't have multiple deployments and such multiple
 // threads to manage, and we don't manager logs and metrics.
 async fn handle_request(
-    req: Request<Body>,
+    mut req: Request<Body>,
     public_dir: Option<PathBuf>,
     ip: String,
     assets: Arc<Mutex<Assets>>,
     isolate_tx: flume::Sender<IsolateEvent>,
+    max_request_body_size: usize,
 ) -> Result<Response<Body>> {
+    let websocket_upgrade = WebSocketUpgrade::from_request(&mut req);
     let url = req.uri().path();
+    let bytes_in = Arc::new(AtomicUsize::new(0));
 
     let (tx, rx) = flume::unbounded();
     let assets = assets.lock().await.to_owned();
@@ -116,7 +251,14 @@ async fn handle_request(
         );
 
         let (mut parts, body) = req.into_parts();
-        let body = hyper::body::to_bytes(body).await?;
+
+        if is_body_too_large(&parts, max_request_body_size) {
+            println!("{} Request body is too large", style("✕").red());
+
+            return Ok(Response::builder().status(413).body(PAGE_413.into())?);
+        }
+
+        let body = limit_body(body, max_request_body_size, Arc::clone(&bytes_in));
 
         parts.headers.insert(X_FORWARDED_FOR, ip.parse()?);
         parts.headers.insert(X_FORWARDED_PROTO, "http".parse()?);
@@ -142,7 +284,35 @@ async fn handle_request(
         ..Deployment::default()
     });
 
-    handle_response(rx, deployment, |event| async move {
+    let run_result = rx.recv_async().await?;
+
+    if matches!(run_result, RunResult::Error(_))
+        && is_body_limit_reached(&bytes_in, max_request_body_size)
+    {
+        println!("{} Request body is too large", style("✕").red());
+
+        return Ok(Response::builder().status(413).body(PAGE_413.into())?);
+    }
+
+    if let RunResult::WebSocket(response_builder, channel) = run_result {
+        let id = channel.id;
+
+        println!("{} WebSocket accepted", style("✓").green());
+
+        return handle_websocket(
+            websocket_upgrade,
+            response_builder,
+            channel,
+            WebSocketOptions::default(),
+            move |message| {
+                isolate_tx
+                    .send(IsolateEvent::WebSocket(id, message))
+                    .unwrap_or(());
+            },
+        );
+    }
+
+    handle_run_result(run_result, rx, deployment, |event| async move {
         match event {
             ResponseEvent::UnexpectedStreamResult(result) => {
                 println!(
@@ -182,11 +352,15 @@ pub async fn dev(
     env: Option<PathBuf>,
     allow_code_generation: bool,
     prod: bool,
+    services: Vec<String>,
+    kv_dir: Option<PathBuf>,
+    db: Option<PathBuf>,
+    max_request_body_size: Option<usize>,
 ) -> Result<()> {
     let (root, function_config) = resolve_path(path.clone(), client, public_dir)?;
-    let (index, assets) = bundle_function(&function_config, &root, prod)?;
+    let (index, source_map, assets) = bundle_function(&function_config, &root, prod)?;
 
-    let index = Arc::new(Mutex::new(index));
+    let index = Arc::new(Mutex::new((index, source_map)));
     let assets = Arc::new(Mutex::new(assets));
 
     let runtime =
@@ -208,26 +382,55 @@ pub async fn dev(
         Err(err) => return Err(anyhow!("Could not load environment variables: {:?}", err)),
     };
 
+    let service_bindings = parse_service_bindings(services)?;
+    let kv_store: Arc<dyn KvStore> = match &kv_dir {
+        Some(kv_dir) => Arc::new(DiskKvStore::new(kv_dir)),
+        None => Arc::new(MemoryKvStore::default()),
+    };
+    let kv = KvNamespace::new(kv_store, LOCAL_KV_NAMESPACE.into())?;
+    // Messages are lost when `lagon dev` exits
+    let queue = Queue::new(Arc::new(MemoryQueueStore::default()), LOCAL_QUEUE.into())?;
+    // Kept in memory only, and shared by the isolates created on each reload
+    let cache = CacheStorage::new(
+        Arc::new(CacheStore::new(CacheStoreOptions::default())),
+        LOCAL_DEPLOYMENT_ID.into(),
+    );
+    let db_path = db.unwrap_or_else(|| root.join(".lagon").join("db.sqlite"));
+
     let (isolate_tx, isolate_rx) = flume::unbounded();
     let (log_sender, log_receiver) = flume::unbounded();
 
     let handle = Handle::current();
     let index_handle = Arc::clone(&index);
+    let queue_handle = queue.clone();
 
     std::thread::spawn(move || {
         handle.block_on(async move {
             loop {
-                let code = {
+                let (code, source_map) = {
                     let index = index_handle.lock().await;
-                    String::from_utf8(index.to_vec()).expect("Code is not UTF-8")
+
+                    (
+                        String::from_utf8(index.0.to_vec()).expect("Code is not UTF-8"),
+                        index
+                            .1
+                            .as_ref()
+                            .map(|source_map| String::from_utf8_lossy(source_map).to_string()),
+                    )
                 };
 
                 let mut isolate = Isolate::new(
                     IsolateOptions::new(code)
+                        .source_map(source_map)
                         .tick_timeout(Duration::from_millis(500))
                         .total_timeout(Duration::from_secs(30))
                         .metadata(Some((String::from(""), String::from(""))))
                         .environment_variables(environment_variables.clone())
+                        .service_bindings(service_bindings.clone())
+                        .kv(Some(kv.clone()))
+                        .queue(Some(queue_handle.clone()))
+                        .cache(Some(cache.clone()))
+                        .db(Some(Database::new(&db_path)))
                         .log_sender(log_sender.clone()),
                     isolate_rx.clone(),
                 );
@@ -251,6 +454,9 @@ pub async fn dev(
         }
     });
 
+    tokio::spawn(consume_queue(queue, isolate_tx.clone()));
+
+    let max_request_body_size = max_request_body_size.unwrap_or(DEFAULT_MAX_REQUEST_BODY_SIZE);
     let assets_handle = Arc::clone(&assets);
     let tx_handle = isolate_tx.clone();
 
@@ -270,6 +476,7 @@ pub async fn dev(
                     ip.clone(),
                     Arc::clone(&assets),
                     tx.clone(),
+                    max_request_body_size,
                 )
             }))
         }
@@ -299,10 +506,11 @@ pub async fn dev(
                 print!("\x1B[2J\x1B[1;1H");
                 println!("{}", style("File modified, updating...").black().bright());
 
-                let (new_index, new_assets) = bundle_function(&function_config, &root, prod)?;
+                let (new_index, new_source_map, new_assets) =
+                    bundle_function(&function_config, &root, prod)?;
 
                 *assets.lock().await = new_assets;
-                *index.lock().await = new_index;
+                *index.lock().await = (new_index, new_source_map);
 
                 isolate_tx
                     .send_async(IsolateEvent::Terminate(String::from("Hot Reload")))
@@ -1,4 +1,5 @@
 mod build;
+mod cron;
 mod deploy;
 mod dev;
 mod link;
@@ -10,6 +11,7 @@ mod rm;
 mod undeploy;
 
 pub use build::build;
+pub use cron::cron_trigger;
 pub use deploy::deploy;
 pub use dev::dev;
 pub use link::link;
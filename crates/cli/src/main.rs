@@ -76,6 +76,18 @@ enum Commands {
         /// Force `process.env.NODE_ENV` to be "production"
         #[clap(visible_alias = "production", long)]
         prod: bool,
+        /// Bind another local dev server as a service binding (e.g `--service AUTH=http://localhost:1235`)
+        #[clap(long = "service", value_name = "NAME=URL")]
+        services: Vec<String>,
+        /// Path to a directory to persist KV data to, instead of keeping it in memory
+        #[clap(long, value_parser)]
+        kv_dir: Option<PathBuf>,
+        /// Path to the SQLite file used by `env.DB` [default: .lagon/db.sqlite]
+        #[clap(long, value_parser)]
+        db: Option<PathBuf>,
+        /// Maximum size of request bodies, in bytes [default: 10485760]
+        #[clap(long)]
+        max_request_body_size: Option<usize>,
     },
     /// Build a Function without deploying it
     Build {
@@ -117,6 +129,24 @@ enum Commands {
         #[clap(value_parser)]
         directory: Option<PathBuf>,
     },
+    /// Manage the Cron schedules of a Function
+    Cron {
+        #[clap(subcommand)]
+        command: CronCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CronCommands {
+    /// Run a Cron schedule of the production Deployment now
+    Trigger {
+        /// Name of the schedule to run
+        #[clap(default_value = "default")]
+        schedule: String,
+        /// Path to a directory containing a Function
+        #[clap(value_parser)]
+        directory: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
@@ -143,6 +173,10 @@ async fn main() {
                 env,
                 allow_code_generation,
                 prod,
+                services,
+                kv_dir,
+                db,
+                max_request_body_size,
             } => {
                 commands::dev(
                     path,
@@ -153,6 +187,10 @@ async fn main() {
                     env,
                     allow_code_generation,
                     prod,
+                    services,
+                    kv_dir,
+                    db,
+                    max_request_body_size,
                 )
                 .await
             }
@@ -171,6 +209,12 @@ async fn main() {
                 deployment_id,
                 directory,
             } => commands::promote(deployment_id, directory).await,
+            Commands::Cron { command } => match command {
+                CronCommands::Trigger {
+                    schedule,
+                    directory,
+                } => commands::cron_trigger(schedule, directory).await,
+            },
         } {
             println!("{} {}", style("✕").red(), err);
             exit(1);
@@ -3,6 +3,7 @@ use super::{
 };
 use crate::utils::{get_theme, print_progress, TrpcClient};
 use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use dialoguer::console::style;
 use dialoguer::{Confirm, Input};
 use pathdiff::diff_paths;
@@ -25,6 +26,8 @@ const ESBUILD: &str = "esbuild.cmd";
 #[cfg(not(windows))]
 const ESBUILD: &str = "esbuild";
 
+const INLINE_SOURCE_MAP: &[u8] = b"//# sourceMappingURL=data:application/json;base64,";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FunctionConfig {
     pub function_id: String,
@@ -224,13 +227,44 @@ pub fn get_function_config_path(root: &Path) -> PathBuf {
     root.join(".lagon").join("config.json")
 }
 
-fn esbuild(file: &Path, root: &Path, prod: bool) -> Result<Vec<u8>> {
+// ESBuild can only write external source maps to a file, so we ask for
+// an inline source map and extract it from the end of the output
+fn split_source_map(mut output: Vec<u8>) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let index = match output
+        .windows(INLINE_SOURCE_MAP.len())
+        .rposition(|window| window == INLINE_SOURCE_MAP)
+    {
+        Some(index) => index,
+        None => return Ok((output, None)),
+    };
+
+    let source_map = std::str::from_utf8(&output[index + INLINE_SOURCE_MAP.len()..])?;
+    let source_map = STANDARD.decode(source_map.trim())?;
+    output.truncate(index);
+
+    Ok((output, Some(source_map)))
+}
+
+fn esbuild(
+    file: &Path,
+    root: &Path,
+    prod: bool,
+    source_map: bool,
+) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
     let node_env = match prod {
         true => "production",
         false => "development",
     };
 
-    let result = Command::new(ESBUILD)
+    let mut command = Command::new(ESBUILD);
+
+    if source_map {
+        command
+            .arg("--sourcemap=inline")
+            .arg("--sources-content=false");
+    }
+
+    let result = command
         .arg(root.join(file))
         .arg(format!("--define:process.env.NODE_ENV=\"{}\"", node_env))
         .arg("--bundle")
@@ -243,7 +277,7 @@ fn esbuild(file: &Path, root: &Path, prod: bool) -> Result<Vec<u8>> {
 
     // TODO: check status code
     if result.status.success() {
-        let output = result.stdout;
+        let (output, source_map) = split_source_map(result.stdout)?;
 
         if output.len() >= MAX_FUNCTION_SIZE_MB {
             return Err(anyhow!(
@@ -252,7 +286,7 @@ fn esbuild(file: &Path, root: &Path, prod: bool) -> Result<Vec<u8>> {
             ));
         }
 
-        return Ok(output);
+        return Ok((output, source_map));
     }
 
     Err(anyhow!(
@@ -266,7 +300,7 @@ pub fn bundle_function(
     function_config: &FunctionConfig,
     root: &Path,
     prod: bool,
-) -> Result<(Vec<u8>, Assets)> {
+) -> Result<(Vec<u8>, Option<Vec<u8>>, Assets)> {
     if let Err(error) = Command::new(ESBUILD).arg("--version").output() {
         return if error.kind() == ErrorKind::NotFound {
             Err(anyhow!(
@@ -281,14 +315,14 @@ pub fn bundle_function(
     }
 
     let end_progress = print_progress("Bundling Function");
-    let index_output = esbuild(&function_config.index, root, prod)?;
+    let (index_output, source_map) = esbuild(&function_config.index, root, prod, true)?;
     end_progress();
 
     let mut final_assets = Assets::new();
 
     if let Some(client) = &function_config.client {
         let end_progress = print_progress("Bundling client file");
-        let client_output = esbuild(client, root, prod)?;
+        let (client_output, _) = esbuild(client, root, prod, false)?;
         end_progress();
 
         let client_path = client.as_path().with_extension("js");
@@ -350,7 +384,7 @@ pub fn bundle_function(
         println!("{}", style("Skipping assets...").black().bright());
     }
 
-    Ok((index_output, final_assets))
+    Ok((index_output, source_map, final_assets))
 }
 
 #[derive(Serialize, Debug)]
@@ -364,6 +398,7 @@ struct Asset {
 struct CreateDeploymentRequest {
     function_id: String,
     function_size: usize,
+    source_map_size: Option<usize>,
     assets: Vec<Asset>,
 }
 
@@ -372,6 +407,7 @@ struct CreateDeploymentRequest {
 struct CreateDeploymentResponse {
     deployment_id: String,
     code_url: String,
+    source_map_url: Option<String>,
     assets_urls: HashMap<String, String>,
 }
 
@@ -395,7 +431,7 @@ pub async fn create_deployment(
     root: &Path,
     prod_bundle: bool,
 ) -> Result<()> {
-    let (index, assets) = bundle_function(function_config, root, prod_bundle)?;
+    let (index, source_map, assets) = bundle_function(function_config, root, prod_bundle)?;
 
     let end_progress = print_progress("Creating Deployment");
 
@@ -410,6 +446,7 @@ pub async fn create_deployment(
             CreateDeploymentRequest {
                 function_id: function_config.function_id.clone(),
                 function_size: index.len(),
+                source_map_size: source_map.as_ref().map(|source_map| source_map.len()),
                 assets: assets
                     .iter()
                     .map(|(key, value)| Asset {
@@ -426,6 +463,7 @@ pub async fn create_deployment(
     let CreateDeploymentResponse {
         deployment_id,
         code_url,
+        source_map_url,
         assets_urls,
     } = response.result.data;
 
@@ -438,6 +476,15 @@ pub async fn create_deployment(
         .send()
         .await?;
 
+    if let (Some(source_map), Some(source_map_url)) = (source_map, source_map_url) {
+        trpc_client
+            .client
+            .request("PUT".parse()?, source_map_url)
+            .body(source_map)
+            .send()
+            .await?;
+    }
+
     let mut join_set = tokio::task::JoinSet::new();
     for (asset, url) in assets_urls {
         let asset = assets
@@ -0,0 +1,204 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_cache::{CacheStorage, CacheStore, CacheStoreOptions};
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::options::IsolateOptions;
+use std::sync::Arc;
+
+mod utils;
+
+fn storage() -> CacheStorage {
+    CacheStorage::new(
+        Arc::new(CacheStore::new(CacheStoreOptions::default())),
+        "deployment".into(),
+    )
+}
+
+#[tokio::test]
+async fn cache_put_match() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    await caches.default.put('https://lagon.app/', new Response('Hello', { status: 201 }));
+
+    const response = await caches.default.match('https://lagon.app/');
+    const missing = await caches.default.match('https://lagon.app/missing');
+
+    return new Response(`${response.status} ${await response.text()} ${missing}`);
+}"
+            .into(),
+        )
+        .cache(Some(storage())),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("201 Hello undefined"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn cache_open() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    const cache = await caches.open('custom');
+    await cache.put('https://lagon.app/', new Response('Hello'));
+
+    const response = await cache.match('https://lagon.app/');
+    const other = await caches.default.match('https://lagon.app/');
+
+    return new Response(`${await response.text()} ${other}`);
+}"
+            .into(),
+        )
+        .cache(Some(storage())),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello undefined"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn cache_delete() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    await caches.default.put('https://lagon.app/', new Response('Hello'));
+
+    const deleted = await caches.default.delete('https://lagon.app/');
+    const deletedAgain = await caches.default.delete('https://lagon.app/');
+    const response = await caches.default.match('https://lagon.app/');
+
+    return new Response(`${deleted} ${deletedAgain} ${response}`);
+}"
+            .into(),
+        )
+        .cache(Some(storage())),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("true false undefined"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn cache_control_and_vary() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    await caches.default.put('https://lagon.app/no-store', new Response('Hello', {
+        headers: { 'cache-control': 'no-store' },
+    }));
+    await caches.default.put(new Request('https://lagon.app/vary', {
+        headers: { 'accept-language': 'en' },
+    }), new Response('Hello', {
+        headers: { 'cache-control': 'max-age=60', vary: 'accept-language' },
+    }));
+
+    const noStore = await caches.default.match('https://lagon.app/no-store');
+    const en = await caches.default.match(new Request('https://lagon.app/vary', {
+        headers: { 'accept-language': 'en' },
+    }));
+    const fr = await caches.default.match(new Request('https://lagon.app/vary', {
+        headers: { 'accept-language': 'fr' },
+    }));
+
+    return new Response(`${noStore} ${en.status} ${fr}`);
+}"
+            .into(),
+        )
+        .cache(Some(storage())),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("undefined 200 undefined"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn cache_put_invalid() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    await caches.default.put(new Request('https://lagon.app/', { method: 'POST' }), new Response('Hello'));
+
+    return new Response('Hello');
+}"
+            .into(),
+        )
+        .cache(Some(storage())),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: Cache only supports GET requests".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn cache_shared_between_isolates() {
+    utils::setup();
+    let storage = storage();
+    let code = "export async function handler() {
+    const previous = await caches.default.match('https://lagon.app/');
+    await caches.default.put('https://lagon.app/', new Response('Hello'));
+
+    return new Response(previous ? await previous.text() : 'None');
+}";
+
+    for body in ["None", "Hello"] {
+        let (send, receiver) =
+            utils::create_isolate(IsolateOptions::new(code.into()).cache(Some(storage.clone())));
+        send(Request::default());
+
+        utils::assert_response(
+            &receiver,
+            Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+            Body::from(body),
+        )
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn cache_not_configured() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export async function handler() {
+    await caches.default.match('https://lagon.app/');
+
+    return new Response('Hello');
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Cache is not available for this Function".into()),
+    )
+    .await;
+}
@@ -0,0 +1,57 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_isolate::options::{ColdStart, IsolateOptions};
+
+mod utils;
+
+const CODE: &str = "export function handler() {
+    return new Response('Hello world');
+}";
+
+async fn run_isolate(code_cache: Option<Vec<u8>>) -> ColdStart {
+    let (cold_start_sender, cold_start_receiver) = flume::unbounded();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(CODE.into())
+            .code_cache(code_cache)
+            .on_cold_start_callback(Box::new(move |_, cold_start| {
+                cold_start_sender.send(cold_start).unwrap();
+            })),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+
+    cold_start_receiver.recv_async().await.unwrap()
+}
+
+#[tokio::test]
+async fn create_code_cache() {
+    utils::setup();
+    let cold_start = run_isolate(None).await;
+
+    assert!(!cold_start.cache_hit);
+    assert!(cold_start.code_cache.is_some());
+}
+
+#[tokio::test]
+async fn consume_code_cache() {
+    utils::setup();
+    let code_cache = run_isolate(None).await.code_cache;
+    let cold_start = run_isolate(code_cache).await;
+
+    assert!(cold_start.cache_hit);
+    assert!(cold_start.code_cache.is_none());
+}
+
+#[tokio::test]
+async fn rejected_code_cache() {
+    utils::setup();
+    let cold_start = run_isolate(Some(vec![0; 32])).await;
+
+    assert!(!cold_start.cache_hit);
+    assert!(cold_start.code_cache.is_some());
+}
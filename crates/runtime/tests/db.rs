@@ -0,0 +1,172 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_db::Database;
+use lagon_runtime_isolate::options::IsolateOptions;
+use std::fs;
+
+mod utils;
+
+fn database(name: &str) -> Database {
+    let path = std::env::temp_dir()
+        .join("lagon-runtime-tests")
+        .join(format!("{name}.sqlite"));
+
+    for extension in ["sqlite", "sqlite-wal", "sqlite-shm"] {
+        fs::remove_file(path.with_extension(extension)).unwrap_or(());
+    }
+
+    Database::new(path)
+}
+
+#[tokio::test]
+async fn db_query() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.DB.exec('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, active BOOLEAN)');
+
+    const { meta } = await env.DB.prepare('INSERT INTO users (name, active) VALUES (?, ?)').bind('John', true).run();
+    await env.DB.prepare('INSERT INTO users (name, active) VALUES (?, ?)').bind('Jane', false).run();
+
+    const { results } = await env.DB.prepare('SELECT * FROM users ORDER BY id').all();
+    const name = await env.DB.prepare('SELECT name FROM users WHERE id = ?').bind(2).first('name');
+    const missing = await env.DB.prepare('SELECT * FROM users WHERE id = ?').bind(3).first();
+    const raw = await env.DB.prepare('SELECT id, name FROM users ORDER BY id').raw();
+
+    return new Response(`${meta.changes} ${meta.last_row_id} ${JSON.stringify(results)} ${name} ${missing} ${JSON.stringify(raw)}`);
+}"
+            .into(),
+        )
+        .db(Some(database("query"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("1 1 [{\"id\":1,\"name\":\"John\",\"active\":1},{\"id\":2,\"name\":\"Jane\",\"active\":0}] Jane null [[1,\"John\"],[2,\"Jane\"]]"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn db_batch() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.DB.exec('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)');
+
+    const insert = env.DB.prepare('INSERT INTO users (name) VALUES (?)');
+    const results = await env.DB.batch([insert.bind('John'), insert.bind('Jane')]);
+
+    let error;
+
+    try {
+        await env.DB.batch([insert.bind('Jack'), insert.bind(null)]);
+    } catch (e) {
+        error = e.message;
+    }
+
+    const { count } = await env.DB.prepare('SELECT COUNT(*) AS count FROM users').first();
+
+    return new Response(`${results.length} ${count} ${error}`);
+}"
+            .into(),
+        )
+        .db(Some(database("batch"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("2 2 NOT NULL constraint failed: users.name"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn db_transaction() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.DB.exec('CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)');
+
+    await env.DB.transaction(async tx => {
+        await tx.prepare('INSERT INTO users (name) VALUES (?)').bind('John').run();
+    });
+
+    try {
+        await env.DB.transaction(async tx => {
+            await tx.prepare('INSERT INTO users (name) VALUES (?)').bind('Jane').run();
+
+            throw new Error('Rollback');
+        });
+    } catch {}
+
+    const { results } = await env.DB.prepare('SELECT name FROM users').all();
+
+    return new Response(results.map(({ name }) => name).join(','));
+}"
+            .into(),
+        )
+        .db(Some(database("transaction"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("John"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn db_invalid_query() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    try {
+        await env.DB.prepare('SELECT * FROM missing').all();
+    } catch (error) {
+        return new Response(`${error instanceof Error} ${error.message.startsWith('no such table: missing')}`);
+    }
+
+    return new Response('Hello');
+}"
+            .into(),
+        )
+        .db(Some(database("invalid_query"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("true true"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn db_not_configured() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export async function handler(request, { env }) {
+    return new Response(`${env.DB}`);
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("undefined"),
+    )
+    .await;
+}
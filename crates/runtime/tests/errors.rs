@@ -1,4 +1,4 @@
-use hyper::Request;
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
 use lagon_runtime_http::RunResult;
 use lagon_runtime_isolate::options::IsolateOptions;
 use std::time::Duration;
@@ -228,3 +228,155 @@ export function handler() {
         RunResult::Error("Uncaught TypeError: a is not a function\n  at test (2:12)\n  at first (6:12)\n  at handler (10:25)".into())
     ).await;
 }
+
+#[tokio::test]
+async fn unhandled_rejection_only_fails_its_request() {
+    utils::setup();
+    let send = utils::create_isolate_with_receivers(IsolateOptions::new(
+        "export async function handler(request) {
+    if (request.url.endsWith('/fail')) {
+        Promise.reject(new Error('Rejected'));
+    }
+
+    await new Promise(resolve => setTimeout(resolve, 100));
+    return new Response('Hello world');
+}"
+        .into(),
+    ));
+    let ok = send(Request::builder().uri("/ok").body(Body::empty()).unwrap());
+    let fail = send(Request::builder().uri("/fail").body(Body::empty()).unwrap());
+
+    utils::assert_run_result(
+        &fail,
+        RunResult::Error("Uncaught Error: Rejected\n  at handler (3:24)".into()),
+    )
+    .await;
+    utils::assert_response(
+        &ok,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn unhandled_rejection_outside_request_logged() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler() {
+    setTimeout(() => {
+        Promise.reject(new Error('Rejected'));
+    }, 100);
+
+    return new Response('Hello world');
+}"
+            .into(),
+        )
+        .log_sender(logs_sender),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+
+    let (level, message, _) = logs_receiver.recv_async().await.unwrap();
+    assert_eq!(level, "error");
+    assert!(message.starts_with("Uncaught Error: Rejected"));
+    assert!(receiver.is_empty());
+}
+
+#[tokio::test]
+async fn tick_timeout_retries_other_requests() {
+    utils::setup();
+    let send = utils::create_isolate_with_receivers(IsolateOptions::new(
+        "export async function handler(request) {
+    if (request.url.endsWith('/loop')) {
+        await new Promise(resolve => setTimeout(resolve, 50));
+        while(true) {}
+    }
+
+    await new Promise(resolve => setTimeout(resolve, 500));
+    return new Response('Should not be reached');
+}"
+        .into(),
+    ));
+    let other = send(
+        Request::builder()
+            .uri("/other")
+            .body(Body::empty())
+            .unwrap(),
+    );
+    let culprit = send(Request::builder().uri("/loop").body(Body::empty()).unwrap());
+
+    utils::assert_run_result(&culprit, RunResult::Timeout).await;
+    utils::assert_run_result(&other, RunResult::Retry).await;
+}
+
+#[tokio::test]
+async fn stacktrace_source_map() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "function a(b) {
+  return b() / 1;
+}
+export function handler() {
+  return new Response(a('a'));
+}"
+            .into(),
+        )
+        .source_map(Some(
+            r#"{
+    "version": 3,
+    "sources": ["src/index.ts"],
+    "names": ["fn", "test"],
+    "mappings": ";SAESA;;;sBAKoBC"
+}"#
+            .into(),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught TypeError: b is not a function\n  at test (src/index.ts:3:10)\n  at handler (src/index.ts:8:30)"
+                .into(),
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn stacktrace_with_environment_variables() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler() {
+    throw new Error('Rejected');
+}"
+            .into(),
+        )
+        .environment_variables(
+            vec![
+                ("FIRST".into(), "first\nline".into()),
+                ("SECOND".into(), "second".into()),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Rejected\n  at handler (2:11)".into()),
+    )
+    .await;
+}
@@ -1,7 +1,14 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use httptest::{matchers::*, responders::*, Expectation, Server};
 use hyper::{header::CONTENT_TYPE, Body, Request, Response};
-use lagon_runtime_http::RunResult;
-use lagon_runtime_isolate::options::IsolateOptions;
+use lagon_runtime_http::{RunResult, StreamResult};
+use lagon_runtime_isolate::{
+    fetch_handler::{FetchHandler, ServiceBindingDepth},
+    fetch_policy::{FetchPolicy, FetchPolicyError},
+    options::IsolateOptions,
+};
+use std::{collections::HashMap, sync::Arc};
 
 mod utils;
 
@@ -308,6 +315,195 @@ async fn response_array_buffer() {
     utils::assert_response(&receiver, Response::builder(), Body::from("Hello, World")).await;
 }
 
+#[tokio::test]
+async fn response_set_cookie_headers() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/")).respond_with(
+            status_code(200)
+                .append_header("set-cookie", "greeting=hello")
+                .append_header("set-cookie", "name=world"),
+        ),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const response = await fetch('{url}');
+
+    return new Response(response.headers.getSetCookie().join(' | '));
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("greeting=hello | name=world"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn request_duplicate_headers() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("GET", "/"),
+            request::headers(contains(("cookie", "a=b"))),
+            request::headers(contains(("cookie", "c=d"))),
+        ])
+        .respond_with(status_code(200)),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const status = (await fetch('{url}', {{
+        headers: [
+            ['Cookie', 'a=b'],
+            ['Cookie', 'c=d'],
+        ],
+    }})).status;
+
+    return new Response(status);
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("200"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn response_binary_body() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/"))
+            .respond_with(status_code(200).body(vec![0, 159, 146, 150, 255, 0, 128])),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const response = await fetch('{url}');
+    const body = await response.arrayBuffer();
+
+    return new Response(body);
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder(),
+        Body::from(vec![0, 159, 146, 150, 255, 0, 128]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn response_body_stream() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/"))
+            .respond_with(status_code(200).body("Hello, World")),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const response = await fetch('{url}');
+    return new Response(response.body);
+}}"
+    )));
+    send(Request::default());
+
+    let mut body = Vec::new();
+
+    loop {
+        match receiver.recv_async().await.unwrap() {
+            RunResult::Stream(StreamResult::Start(_)) => {}
+            RunResult::Stream(StreamResult::Data(bytes)) => body.extend(bytes),
+            RunResult::Stream(StreamResult::Done(_)) => break,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    assert_eq!(body, b"Hello, World");
+}
+
+#[tokio::test]
+async fn response_body_not_read() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/"))
+            .times(2)
+            .respond_with(status_code(200).body("Hello, World")),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const response = await fetch('{url}');
+    return new Response(response.status);
+}}"
+    )));
+
+    for _ in 0..2 {
+        send(Request::default());
+
+        utils::assert_response(
+            &receiver,
+            Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+            Body::from("200"),
+        )
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn request_binary_body() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(all_of![
+            request::method_path("POST", "/"),
+            request::body(eq(vec![0, 159, 146, 150, 255, 0, 128]))
+        ])
+        .respond_with(status_code(200).body("Hello, World")),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const body = await fetch('{url}', {{
+        method: 'POST',
+        body: new Uint8Array([0, 159, 146, 150, 255, 0, 128]),
+    }}).then(res => res.text());
+
+    return new Response(body);
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello, World"),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn throw_invalid_url() {
     utils::setup();
@@ -515,7 +711,9 @@ export async function handler() {{
 
     utils::assert_run_result(
         &receiver,
-        RunResult::Error("Uncaught Error: fetch() can only be called 20 times per requests".into()),
+        RunResult::Error(
+            "Uncaught TypeError: fetch() can only be called 20 times per requests".into(),
+        ),
     )
     .await;
 
@@ -524,7 +722,9 @@ export async function handler() {{
 
     utils::assert_run_result(
         &receiver,
-        RunResult::Error("Uncaught Error: fetch() can only be called 20 times per requests".into()),
+        RunResult::Error(
+            "Uncaught TypeError: fetch() can only be called 20 times per requests".into(),
+        ),
     )
     .await;
 
@@ -675,3 +875,319 @@ async fn fetch_input_request_init() {
     )
     .await;
 }
+
+#[tokio::test]
+async fn fetch_policy_max_calls() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/"))
+            .times(2)
+            .respond_with(status_code(200)),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    await fetch('{url}');
+    await fetch('{url}');
+    await fetch('{url}');
+    return new Response('unreachable');
+}}"
+        ))
+        .fetch_policy(Arc::new(FetchPolicy::default().max_calls(2))),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught TypeError: fetch() can only be called 2 times per requests".into(),
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn fetch_policy_block_private_addresses() {
+    utils::setup();
+    let server = Server::run();
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    await fetch('{url}');
+    return new Response('unreachable');
+}}"
+        ))
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().block_private_addresses(true),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught TypeError: fetch() to private address 127.0.0.1 is not allowed".into(),
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn fetch_policy_denied_hosts() {
+    utils::setup();
+    let server = Server::run();
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    await fetch('{url}');
+    return new Response('unreachable');
+}}"
+        ))
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().denied_hosts(vec!["127.0.0.0/8".parse().unwrap()]),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: fetch() to 127.0.0.1 is denied".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn fetch_policy_allowed_hosts_redirect() {
+    utils::setup();
+    let server = Server::run();
+    let port = server.addr().port();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/")).respond_with(
+            status_code(301)
+                .append_header("Location", format!("http://127.0.0.1:{port}/redirected")),
+        ),
+    );
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    await fetch('http://localhost:{port}/');
+    return new Response('unreachable');
+}}"
+        ))
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().allowed_hosts(vec!["localhost".parse().unwrap()]),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: fetch() to 127.0.0.1 is not allowed".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn fetch_policy_max_response_size() {
+    utils::setup();
+    let server = Server::run();
+    server.expect(
+        Expectation::matching(request::method_path("GET", "/"))
+            .respond_with(status_code(200).body("Hello, World")),
+    );
+    let url = server.url("/");
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    const body = await fetch('{url}').then(res => res.text());
+    return new Response(body);
+}}"
+        ))
+        .fetch_policy(Arc::new(FetchPolicy::default().max_response_size(Some(5)))),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught TypeError: fetch() response body exceeds the maximum size of 5 bytes".into(),
+        ),
+    )
+    .await;
+}
+
+// Answers without going through the network
+struct MockFetchHandler;
+
+#[async_trait]
+impl FetchHandler for MockFetchHandler {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>> {
+        match request.uri().path() {
+            "/error" => Err(anyhow!("Mock error")),
+            "/denied" => Err(FetchPolicyError("Mock denied".into()).into()),
+            path => Ok(Response::builder()
+                .status(201)
+                .header("x-method", request.method().as_str())
+                .body(Body::from(format!("Hello from {path}")))?),
+        }
+    }
+}
+
+#[tokio::test]
+async fn fetch_handler() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler() {
+    const response = await fetch('https://lagon.test/mocked', { method: 'POST' });
+    const body = await response.text();
+
+    return new Response(`${response.status} ${response.headers.get('x-method')} ${body}`);
+}"
+            .into(),
+        )
+        .fetch_handler(Arc::new(MockFetchHandler)),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("201 POST Hello from /mocked"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn fetch_handler_errors() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request) {
+    await fetch(request.url.endsWith('/error') ? 'https://lagon.test/error' : 'https://lagon.test/denied');
+    return new Response('unreachable');
+}"
+            .into(),
+        )
+        .fetch_handler(Arc::new(MockFetchHandler)),
+    );
+    send(
+        Request::builder()
+            .uri("/error")
+            .body(Body::empty())
+            .unwrap(),
+    );
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Mock error".into()),
+    )
+    .await;
+
+    send(
+        Request::builder()
+            .uri("/denied")
+            .body(Body::empty())
+            .unwrap(),
+    );
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: Mock denied".into()),
+    )
+    .await;
+}
+
+// Answers with the number of service bindings traversed
+struct MockServiceBinding;
+
+#[async_trait]
+impl FetchHandler for MockServiceBinding {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let depth = request
+            .extensions()
+            .get::<ServiceBindingDepth>()
+            .map_or(0, |depth| depth.0);
+
+        Ok(Response::new(Body::from(format!(
+            "{} {depth}",
+            request.uri().path()
+        ))))
+    }
+}
+
+#[tokio::test]
+async fn service_binding() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, ctx) {
+    const response = await ctx.env.AUTH.fetch('https://auth/login');
+    return new Response(`${Object.keys(ctx.env)} ${await response.text()}`);
+}"
+            .into(),
+        )
+        .service_bindings(HashMap::from([(
+            "AUTH".into(),
+            Arc::new(MockServiceBinding) as Arc<dyn FetchHandler>,
+        )])),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("AUTH /login 1"),
+    )
+    .await;
+
+    // Requests sent by a service binding increase the depth
+    let mut request = Request::default();
+    request.extensions_mut().insert(ServiceBindingDepth(3));
+    send(request);
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("AUTH /login 4"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn service_binding_skips_fetch_policy() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, ctx) {
+    const response = await ctx.env.AUTH.fetch('http://127.0.0.1/login');
+    return new Response(await response.text());
+}"
+            .into(),
+        )
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().block_private_addresses(true),
+        ))
+        .service_bindings(HashMap::from([(
+            "AUTH".into(),
+            Arc::new(MockServiceBinding) as Arc<dyn FetchHandler>,
+        )])),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("/login 1"),
+    )
+    .await;
+}
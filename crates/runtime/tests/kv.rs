@@ -0,0 +1,186 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::options::IsolateOptions;
+use lagon_runtime_kv::{KvNamespace, KvStore, MemoryKvStore};
+use std::sync::Arc;
+
+mod utils;
+
+fn namespace(name: &str) -> KvNamespace {
+    KvNamespace::new(Arc::new(MemoryKvStore::default()), name.into()).unwrap()
+}
+
+#[tokio::test]
+async fn kv_put_get() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.KV.put('text', 'Hello');
+    await env.KV.put('json', JSON.stringify({ hello: 'world' }), { metadata: { a: 1 } });
+
+    const text = await env.KV.get('text');
+    const json = await env.KV.get('json', 'json');
+    const { metadata } = await env.KV.getWithMetadata('json');
+    const buffer = await env.KV.get('text', { type: 'arrayBuffer' });
+    const missing = await env.KV.get('missing');
+
+    return new Response(`${text} ${json.hello} ${metadata.a} ${buffer.byteLength} ${missing}`);
+}"
+            .into(),
+        )
+        .kv(Some(namespace("function"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world 1 5 null"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn kv_delete() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.KV.put('key', 'value');
+    await env.KV.delete('key');
+
+    return new Response(`${await env.KV.get('key')}`);
+}"
+            .into(),
+        )
+        .kv(Some(namespace("function"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("null"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn kv_list() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    for (const key of ['a', 'user:1', 'user:2', 'user:3']) {
+        await env.KV.put(key, '', { metadata: key });
+    }
+
+    const first = await env.KV.list({ prefix: 'user:', limit: 2 });
+    const second = await env.KV.list({ prefix: 'user:', limit: 2, cursor: first.cursor });
+
+    return new Response(JSON.stringify([first, second]));
+}"
+            .into(),
+        )
+        .kv(Some(namespace("function"))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from(
+            r#"[{"keys":[{"name":"user:1","metadata":"user:1"},{"name":"user:2","metadata":"user:2"}],"list_complete":false,"cursor":"user:2"},{"keys":[{"name":"user:3","metadata":"user:3"}],"list_complete":true}]"#,
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn kv_errors() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.KV.put('', 'value');
+}"
+            .into(),
+        )
+        .kv(Some(namespace("function"))),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: KV key can't be empty".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn kv_expiration_ttl_overflow() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.KV.put('key', 'value', { expirationTtl: Number.MAX_VALUE });
+}"
+            .into(),
+        )
+        .kv(Some(namespace("function"))),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: KV expirationTtl is too large".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn kv_namespaces_are_isolated() {
+    utils::setup();
+    let store: Arc<dyn KvStore> = Arc::new(MemoryKvStore::default());
+    let code = "export async function handler(request, { env }) {
+    const previous = await env.KV.get('key');
+    await env.KV.put('key', 'value');
+
+    return new Response(`${previous}`);
+}";
+
+    for name in ["first", "second"] {
+        let (send, receiver) = utils::create_isolate(
+            IsolateOptions::new(code.into())
+                .kv(KvNamespace::new(Arc::clone(&store), name.into()).ok()),
+        );
+        send(Request::default());
+
+        utils::assert_response(
+            &receiver,
+            Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+            Body::from("null"),
+        )
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn kv_not_configured() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request, { env }) {
+    return new Response(`${env.KV}`);
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("undefined"),
+    )
+    .await;
+}
@@ -0,0 +1,208 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::{options::IsolateOptions, IsolateEvent, IsolateQueue};
+use lagon_runtime_queue::{MemoryQueueStore, Queue, QueueMessage, QueueOutcome};
+use std::sync::Arc;
+
+mod utils;
+
+fn queue() -> Queue {
+    Queue::new(Arc::new(MemoryQueueStore::default()), "function".into()).unwrap()
+}
+
+fn message(id: &str, body: &str) -> QueueMessage {
+    QueueMessage {
+        id: id.into(),
+        body: body.into(),
+        timestamp: 1_690_000_000_000,
+        attempts: 1,
+    }
+}
+
+fn send_batch(
+    isolate_tx: &flume::Sender<IsolateEvent>,
+    messages: Vec<QueueMessage>,
+) -> (
+    flume::Receiver<RunResult>,
+    flume::Receiver<(String, QueueOutcome)>,
+) {
+    let (sender, receiver) = flume::unbounded();
+    let (outcomes_sender, outcomes_receiver) = flume::unbounded();
+
+    isolate_tx
+        .send(IsolateEvent::Queue(IsolateQueue {
+            queue: "function".into(),
+            messages,
+            outcomes: outcomes_sender,
+            sender,
+        }))
+        .unwrap();
+
+    (receiver, outcomes_receiver)
+}
+
+#[tokio::test]
+async fn queue_send() {
+    utils::setup();
+    let queue = queue();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.QUEUE.send({ hello: 'world' });
+    await env.QUEUE.send('text');
+
+    return new Response('Sent');
+}"
+            .into(),
+        )
+        .queue(Some(queue.clone())),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Sent"),
+    )
+    .await;
+
+    assert_eq!(
+        queue
+            .receive(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| message.body)
+            .collect::<Vec<_>>(),
+        [r#"{"hello":"world"}"#, r#""text""#]
+    );
+}
+
+#[tokio::test]
+async fn queue_send_errors() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export async function handler(request, { env }) {
+    await env.QUEUE.send(undefined);
+}"
+            .into(),
+        )
+        .queue(Some(queue())),
+    );
+    send(Request::default());
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught TypeError: Message body must be serializable to JSON".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn queue_not_configured() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request, { env }) {
+    return new Response(`${env.QUEUE}`);
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("undefined"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn queue_batch() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(
+        IsolateOptions::new(
+            "export async function queue(batch) {
+    for (const message of batch.messages) {
+        console.log(`${batch.queue} ${message.id} ${message.body.value} ${message.attempts} ${message.timestamp.getTime()}`);
+    }
+
+    batch.messages[0].retry();
+    batch.messages[1].ack();
+}"
+            .into(),
+        )
+        .log_sender(logs_sender),
+    );
+    let (receiver, outcomes) = send_batch(
+        &isolate_tx,
+        vec![
+            message("1", r#"{"value":"a"}"#),
+            message("2", r#"{"value":"b"}"#),
+        ],
+    );
+
+    for log in [
+        "function 1 a 1 1690000000000",
+        "function 2 b 1 1690000000000",
+    ] {
+        assert_eq!(
+            logs_receiver.recv_async().await.unwrap(),
+            ("log".into(), log.into(), None)
+        );
+    }
+
+    utils::assert_run_result(&receiver, RunResult::Scheduled(Default::default())).await;
+    assert_eq!(
+        outcomes.drain().collect::<Vec<_>>(),
+        [
+            ("1".into(), QueueOutcome::Retry),
+            ("2".into(), QueueOutcome::Ack)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn queue_batch_error() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export async function queue(batch) {
+    batch.ackAll();
+    throw new Error('Failed');
+}"
+        .into(),
+    ));
+    let (receiver, outcomes) = send_batch(&isolate_tx, vec![message("1", "null")]);
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Failed\n  at queue (3:11)".into()),
+    )
+    .await;
+    assert_eq!(
+        outcomes.drain().collect::<Vec<_>>(),
+        [("1".into(), QueueOutcome::Ack)]
+    );
+}
+
+#[tokio::test]
+async fn queue_batch_without_export() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    return new Response('Hello');
+}"
+        .into(),
+    ));
+    let (receiver, _) = send_batch(&isolate_tx, vec![message("1", "null")]);
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught Error: Queue function is not defined or is not a function".into(),
+        ),
+    )
+    .await;
+}
@@ -1,5 +1,7 @@
 use hyper::{
+    body::Bytes,
     header::{CONTENT_TYPE, HOST},
+    http::HeaderValue,
     Body, Method, Request, Response,
 };
 use lagon_runtime_http::{RunResult, StreamResult};
@@ -101,6 +103,66 @@ async fn environment_variables() {
     .await;
 }
 
+#[tokio::test]
+async fn environment_variables_any_value() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler() {
+    return new Response(process.env['MY-KEY'] + process.env.__proto__);
+}"
+            .into(),
+        )
+        .environment_variables(
+            vec![
+                ("MY-KEY".into(), "it's a \"test\"\n\\".into()),
+                (
+                    "__proto__".into(),
+                    "');throw new Error('injected');('".into(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("it's a \"test\"\n\\');throw new Error('injected');('"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn environment_variables_frozen() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler() {
+    process.env.TEST = 'Changed';
+    process.env.OTHER = 'Added';
+    return new Response(`${Object.isFrozen(process.env)} ${process.env.TEST} ${process.env.OTHER}`);
+}"
+            .into(),
+        )
+        .environment_variables(
+            vec![("TEST".into(), "Hello world".into())]
+                .into_iter()
+                .collect(),
+        ),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("true Hello world undefined"),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn get_body_streaming() {
     utils::setup();
@@ -158,6 +220,85 @@ async fn get_body() {
     .await;
 }
 
+#[tokio::test]
+async fn get_body_binary() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export async function handler(request) {
+    return new Response(await request.arrayBuffer());
+}"
+        .into(),
+    ));
+    send(
+        Request::builder()
+            .method(Method::POST)
+            .body(vec![0, 159, 146, 150, 255, 0, 128].into())
+            .unwrap(),
+    );
+
+    utils::assert_response(
+        &receiver,
+        Response::builder(),
+        Body::from(vec![0, 159, 146, 150, 255, 0, 128]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn get_body_chunked() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export async function handler(request) {
+    return new Response(await request.text());
+}"
+        .into(),
+    ));
+    let (mut sender, body) = Body::channel();
+    send(Request::builder().method(Method::POST).body(body).unwrap());
+
+    // Split a multi-byte character (é) across two chunks
+    sender
+        .send_data(Bytes::from(vec![72, 101, 108, 108, 111, 32, 0xC3]))
+        .await
+        .unwrap();
+    sender.send_data(Bytes::from(vec![0xA9, 33])).await.unwrap();
+    drop(sender);
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello é!"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn get_body_not_read() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request) {
+    return new Response(request.method);
+}"
+        .into(),
+    ));
+
+    for _ in 0..2 {
+        send(
+            Request::builder()
+                .method(Method::POST)
+                .body("Hello world".into())
+                .unwrap(),
+        );
+
+        utils::assert_response(
+            &receiver,
+            Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+            Body::from("POST"),
+        )
+        .await;
+    }
+}
+
 #[tokio::test]
 async fn get_input() {
     utils::setup();
@@ -233,6 +374,59 @@ async fn get_headers() {
     .await;
 }
 
+#[tokio::test]
+async fn get_headers_duplicate() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request) {
+    return new Response(request.headers.get('accept'));
+}"
+        .into(),
+    ));
+    send(
+        Request::builder()
+            .header("accept", "text/html")
+            .header("accept", "application/json")
+            .body(Body::empty())
+            .unwrap(),
+    );
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("text/html, application/json"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn get_headers_opaque_bytes() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request) {
+    return new Response(null, {
+        headers: {
+            'x-value': request.headers.get('x-value'),
+        },
+    });
+}"
+        .into(),
+    ));
+    send(
+        Request::builder()
+            .header("x-value", HeaderValue::from_bytes(b"caf\xe9 \xff").unwrap())
+            .body(Body::empty())
+            .unwrap(),
+    );
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header("x-value", HeaderValue::from_bytes(b"caf\xe9 \xff").unwrap()),
+        Body::empty(),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn return_headers() {
     utils::setup();
@@ -285,6 +479,31 @@ async fn return_headers_from_headers_api() {
     .await;
 }
 
+#[tokio::test]
+async fn return_set_cookie_headers() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    const headers = new Headers();
+    headers.append('Set-Cookie', 'greeting=hello');
+    headers.append('Set-Cookie', 'name=world');
+
+    return new Response('Hello world', { headers });
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder()
+            .header("set-cookie", "greeting=hello")
+            .header("set-cookie", "name=world"),
+        Body::from("Hello world"),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn return_status() {
     utils::setup();
@@ -324,6 +543,26 @@ async fn return_uint8array() {
     utils::assert_response(&receiver, Response::builder(), Body::from("Hello world")).await;
 }
 
+#[tokio::test]
+async fn return_binary_body() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    const body = new Uint8Array([0, 159, 146, 150, 255, 0, 128]);
+    return new Response(body);
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder(),
+        Body::from(vec![0, 159, 146, 150, 255, 0, 128]),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn console_log() {
     utils::setup();
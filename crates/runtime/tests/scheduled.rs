@@ -0,0 +1,127 @@
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::{options::IsolateOptions, IsolateEvent, IsolateScheduled};
+
+mod utils;
+
+fn send_scheduled(isolate_tx: &flume::Sender<IsolateEvent>) -> flume::Receiver<RunResult> {
+    let (sender, receiver) = flume::unbounded();
+
+    isolate_tx
+        .send(IsolateEvent::Scheduled(IsolateScheduled {
+            scheduled_time: 1_690_000_000_000,
+            name: "default".into(),
+            cron: "*/5 * * * *".into(),
+            sender,
+        }))
+        .unwrap();
+
+    receiver
+}
+
+#[tokio::test]
+async fn scheduled_event() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(
+        IsolateOptions::new(
+            "export async function scheduled(event) {
+    console.log(`${event.name} ${event.cron} ${event.scheduledTime}`);
+}"
+            .into(),
+        )
+        .log_sender(logs_sender),
+    );
+    let receiver = send_scheduled(&isolate_tx);
+
+    assert_eq!(
+        logs_receiver.recv_async().await.unwrap(),
+        (
+            "log".into(),
+            "default */5 * * * * 1690000000000".into(),
+            None
+        )
+    );
+    utils::assert_run_result(&receiver, RunResult::Scheduled(Default::default())).await;
+}
+
+#[tokio::test]
+async fn scheduled_event_error() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export async function scheduled() {
+    throw new Error('Failed');
+}"
+        .into(),
+    ));
+    let receiver = send_scheduled(&isolate_tx);
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Failed\n  at scheduled (2:11)".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn scheduled_event_env() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export async function scheduled(event, ctx) {
+    if (typeof ctx.waitUntil !== 'function' || typeof ctx.env !== 'object') {
+        throw new Error('Invalid context');
+    }
+}"
+        .into(),
+    ));
+    let receiver = send_scheduled(&isolate_tx);
+
+    utils::assert_run_result(&receiver, RunResult::Scheduled(Default::default())).await;
+}
+
+#[tokio::test]
+async fn scheduled_event_handler_fallback() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    return new Response('Done');
+}"
+        .into(),
+    ));
+    let receiver = send_scheduled(&isolate_tx);
+
+    utils::assert_run_result(&receiver, RunResult::Scheduled(Default::default())).await;
+}
+
+#[tokio::test]
+async fn scheduled_event_handler_fallback_status() {
+    utils::setup();
+    let (_, _, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    return new Response('Not found', { status: 404 });
+}"
+        .into(),
+    ));
+    let receiver = send_scheduled(&isolate_tx);
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error("Uncaught Error: Handler function responded with status 404".into()),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn scheduled_event_without_export() {
+    utils::setup();
+    let (_, _, isolate_tx) =
+        utils::create_isolate_with_sender(IsolateOptions::new("export const a = 1;".into()));
+    let receiver = send_scheduled(&isolate_tx);
+
+    utils::assert_run_result(
+        &receiver,
+        RunResult::Error(
+            "Uncaught Error: Scheduled function is not defined or is not a function".into(),
+        ),
+    )
+    .await;
+}
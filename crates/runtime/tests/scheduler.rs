@@ -0,0 +1,132 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::{
+    options::IsolateOptions, scheduler::Scheduler, Isolate, IsolateEvent, IsolateRequest,
+};
+use std::time::Duration;
+
+mod utils;
+
+// Returns the isolate's sender too, since the isolate
+// is terminated when all its senders are dropped
+fn spawn_isolate(
+    scheduler: &Scheduler,
+    code: &str,
+) -> (flume::Sender<IsolateEvent>, flume::Receiver<RunResult>) {
+    let (request_tx, request_rx) = flume::unbounded();
+    let (sender, receiver) = flume::unbounded();
+    let options = IsolateOptions::new(code.into())
+        .tick_timeout(Duration::from_millis(100))
+        .snapshot_blob(include_bytes!("../../serverless/snapshot.bin"));
+
+    scheduler.spawn(move || Isolate::new(options, request_rx));
+
+    request_tx
+        .send(IsolateEvent::Request(IsolateRequest {
+            request: Request::default().into_parts(),
+            sender,
+        }))
+        .unwrap();
+
+    (request_tx, receiver)
+}
+
+#[tokio::test]
+async fn isolates_share_worker_thread() {
+    utils::setup();
+    let scheduler = Scheduler::new(1);
+
+    let (_slow_sender, slow) = spawn_isolate(
+        &scheduler,
+        "export async function handler() {
+    await new Promise(resolve => setTimeout(resolve, 200));
+    return new Response('slow');
+}",
+    );
+    let (_fast_sender, fast) = spawn_isolate(
+        &scheduler,
+        "export function handler() {
+    return new Response('fast');
+}",
+    );
+
+    // The fast isolate doesn't wait for the slow
+    // one, even if they run on the same thread
+    utils::assert_response(
+        &fast,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("fast"),
+    )
+    .await;
+    assert!(slow.is_empty());
+    utils::assert_response(
+        &slow,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("slow"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn blocked_isolate_terminated_on_shared_thread() {
+    utils::setup();
+    let scheduler = Scheduler::new(1);
+
+    let (_blocked_sender, blocked) = spawn_isolate(
+        &scheduler,
+        "export function handler() {
+    while(true) {}
+    return new Response('unreachable');
+}",
+    );
+    let (_other_sender, other) = spawn_isolate(
+        &scheduler,
+        "export function handler() {
+    return new Response('Hello world');
+}",
+    );
+
+    utils::assert_run_result(&blocked, RunResult::Timeout).await;
+    utils::assert_response(
+        &other,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn blocked_isolate_doesnt_terminate_others_on_shared_thread() {
+    utils::setup();
+    let scheduler = Scheduler::new(1);
+
+    let (_busy_sender, busy) = spawn_isolate(
+        &scheduler,
+        "export async function handler() {
+    await new Promise(resolve => setTimeout(resolve, 500));
+    return new Response('busy');
+}",
+    );
+
+    // Make sure the busy isolate is processing its request
+    // before the other isolate blocks their worker thread
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (_blocked_sender, blocked) = spawn_isolate(
+        &scheduler,
+        "export function handler() {
+    while(true) {}
+    return new Response('unreachable');
+}",
+    );
+
+    // The busy isolate wasn't polled while the other isolate was
+    // blocking the thread, but only the blocked one is terminated
+    utils::assert_run_result(&blocked, RunResult::Timeout).await;
+    utils::assert_response(
+        &busy,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("busy"),
+    )
+    .await;
+}
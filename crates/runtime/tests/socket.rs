@@ -0,0 +1,295 @@
+use futures::{SinkExt, StreamExt};
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_isolate::{fetch_policy::FetchPolicy, options::IsolateOptions};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_tungstenite::tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+
+mod utils;
+
+// Send back everything received on the first connection
+async fn run_echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = [0; 1024];
+
+        while let Ok(size) = stream.read(&mut buffer).await {
+            if size == 0 || stream.write_all(&buffer[..size]).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    port
+}
+
+// Echo the first message, and close the socket on the second one
+async fn run_websocket_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut messages = 0;
+
+        while let Some(Ok(message)) = websocket.next().await {
+            if !message.is_text() && !message.is_binary() {
+                continue;
+            }
+
+            messages += 1;
+
+            if messages == 1 {
+                websocket.send(message).await.unwrap();
+            } else {
+                websocket
+                    .close(Some(CloseFrame {
+                        code: CloseCode::from(4000),
+                        reason: "Bye".into(),
+                    }))
+                    .await
+                    .unwrap_or(());
+            }
+        }
+    });
+
+    port
+}
+
+#[tokio::test]
+async fn socket_connect() {
+    utils::setup();
+    let port = run_echo_server().await;
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const socket = connect({{ hostname: '127.0.0.1', port: {port} }});
+    const writer = socket.writable.getWriter();
+    const reader = socket.readable.getReader();
+
+    await socket.opened;
+    await writer.write(new TextEncoder().encode('Hello'));
+    const {{ value }} = await reader.read();
+    await socket.close();
+
+    return new Response(new TextDecoder().decode(value));
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn socket_connect_string_address() {
+    utils::setup();
+    let port = run_echo_server().await;
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export async function handler() {{
+    const socket = connect('127.0.0.1:{port}');
+    const writer = socket.writable.getWriter();
+    await writer.write('Hello');
+
+    const {{ value }} = await socket.readable.getReader().read();
+    await socket.close();
+
+    return new Response(new TextDecoder().decode(value));
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn socket_connect_invalid_port() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    try {
+        connect({ hostname: '127.0.0.1', port: 70000 });
+    } catch (error) {
+        return new Response(`${error.name}: ${error.message}`);
+    }
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("TypeError: Port must be an integer between 1 and 65535"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn socket_connect_denied_hosts() {
+    utils::setup();
+    let port = run_echo_server().await;
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    try {{
+        await connect('127.0.0.1:{port}').opened;
+    }} catch (error) {{
+        return new Response(`${{error.name}}: ${{error.message}}`);
+    }}
+}}"
+        ))
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().denied_hosts(vec!["127.0.0.0/8".parse().unwrap()]),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("TypeError: connect() to 127.0.0.1 is denied"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn socket_connect_max_calls() {
+    utils::setup();
+    let port = run_echo_server().await;
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export async function handler() {{
+    const socket = connect('127.0.0.1:{port}');
+    await socket.opened;
+
+    try {{
+        await connect('127.0.0.1:{port}').opened;
+    }} catch (error) {{
+        await socket.close();
+
+        return new Response(`${{error.name}}: ${{error.message}}`);
+    }}
+}}"
+        ))
+        .fetch_policy(Arc::new(FetchPolicy::default().max_calls(1))),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("TypeError: connect() can only be called 1 times per requests"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn websocket_client() {
+    utils::setup();
+    let port = run_websocket_server().await;
+
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(format!(
+        "export function handler() {{
+    return new Promise(resolve => {{
+        const messages = [];
+        const socket = new WebSocket('ws://127.0.0.1:{port}');
+
+        socket.addEventListener('open', () => {{
+            socket.send('Hello');
+        }});
+        socket.onmessage = event => {{
+            messages.push(event.data);
+            socket.send('Close');
+        }};
+        socket.addEventListener('close', event => {{
+            resolve(new Response(`${{messages.join(',')}} ${{event.code}} ${{event.reason}}`));
+        }});
+    }});
+}}"
+    )));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello 4000 Bye"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn websocket_client_invalid_url() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    try {
+        new WebSocket('ftp://127.0.0.1');
+    } catch (error) {
+        return new Response(`${error.name}: ${error.message}`);
+    }
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("SyntaxError: Invalid WebSocket URL scheme: ftp:"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn websocket_client_denied_hosts() {
+    utils::setup();
+    let port = run_websocket_server().await;
+
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(format!(
+            "export function handler() {{
+    return new Promise(resolve => {{
+        const socket = new WebSocket('ws://127.0.0.1:{port}');
+
+        socket.addEventListener('error', event => {{
+            resolve(new Response(`${{event.error.name}}: ${{event.error.message}}`));
+        }});
+    }});
+}}"
+        ))
+        .fetch_policy(Arc::new(
+            FetchPolicy::default().denied_hosts(vec!["127.0.0.0/8".parse().unwrap()]),
+        )),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("TypeError: new WebSocket() to 127.0.0.1 is denied"),
+    )
+    .await;
+}
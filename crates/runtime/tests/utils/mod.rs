@@ -46,23 +46,86 @@ pub fn create_isolate(options: IsolateOptions) -> (SendRequest, flume::Receiver<
     });
 
     let send_isolate_event = Box::new(move |req: Request<Body>| {
-        let request_tx = request_tx.clone();
         let sender = sender.clone();
+        let request = req.into_parts();
 
-        tokio::spawn(async move {
-            let (parts, body) = req.into_parts();
-            let body = hyper::body::to_bytes(body).await.unwrap();
-            let request = (parts, body);
-
-            request_tx
-                .send(IsolateEvent::Request(IsolateRequest { request, sender }))
-                .unwrap();
-        });
+        request_tx
+            .send(IsolateEvent::Request(IsolateRequest { request, sender }))
+            .unwrap();
     });
 
     (send_isolate_event, receiver)
 }
 
+// Like `create_isolate`, but also returns the isolate's sender
+// to send other events, e.g WebSocket messages
+#[allow(dead_code)]
+pub fn create_isolate_with_sender(
+    options: IsolateOptions,
+) -> (
+    SendRequest,
+    flume::Receiver<RunResult>,
+    flume::Sender<IsolateEvent>,
+) {
+    let (request_tx, request_rx) = flume::unbounded();
+    let (sender, receiver) = flume::unbounded();
+
+    let handle = Handle::current();
+    std::thread::spawn(move || {
+        handle.block_on(async move {
+            let mut isolate = Isolate::new(
+                options.snapshot_blob(include_bytes!("../../../serverless/snapshot.bin")),
+                request_rx,
+            );
+            isolate.evaluate();
+            isolate.run_event_loop().await;
+        })
+    });
+
+    let isolate_tx = request_tx.clone();
+    let send_isolate_event = Box::new(move |req: Request<Body>| {
+        let sender = sender.clone();
+        let request = req.into_parts();
+
+        request_tx
+            .send(IsolateEvent::Request(IsolateRequest { request, sender }))
+            .unwrap();
+    });
+
+    (send_isolate_event, receiver, isolate_tx)
+}
+
+type SendRequestWithReceiver = Box<dyn Fn(Request<Body>) -> flume::Receiver<RunResult>>;
+
+// Like `create_isolate`, but each request gets its own receiver
+#[allow(dead_code)]
+pub fn create_isolate_with_receivers(options: IsolateOptions) -> SendRequestWithReceiver {
+    let (request_tx, request_rx) = flume::unbounded();
+
+    let handle = Handle::current();
+    std::thread::spawn(move || {
+        handle.block_on(async move {
+            let mut isolate = Isolate::new(
+                options.snapshot_blob(include_bytes!("../../../serverless/snapshot.bin")),
+                request_rx,
+            );
+            isolate.evaluate();
+            isolate.run_event_loop().await;
+        })
+    });
+
+    Box::new(move |req: Request<Body>| {
+        let (sender, receiver) = flume::unbounded();
+        let request = req.into_parts();
+
+        request_tx
+            .send(IsolateEvent::Request(IsolateRequest { request, sender }))
+            .unwrap();
+
+        receiver
+    })
+}
+
 #[allow(dead_code)]
 pub fn create_isolate_without_snapshot(
     options: IsolateOptions,
@@ -80,18 +143,12 @@ pub fn create_isolate_without_snapshot(
     });
 
     let send_isolate_event = Box::new(move |req: Request<Body>| {
-        let request_tx = request_tx.clone();
         let sender = sender.clone();
+        let request = req.into_parts();
 
-        tokio::spawn(async move {
-            let (parts, body) = req.into_parts();
-            let body = hyper::body::to_bytes(body).await.unwrap();
-            let request = (parts, body);
-
-            request_tx
-                .send(IsolateEvent::Request(IsolateRequest { request, sender }))
-                .unwrap();
-        });
+        request_tx
+            .send(IsolateEvent::Request(IsolateRequest { request, sender }))
+            .unwrap();
     });
 
     (send_isolate_event, receiver)
@@ -118,6 +175,23 @@ pub async fn assert_run_result(receiver: &flume::Receiver<RunResult>, run_result
         RunResult::Timeout => {
             assert!(result.is_timeout(), "Expected Timeout, got {:?}", result);
         }
+        RunResult::Retry => {
+            assert!(result.is_retry(), "Expected Retry, got {:?}", result);
+        }
+        RunResult::WebSocket(..) => {
+            assert!(
+                matches!(result, RunResult::WebSocket(..)),
+                "Expected WebSocket, got {:?}",
+                result
+            );
+        }
+        RunResult::Scheduled(_) => {
+            assert!(
+                matches!(result, RunResult::Scheduled(_)),
+                "Expected Scheduled, got {:?}",
+                result
+            );
+        }
         RunResult::Stream(stream_result) => match stream_result {
             StreamResult::Done(_) => {
                 assert!(
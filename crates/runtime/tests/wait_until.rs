@@ -0,0 +1,156 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_isolate::options::IsolateOptions;
+use std::time::Duration;
+
+mod utils;
+
+#[tokio::test]
+async fn wait_until_after_response() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler(request, ctx) {
+    ctx.waitUntil(new Promise(resolve => setTimeout(() => {
+        console.log('done');
+        resolve();
+    }, 100)));
+
+    return new Response('Hello world');
+}"
+            .into(),
+        )
+        .log_sender(logs_sender),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+    assert!(logs_receiver.is_empty());
+    assert_eq!(
+        logs_receiver.recv_async().await.unwrap(),
+        ("log".into(), "done".into(), None)
+    );
+}
+
+#[tokio::test]
+async fn wait_until_outlives_total_timeout() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler(request, ctx) {
+    ctx.waitUntil(new Promise(resolve => setTimeout(resolve, 300)).then(() => {
+        console.log('done');
+    }));
+
+    return new Response('Hello world');
+}"
+            .into(),
+        )
+        .total_timeout(Duration::from_millis(100))
+        .log_sender(logs_sender),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+    assert_eq!(
+        logs_receiver.recv_async().await.unwrap(),
+        ("log".into(), "done".into(), None)
+    );
+    assert!(receiver.is_empty());
+}
+
+#[tokio::test]
+async fn wait_until_timeout() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler(request, ctx) {
+    ctx.waitUntil(new Promise(() => {}));
+
+    return new Response('Hello world');
+}"
+            .into(),
+        )
+        .wait_until_timeout(Duration::from_millis(100))
+        .log_sender(logs_sender),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+    assert_eq!(
+        logs_receiver.recv_async().await.unwrap(),
+        (
+            "warn".into(),
+            "ctx.waitUntil() promises did not settle within 100ms after the response".into(),
+            None
+        )
+    );
+}
+
+#[tokio::test]
+async fn wait_until_rejection_logged() {
+    let (logs_sender, logs_receiver) = flume::unbounded();
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(
+        IsolateOptions::new(
+            "export function handler(request, ctx) {
+    ctx.waitUntil(new Promise((_, reject) => setTimeout(() => reject(new Error('Rejected')), 100)));
+
+    return new Response('Hello world');
+}"
+            .into(),
+        )
+        .log_sender(logs_sender),
+    );
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+
+    let (level, message, _) = logs_receiver.recv_async().await.unwrap();
+    assert_eq!(level, "error");
+    assert!(message.starts_with("Uncaught Error: Rejected"));
+    assert!(receiver.is_empty());
+}
+
+#[tokio::test]
+async fn wait_until_non_promise() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler(request, ctx) {
+    ctx.waitUntil('Hello');
+
+    return new Response('Hello world');
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("Hello world"),
+    )
+    .await;
+}
@@ -0,0 +1,161 @@
+use hyper::{header::CONTENT_TYPE, Body, Request, Response};
+use lagon_runtime_http::{RunResult, WebSocketMessage};
+use lagon_runtime_isolate::{options::IsolateOptions, IsolateEvent};
+
+mod utils;
+
+#[tokio::test]
+async fn websocket_accept() {
+    utils::setup();
+    let (send, receiver, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    const { 0: client, 1: server } = new WebSocketPair();
+
+    server.accept();
+    server.send('Hello');
+    server.addEventListener('message', event => {
+        server.send(`Echo: ${event.data}`);
+    });
+
+    return new Response(null, { status: 101, webSocket: client });
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    let (response_builder, channel) = receiver.recv_async().await.unwrap().as_websocket();
+    let response = response_builder.body(Body::empty()).unwrap();
+
+    assert_eq!(response.status(), 101);
+    assert_eq!(
+        channel.receiver.recv_async().await.unwrap(),
+        WebSocketMessage::Text("Hello".into())
+    );
+
+    isolate_tx
+        .send(IsolateEvent::WebSocket(
+            channel.id,
+            WebSocketMessage::Text("Hi".into()),
+        ))
+        .unwrap();
+
+    assert_eq!(
+        channel.receiver.recv_async().await.unwrap(),
+        WebSocketMessage::Text("Echo: Hi".into())
+    );
+}
+
+#[tokio::test]
+async fn websocket_binary() {
+    utils::setup();
+    let (send, receiver, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    const { 0: client, 1: server } = new WebSocketPair();
+
+    server.accept();
+    server.addEventListener('message', event => {
+        server.send(new Uint8Array(event.data).reverse());
+    });
+
+    return new Response(null, { status: 101, webSocket: client });
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    let (_, channel) = receiver.recv_async().await.unwrap().as_websocket();
+
+    isolate_tx
+        .send(IsolateEvent::WebSocket(
+            channel.id,
+            WebSocketMessage::Binary(vec![1, 2, 3]),
+        ))
+        .unwrap();
+
+    assert_eq!(
+        channel.receiver.recv_async().await.unwrap(),
+        WebSocketMessage::Binary(vec![3, 2, 1])
+    );
+}
+
+#[tokio::test]
+async fn websocket_close() {
+    utils::setup();
+    let (send, receiver, isolate_tx) = utils::create_isolate_with_sender(IsolateOptions::new(
+        "export function handler() {
+    const { 0: client, 1: server } = new WebSocketPair();
+
+    server.accept();
+    server.addEventListener('message', () => {
+        server.close(1000, 'Goodbye');
+    });
+
+    return new Response(null, { status: 101, webSocket: client });
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    let (_, channel) = receiver.recv_async().await.unwrap().as_websocket();
+
+    isolate_tx
+        .send(IsolateEvent::WebSocket(
+            channel.id,
+            WebSocketMessage::Text("Hi".into()),
+        ))
+        .unwrap();
+
+    assert_eq!(
+        channel.receiver.recv_async().await.unwrap(),
+        WebSocketMessage::Close(1000, "Goodbye".into())
+    );
+}
+
+#[tokio::test]
+async fn websocket_without_switching_protocols() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    const { 0: client } = new WebSocketPair();
+
+    try {
+        return new Response(null, { webSocket: client });
+    } catch (error) {
+        return new Response(`${error.name}: ${error.message}`);
+    }
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    utils::assert_response(
+        &receiver,
+        Response::builder().header(CONTENT_TYPE, "text/plain;charset=UTF-8"),
+        Body::from("RangeError: Responses with a WebSocket must have a 101 status code"),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn websocket_server_in_response() {
+    utils::setup();
+    let (send, receiver) = utils::create_isolate(IsolateOptions::new(
+        "export function handler() {
+    const { 1: server } = new WebSocketPair();
+
+    server.accept();
+
+    return new Response(null, { status: 101, webSocket: server });
+}"
+        .into(),
+    ));
+    send(Request::default());
+
+    let result = receiver.recv_async().await.unwrap();
+
+    assert!(
+        matches!(&result, RunResult::Error(error) if error.contains("Only the client end of a WebSocketPair can be returned in a Response")),
+        "Expected an error, got {:?}",
+        result
+    );
+}
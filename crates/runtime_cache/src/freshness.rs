@@ -0,0 +1,90 @@
+use hyper::{
+    header::{AGE, CACHE_CONTROL, EXPIRES, VARY},
+    HeaderMap,
+};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Freshness {
+    NotStorable,
+    // Unix timestamp, in seconds
+    Until(u64),
+    // Responses without any freshness information are
+    // kept until they are evicted or explicitly deleted
+    Unbounded,
+}
+
+fn parse_seconds(value: &str) -> Option<u64> {
+    value.trim_matches('"').parse().ok()
+}
+
+// Follows the rules of shared caches: `s-maxage` takes precedence over
+// `max-age`, which itself takes precedence over `Expires`
+pub(crate) fn get_freshness(headers: &HeaderMap, now: u64) -> Freshness {
+    let mut max_age = None;
+    let mut s_maxage = None;
+
+    for value in headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+    {
+        for directive in value.split(',') {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (directive.trim(), None),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                // We can't revalidate responses, so `no-cache` is the same as `no-store`
+                "no-store" | "no-cache" | "private" => return Freshness::NotStorable,
+                "max-age" => max_age = value.and_then(parse_seconds),
+                "s-maxage" => s_maxage = value.and_then(parse_seconds),
+                _ => {}
+            }
+        }
+    }
+
+    if headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.split(',').any(|name| name.trim() == "*"))
+    {
+        return Freshness::NotStorable;
+    }
+
+    if let Some(max_age) = s_maxage.or(max_age) {
+        let age = headers
+            .get(AGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_seconds)
+            .unwrap_or(0);
+
+        return match max_age.checked_sub(age) {
+            // A TTL too large to be represented is the same as no TTL
+            Some(ttl) if ttl > 0 => now
+                .checked_add(ttl)
+                .map_or(Freshness::Unbounded, Freshness::Until),
+            _ => Freshness::NotStorable,
+        };
+    }
+
+    match headers.get(EXPIRES) {
+        Some(expires) => {
+            // Invalid dates mean that the response is already expired
+            let expires = expires
+                .to_str()
+                .ok()
+                .and_then(|expires| httpdate::parse_http_date(expires).ok())
+                .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                .map(|expires| expires.as_secs());
+
+            match expires {
+                Some(expires) if expires > now => Freshness::Until(expires),
+                _ => Freshness::NotStorable,
+            }
+        }
+        None => Freshness::Unbounded,
+    }
+}
@@ -0,0 +1,617 @@
+use anyhow::Result;
+use freshness::{get_freshness, Freshness};
+use hyper::{
+    body::Bytes,
+    header::{HeaderName, HeaderValue, VARY},
+    HeaderMap,
+};
+use linked_hash_map::LinkedHashMap;
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, sync::Mutex};
+
+mod freshness;
+
+pub const DEFAULT_MAX_MEMORY_SIZE: usize = 16 * 1024 * 1024; // 16MB
+pub const DEFAULT_MAX_DISK_SIZE: usize = 256 * 1024 * 1024; // 256MB
+                                                            // The subdirectory of `root` owned by the store, which is cleared on startup
+pub const CACHE_DIR: &str = "lagon-cache";
+
+#[derive(Debug, Clone)]
+pub struct CacheStoreOptions {
+    // Entries evicted from memory are moved to the `CACHE_DIR`
+    // subdirectory of this directory when set, or dropped otherwise
+    pub root: Option<PathBuf>,
+    // Both sizes are limits per deployment
+    pub max_memory_size: usize,
+    pub max_disk_size: usize,
+}
+
+impl Default for CacheStoreOptions {
+    fn default() -> Self {
+        Self {
+            root: None,
+            max_memory_size: DEFAULT_MAX_MEMORY_SIZE,
+            max_disk_size: DEFAULT_MAX_DISK_SIZE,
+        }
+    }
+}
+
+impl CacheStoreOptions {
+    pub fn root(mut self, root: Option<PathBuf>) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub fn max_memory_size(mut self, max_memory_size: usize) -> Self {
+        self.max_memory_size = max_memory_size;
+        self
+    }
+
+    pub fn max_disk_size(mut self, max_disk_size: usize) -> Self {
+        self.max_disk_size = max_disk_size;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+// Everything needed to match an entry, which is kept
+// in memory even when the body has been moved to disk
+#[derive(Debug, Clone)]
+struct Metadata {
+    status: u16,
+    headers: HeaderMap,
+    // Values of the request headers listed in the response's `Vary` header
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    // Unix timestamp, in seconds
+    expiration: Option<u64>,
+}
+
+impl Metadata {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expiration
+            .map_or(false, |expiration| expiration <= now)
+    }
+
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    fn size(&self) -> usize {
+        self.headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum()
+    }
+}
+
+struct MemoryEntry {
+    metadata: Metadata,
+    body: Bytes,
+}
+
+impl MemoryEntry {
+    fn size(&self) -> usize {
+        self.metadata.size() + self.body.len()
+    }
+}
+
+struct DiskEntry {
+    metadata: Metadata,
+    path: PathBuf,
+    size: usize,
+}
+
+// The entries of a single deployment, in least recently used order
+#[derive(Default)]
+struct Partition {
+    memory: LinkedHashMap<String, MemoryEntry>,
+    memory_size: usize,
+    disk: LinkedHashMap<String, DiskEntry>,
+    disk_size: usize,
+}
+
+impl Partition {
+    fn metadata(&self, key: &str) -> Option<&Metadata> {
+        self.memory
+            .get(key)
+            .map(|entry| &entry.metadata)
+            .or_else(|| self.disk.get(key).map(|entry| &entry.metadata))
+    }
+
+    async fn remove(&mut self, key: &str) -> Result<bool> {
+        if let Some(entry) = self.memory.remove(key) {
+            self.memory_size -= entry.size();
+            return Ok(true);
+        }
+
+        if let Some(entry) = self.disk.remove(key) {
+            self.disk_size -= entry.size;
+            remove_file(&entry.path).await?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+}
+
+// A node-local store shared by all the isolates, so entries survive
+// isolate restarts. Each deployment gets its own partition, with its
+// own size limits, which is purged when the deployment is removed
+pub struct CacheStore {
+    options: CacheStoreOptions,
+    partitions: std::sync::Mutex<HashMap<String, Arc<Mutex<Partition>>>>,
+    next_file_id: AtomicU64,
+}
+
+impl CacheStore {
+    pub fn new(mut options: CacheStoreOptions) -> Self {
+        options.root = options.root.map(|root| root.join(CACHE_DIR));
+
+        // Entries on disk are only indexed in memory, so
+        // the ones left by a previous process are unusable
+        if let Some(root) = &options.root {
+            std::fs::remove_dir_all(root).unwrap_or(());
+        }
+
+        Self {
+            options,
+            partitions: std::sync::Mutex::new(HashMap::new()),
+            next_file_id: AtomicU64::new(0),
+        }
+    }
+
+    fn partition(&self, deployment_id: &str) -> Arc<Mutex<Partition>> {
+        let mut partitions = self.partitions.lock().unwrap();
+
+        Arc::clone(partitions.entry(deployment_id.to_string()).or_default())
+    }
+
+    fn partition_root(&self, deployment_id: &str) -> Option<PathBuf> {
+        self.options
+            .root
+            .as_ref()
+            .map(|root| root.join(deployment_id))
+    }
+
+    pub async fn purge(&self, deployment_id: &str) -> Result<()> {
+        let partition = self.partitions.lock().unwrap().remove(deployment_id);
+
+        if let Some(partition) = partition {
+            // Wait for the pending operations to finish before removing the files
+            let _partition = partition.lock().await;
+
+            if let Some(root) = self.partition_root(deployment_id) {
+                match fs::remove_dir_all(root).await {
+                    Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The caches of a single deployment, exposed as `caches` to its isolates.
+// `caches.default` and each cache opened with `caches.open()` share
+// the same partition, but are matched separately using their name
+#[derive(Clone)]
+pub struct CacheStorage {
+    store: Arc<CacheStore>,
+    deployment_id: String,
+}
+
+impl CacheStorage {
+    pub fn new(store: Arc<CacheStore>, deployment_id: String) -> Self {
+        Self {
+            store,
+            deployment_id,
+        }
+    }
+
+    pub async fn get(
+        &self,
+        cache_name: &str,
+        url: &str,
+        headers: &HeaderMap,
+    ) -> Result<Option<CachedResponse>> {
+        let key = get_key(cache_name, url);
+        let partition = self.store.partition(&self.deployment_id);
+        let mut partition = partition.lock().await;
+
+        let (is_expired, matches) = match partition.metadata(&key) {
+            Some(metadata) => (metadata.is_expired(now()), metadata.matches(headers)),
+            None => return Ok(None),
+        };
+
+        if is_expired {
+            partition.remove(&key).await?;
+            return Ok(None);
+        }
+
+        if !matches {
+            return Ok(None);
+        }
+
+        if let Some(entry) = partition.memory.get_refresh(&key) {
+            return Ok(Some(CachedResponse {
+                status: entry.metadata.status,
+                headers: entry.metadata.headers.clone(),
+                body: entry.body.clone(),
+            }));
+        }
+
+        // The entry is on disk, move it back to memory since it's used again
+        let entry = partition.disk.remove(&key).unwrap();
+        partition.disk_size -= entry.size;
+
+        let body = Bytes::from(fs::read(&entry.path).await?);
+        remove_file(&entry.path).await?;
+
+        let response = CachedResponse {
+            status: entry.metadata.status,
+            headers: entry.metadata.headers.clone(),
+            body: body.clone(),
+        };
+
+        self.insert(
+            &mut partition,
+            key,
+            MemoryEntry {
+                metadata: entry.metadata,
+                body,
+            },
+        )
+        .await?;
+
+        Ok(Some(response))
+    }
+
+    // Responses that can't be stored according to their
+    // `Cache-Control` and `Expires` headers are ignored
+    pub async fn put(
+        &self,
+        cache_name: &str,
+        url: &str,
+        request_headers: &HeaderMap,
+        response: CachedResponse,
+    ) -> Result<()> {
+        let expiration = match get_freshness(&response.headers, now()) {
+            Freshness::NotStorable => return Ok(()),
+            Freshness::Until(expiration) => Some(expiration),
+            Freshness::Unbounded => None,
+        };
+
+        let vary = response
+            .headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .map(|name| {
+                let value = request_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+
+        let key = get_key(cache_name, url);
+        let partition = self.store.partition(&self.deployment_id);
+        let mut partition = partition.lock().await;
+
+        partition.remove(&key).await?;
+
+        self.insert(
+            &mut partition,
+            key,
+            MemoryEntry {
+                metadata: Metadata {
+                    status: response.status,
+                    headers: response.headers,
+                    vary,
+                    expiration,
+                },
+                body: response.body,
+            },
+        )
+        .await
+    }
+
+    pub async fn delete(&self, cache_name: &str, url: &str, headers: &HeaderMap) -> Result<bool> {
+        let key = get_key(cache_name, url);
+        let partition = self.store.partition(&self.deployment_id);
+        let mut partition = partition.lock().await;
+
+        match partition.metadata(&key) {
+            Some(metadata) if metadata.matches(headers) => partition.remove(&key).await,
+            _ => Ok(false),
+        }
+    }
+
+    async fn insert(
+        &self,
+        partition: &mut Partition,
+        key: String,
+        entry: MemoryEntry,
+    ) -> Result<()> {
+        partition.memory_size += entry.size();
+        partition.memory.insert(key, entry);
+
+        while partition.memory_size > self.store.options.max_memory_size {
+            match partition.memory.pop_front() {
+                Some((key, entry)) => {
+                    partition.memory_size -= entry.size();
+                    self.spill(partition, key, entry).await?;
+                }
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    // Moves an entry evicted from memory to disk, evicting
+    // the least recently used entries on disk if needed
+    async fn spill(
+        &self,
+        partition: &mut Partition,
+        key: String,
+        entry: MemoryEntry,
+    ) -> Result<()> {
+        let max_disk_size = self.store.options.max_disk_size;
+        let size = entry.size();
+
+        let root = match self.store.partition_root(&self.deployment_id) {
+            Some(root) if size <= max_disk_size => root,
+            _ => return Ok(()),
+        };
+
+        while partition.disk_size + size > max_disk_size {
+            match partition.disk.pop_front() {
+                Some((_, entry)) => {
+                    partition.disk_size -= entry.size;
+                    remove_file(&entry.path).await?;
+                }
+                None => break,
+            }
+        }
+
+        fs::create_dir_all(&root).await?;
+
+        let id = self.store.next_file_id.fetch_add(1, Ordering::Relaxed);
+        let path = root.join(id.to_string());
+
+        fs::write(&path, &entry.body).await?;
+
+        partition.disk_size += size;
+        partition.disk.insert(
+            key,
+            DiskEntry {
+                metadata: entry.metadata,
+                path,
+                size,
+            },
+        );
+
+        Ok(())
+    }
+}
+
+// URLs can't contain new lines, so keys of different caches can't collide
+fn get_key(cache_name: &str, url: &str) -> String {
+    format!("{cache_name}\n{url}")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+async fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path).await {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{ACCEPT_LANGUAGE, CACHE_CONTROL, EXPIRES};
+
+    fn storage(options: CacheStoreOptions) -> CacheStorage {
+        CacheStorage::new(Arc::new(CacheStore::new(options)), "deployment".into())
+    }
+
+    fn response(headers: &[(HeaderName, &str)], body: &'static str) -> CachedResponse {
+        let mut header_map = HeaderMap::new();
+
+        for (name, value) in headers {
+            header_map.insert(name, value.parse().unwrap());
+        }
+
+        CachedResponse {
+            status: 200,
+            headers: header_map,
+            body: Bytes::from(body),
+        }
+    }
+
+    #[tokio::test]
+    async fn put_get_delete() {
+        let storage = storage(CacheStoreOptions::default());
+        let headers = HeaderMap::new();
+
+        storage
+            .put(
+                "default",
+                "https://a.com/",
+                &headers,
+                response(&[], "Hello"),
+            )
+            .await
+            .unwrap();
+
+        let response = storage
+            .get("default", "https://a.com/", &headers)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "Hello");
+
+        assert!(storage
+            .get("other", "https://a.com/", &headers)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert!(storage
+            .delete("default", "https://a.com/", &headers)
+            .await
+            .unwrap());
+        assert!(storage
+            .get("default", "https://a.com/", &headers)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn not_storable() {
+        let storage = storage(CacheStoreOptions::default());
+        let headers = HeaderMap::new();
+
+        for response in [
+            response(&[(CACHE_CONTROL, "no-store")], ""),
+            response(&[(CACHE_CONTROL, "public, max-age=0")], ""),
+            response(&[(EXPIRES, "Thu, 01 Jan 1970 00:00:00 GMT")], ""),
+            response(&[(VARY, "*")], ""),
+        ] {
+            storage
+                .put("default", "https://a.com/", &headers, response)
+                .await
+                .unwrap();
+
+            assert!(storage
+                .get("default", "https://a.com/", &headers)
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn freshness() {
+        let shared = response(&[(CACHE_CONTROL, "max-age=60, s-maxage=120")], "");
+        assert_eq!(get_freshness(&shared.headers, 1000), Freshness::Until(1120));
+
+        let overflow = response(&[(CACHE_CONTROL, "max-age=18446744073709551615")], "");
+        assert_eq!(get_freshness(&overflow.headers, 1000), Freshness::Unbounded);
+
+        let expires = response(&[(EXPIRES, "Thu, 01 Jan 1970 00:16:40 GMT")], "");
+        assert_eq!(get_freshness(&expires.headers, 100), Freshness::Until(1000));
+
+        assert_eq!(get_freshness(&HeaderMap::new(), 0), Freshness::Unbounded);
+    }
+
+    #[tokio::test]
+    async fn vary() {
+        let storage = storage(CacheStoreOptions::default());
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_LANGUAGE, "en".parse().unwrap());
+
+        storage
+            .put(
+                "default",
+                "https://a.com/",
+                &headers,
+                response(&[(VARY, "Accept-Language")], "Hello"),
+            )
+            .await
+            .unwrap();
+
+        assert!(storage
+            .get("default", "https://a.com/", &headers)
+            .await
+            .unwrap()
+            .is_some());
+
+        headers.insert(ACCEPT_LANGUAGE, "fr".parse().unwrap());
+        assert!(storage
+            .get("default", "https://a.com/", &headers)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn spill_to_disk_and_purge() {
+        let root = std::env::temp_dir().join("lagon-runtime-cache-spill");
+        let store = Arc::new(CacheStore::new(
+            CacheStoreOptions::default()
+                .root(Some(root.clone()))
+                .max_memory_size(8)
+                .max_disk_size(8),
+        ));
+        let storage = CacheStorage::new(Arc::clone(&store), "deployment".into());
+        let headers = HeaderMap::new();
+
+        for (url, body) in [("https://a.com/1", "first"), ("https://a.com/2", "second")] {
+            storage
+                .put("default", url, &headers, response(&[], body))
+                .await
+                .unwrap();
+        }
+
+        // "first" was moved to disk when "second" was put, and
+        // is moved back to memory in place of "second" when used
+        for (url, body) in [("https://a.com/1", "first"), ("https://a.com/2", "second")] {
+            let response = storage
+                .get("default", url, &headers)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.body, body);
+        }
+
+        store.purge("deployment").await.unwrap();
+        assert!(!root.join(CACHE_DIR).join("deployment").exists());
+        assert!(storage
+            .get("default", "https://a.com/2", &headers)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn only_clears_its_directory() {
+        let root = std::env::temp_dir().join("lagon-runtime-cache-root");
+        std::fs::create_dir_all(root.join(CACHE_DIR)).unwrap();
+        std::fs::write(root.join(CACHE_DIR).join("entry"), "entry").unwrap();
+        std::fs::write(root.join("file"), "file").unwrap();
+
+        CacheStore::new(CacheStoreOptions::default().root(Some(root.clone())));
+
+        assert!(!root.join(CACHE_DIR).exists());
+        assert!(root.join("file").exists());
+    }
+}
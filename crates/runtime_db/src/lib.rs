@@ -0,0 +1,564 @@
+use anyhow::{anyhow, Result};
+use rusqlite::{params_from_iter, types::ValueRef, Batch, Connection, InterruptHandle};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+pub use rusqlite::types::Value;
+
+// How long to wait for another connection to release its lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+// How often a query that exceeded its deadline is interrupted, until it stops
+const INTERRUPT_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+// Rows are returned as arrays of values, in the same order as `columns`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    pub changes: u64,
+    pub last_row_id: i64,
+    // In milliseconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExecResult {
+    pub count: usize,
+    // In milliseconds
+    pub duration: f64,
+}
+
+struct Handle {
+    connection: Mutex<Connection>,
+    interrupt: InterruptHandle,
+    // The query running on the connection, which is the one holding its lock.
+    // Interrupting a query holds this lock, so that the query can't finish and
+    // be replaced by another one in the meantime
+    running: Mutex<Option<u64>>,
+    next_query_id: AtomicU64,
+}
+
+impl Handle {
+    fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        // Allows reading while another connection is writing
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        connection.pragma_update(None, "foreign_keys", "ON")?;
+
+        Ok(Self {
+            interrupt: connection.get_interrupt_handle(),
+            connection: Mutex::new(connection),
+            running: Mutex::new(None),
+            next_query_id: AtomicU64::new(1),
+        })
+    }
+}
+
+struct Transaction {
+    handle: Arc<Handle>,
+    // The request that started the transaction
+    owner: u32,
+}
+
+// A SQLite database stored in a single file. The connection is opened on the
+// first query, so Functions that never use their database don't create a file
+#[derive(Clone)]
+pub struct Database {
+    path: PathBuf,
+    handle: Arc<Mutex<Option<Arc<Handle>>>>,
+    // Each transaction uses its own connection, so queries
+    // made by concurrent requests are never part of it
+    transactions: Arc<Mutex<HashMap<u32, Transaction>>>,
+    next_transaction_id: Arc<AtomicU32>,
+}
+
+impl Database {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            handle: Arc::new(Mutex::new(None)),
+            transactions: Arc::new(Mutex::new(HashMap::new())),
+            next_transaction_id: Arc::new(AtomicU32::new(1)),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn handle(&self, transaction: Option<u32>) -> Result<Arc<Handle>> {
+        if let Some(id) = transaction {
+            return self
+                .transactions
+                .lock()
+                .unwrap()
+                .get(&id)
+                .map(|transaction| Arc::clone(&transaction.handle))
+                .ok_or_else(|| anyhow!("Transaction has already been committed or rolled back"));
+        }
+
+        let mut handle = self.handle.lock().unwrap();
+
+        match handle.as_ref() {
+            Some(handle) => Ok(Arc::clone(handle)),
+            None => {
+                let new_handle = Arc::new(Handle::open(&self.path)?);
+                *handle = Some(Arc::clone(&new_handle));
+
+                Ok(new_handle)
+            }
+        }
+    }
+
+    // Runs a closure on a blocking thread, interrupting the running
+    // query if it's still running once the deadline is reached
+    async fn run<T, F>(&self, transaction: Option<u32>, deadline: Instant, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+    {
+        let handle = self.handle(transaction)?;
+        let task_handle = Arc::clone(&handle);
+        let id = handle.next_query_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut task = tokio::task::spawn_blocking(move || {
+            let connection = task_handle.connection.lock().unwrap();
+            *task_handle.running.lock().unwrap() = Some(id);
+
+            // The query might have been waiting for another one to finish. This
+            // is checked once it's marked as running, so reaching the deadline
+            // after the check always interrupts it
+            let result = match Instant::now() >= deadline {
+                true => Err(timeout_error()),
+                false => f(&connection),
+            };
+
+            *task_handle.running.lock().unwrap() = None;
+
+            result
+        });
+
+        match tokio::time::timeout_at(deadline.into(), &mut task).await {
+            Ok(result) => result?,
+            Err(_) => {
+                let interrupt = move || {
+                    if *handle.running.lock().unwrap() == Some(id) {
+                        handle.interrupt.interrupt();
+                    }
+                };
+
+                interrupt();
+
+                // Interrupting a query that is about to start is a no-op,
+                // so we keep interrupting it until the task is finished
+                tokio::spawn(async move {
+                    while !task.is_finished() {
+                        tokio::time::sleep(INTERRUPT_INTERVAL).await;
+                        interrupt();
+                    }
+                });
+
+                Err(timeout_error())
+            }
+        }
+    }
+
+    pub async fn query(
+        &self,
+        transaction: Option<u32>,
+        statement: Statement,
+        deadline: Instant,
+    ) -> Result<QueryResult> {
+        self.run(transaction, deadline, move |connection| {
+            query(connection, &statement)
+        })
+        .await
+    }
+
+    // Either all the statements are applied, or none of them
+    pub async fn batch(
+        &self,
+        transaction: Option<u32>,
+        statements: Vec<Statement>,
+        deadline: Instant,
+    ) -> Result<Vec<QueryResult>> {
+        self.run(transaction, deadline, move |connection| {
+            // Savepoints can be nested inside a transaction, unlike `BEGIN`
+            connection.execute_batch("SAVEPOINT batch")?;
+
+            match statements
+                .iter()
+                .map(|statement| query(connection, statement))
+                .collect::<Result<Vec<_>>>()
+            {
+                Ok(results) => {
+                    connection.execute_batch("RELEASE batch")?;
+                    Ok(results)
+                }
+                Err(error) => {
+                    connection.execute_batch("ROLLBACK TO batch; RELEASE batch")?;
+                    Err(error)
+                }
+            }
+        })
+        .await
+    }
+
+    // Executes one or more statements separated by semicolons, without parameters
+    pub async fn exec(
+        &self,
+        transaction: Option<u32>,
+        sql: String,
+        deadline: Instant,
+    ) -> Result<ExecResult> {
+        self.run(transaction, deadline, move |connection| {
+            let start = Instant::now();
+            let mut batch = Batch::new(connection, &sql);
+            let mut count = 0;
+
+            while let Some(mut statement) = batch.next()? {
+                let mut rows = statement.raw_query();
+                while rows.next()?.is_some() {}
+
+                count += 1;
+            }
+
+            Ok(ExecResult {
+                count,
+                duration: get_duration(start),
+            })
+        })
+        .await
+    }
+
+    pub async fn begin(&self, owner: u32) -> Result<u32> {
+        let path = self.path.clone();
+
+        let handle = tokio::task::spawn_blocking(move || -> Result<Handle> {
+            let handle = Handle::open(&path)?;
+            handle.connection.lock().unwrap().execute_batch("BEGIN")?;
+
+            Ok(handle)
+        })
+        .await??;
+
+        let id = self.next_transaction_id.fetch_add(1, Ordering::Relaxed);
+
+        self.transactions.lock().unwrap().insert(
+            id,
+            Transaction {
+                handle: Arc::new(handle),
+                owner,
+            },
+        );
+
+        Ok(id)
+    }
+
+    pub async fn commit(&self, id: u32, deadline: Instant) -> Result<()> {
+        self.end(id, "COMMIT", deadline).await
+    }
+
+    pub async fn rollback(&self, id: u32, deadline: Instant) -> Result<()> {
+        self.end(id, "ROLLBACK", deadline).await
+    }
+
+    async fn end(&self, id: u32, sql: &'static str, deadline: Instant) -> Result<()> {
+        let result = self
+            .run(Some(id), deadline, move |connection| {
+                Ok(connection.execute_batch(sql)?)
+            })
+            .await;
+
+        // Closing the connection rolls back the transaction if it failed
+        self.transactions.lock().unwrap().remove(&id);
+
+        result
+    }
+
+    // Rolls back the transactions of the requests that are
+    // finished without having committed or rolled them back
+    pub fn retain_transactions(&self, is_running: impl Fn(u32) -> bool) {
+        self.transactions
+            .lock()
+            .unwrap()
+            .retain(|_, transaction| is_running(transaction.owner));
+    }
+}
+
+fn query(connection: &Connection, statement: &Statement) -> Result<QueryResult> {
+    let start = Instant::now();
+    let mut prepared = connection.prepare(&statement.sql)?;
+    let is_readonly = prepared.readonly();
+    let columns = prepared
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut rows = Vec::new();
+    let mut result = prepared.query(params_from_iter(&statement.params))?;
+
+    while let Some(row) = result.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+
+        for index in 0..columns.len() {
+            values.push(value_to_json(row.get_ref(index)?));
+        }
+
+        rows.push(values);
+    }
+
+    Ok(QueryResult {
+        columns,
+        rows,
+        changes: match is_readonly {
+            true => 0,
+            false => connection.changes(),
+        },
+        last_row_id: connection.last_insert_rowid(),
+        duration: get_duration(start),
+    })
+}
+
+// Blobs are returned as arrays of bytes
+fn value_to_json(value: ValueRef) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(integer) => integer.into(),
+        ValueRef::Real(real) => real.into(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+        ValueRef::Blob(blob) => blob.iter().copied().collect(),
+    }
+}
+
+fn get_duration(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn timeout_error() -> anyhow::Error {
+    anyhow!("Database query exceeded the timeout")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn database(name: &str) -> Database {
+        let path = std::env::temp_dir()
+            .join("lagon-runtime-db")
+            .join(format!("{name}.sqlite"));
+
+        for extension in ["sqlite", "sqlite-wal", "sqlite-shm"] {
+            fs::remove_file(path.with_extension(extension)).unwrap_or(());
+        }
+
+        Database::new(path)
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(5)
+    }
+
+    fn statement(sql: &str, params: Vec<Value>) -> Statement {
+        Statement {
+            sql: sql.into(),
+            params,
+        }
+    }
+
+    #[tokio::test]
+    async fn query_and_exec() {
+        let database = database("query_and_exec");
+
+        let result = database
+            .exec(
+                None,
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT); INSERT INTO users (name) VALUES ('John')".into(),
+                deadline(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.count, 2);
+
+        let result = database
+            .query(
+                None,
+                statement(
+                    "INSERT INTO users (name) VALUES (?)",
+                    vec![Value::Text("Jane".into())],
+                ),
+                deadline(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.changes, 1);
+        assert_eq!(result.last_row_id, 2);
+
+        let result = database
+            .query(
+                None,
+                statement("SELECT * FROM users ORDER BY id", Vec::new()),
+                deadline(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.columns, ["id", "name"]);
+        assert_eq!(
+            result.rows,
+            [vec![json!(1), json!("John")], vec![json!(2), json!("Jane")]]
+        );
+        assert_eq!(result.changes, 0);
+    }
+
+    #[tokio::test]
+    async fn batch_is_atomic() {
+        let database = database("batch_is_atomic");
+
+        database
+            .exec(
+                None,
+                "CREATE TABLE a (id INTEGER PRIMARY KEY)".into(),
+                deadline(),
+            )
+            .await
+            .unwrap();
+
+        assert!(database
+            .batch(
+                None,
+                vec![
+                    statement("INSERT INTO a (id) VALUES (1)", Vec::new()),
+                    statement("INSERT INTO a (id) VALUES (1)", Vec::new()),
+                ],
+                deadline(),
+            )
+            .await
+            .is_err());
+
+        let result = database
+            .query(None, statement("SELECT * FROM a", Vec::new()), deadline())
+            .await
+            .unwrap();
+        assert!(result.rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn transactions() {
+        let database = database("transactions");
+
+        database
+            .exec(
+                None,
+                "CREATE TABLE a (id INTEGER PRIMARY KEY)".into(),
+                deadline(),
+            )
+            .await
+            .unwrap();
+
+        let id = database.begin(1).await.unwrap();
+        database
+            .exec(Some(id), "INSERT INTO a (id) VALUES (1)".into(), deadline())
+            .await
+            .unwrap();
+        database.rollback(id, deadline()).await.unwrap();
+
+        let id = database.begin(1).await.unwrap();
+        database
+            .exec(Some(id), "INSERT INTO a (id) VALUES (2)".into(), deadline())
+            .await
+            .unwrap();
+        database.commit(id, deadline()).await.unwrap();
+
+        assert!(database.commit(id, deadline()).await.is_err());
+
+        let result = database
+            .query(None, statement("SELECT * FROM a", Vec::new()), deadline())
+            .await
+            .unwrap();
+        assert_eq!(result.rows, [vec![json!(2)]]);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let database = database("timeout");
+
+        let result = database
+            .query(
+                None,
+                statement(
+                    "WITH RECURSIVE numbers(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM numbers) SELECT count(*) FROM numbers",
+                    Vec::new(),
+                ),
+                Instant::now() + Duration::from_millis(50),
+            )
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Database query exceeded the timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout_waiting_for_connection() {
+        let database = database("timeout_waiting_for_connection");
+        let handle = database.handle(None).unwrap();
+
+        // Another query holds the connection until right before the deadline
+        let blocking = std::thread::spawn(move || {
+            let _connection = handle.connection.lock().unwrap();
+            std::thread::sleep(Duration::from_millis(45));
+        });
+
+        let result = database
+            .query(
+                None,
+                statement(
+                    "WITH RECURSIVE numbers(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM numbers) SELECT count(*) FROM numbers",
+                    Vec::new(),
+                ),
+                Instant::now() + Duration::from_millis(50),
+            )
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Database query exceeded the timeout"
+        );
+        blocking.join().unwrap();
+
+        // The query doesn't keep the connection once it timed out
+        let result = database
+            .query(
+                None,
+                statement("SELECT 1", Vec::new()),
+                Instant::now() + Duration::from_secs(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.rows, [vec![json!(1)]]);
+    }
+}
@@ -18,15 +18,39 @@ pub enum StreamResult {
     Done(Duration),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Vec<u8>),
+    // The close code and reason
+    Close(u16, String),
+}
+
+// A WebSocket accepted by a Function. Messages sent by the Function are
+// received on `receiver`, and messages sent by the client are sent back
+// to the isolate with the id of the socket
+#[derive(Debug)]
+pub struct WebSocketChannel {
+    pub id: u32,
+    pub receiver: flume::Receiver<WebSocketMessage>,
+}
+
 #[derive(Debug)]
 pub enum RunResult {
     // Isolate responses have a duration (cpu time)
     // Assets responses don't
     Response(Builder, Body, Option<Duration>),
     Stream(StreamResult),
+    // A `101 Switching Protocols` response, upgrading the connection
+    WebSocket(Builder, WebSocketChannel),
+    // A scheduled or queue event completed, with its duration (cpu time)
+    Scheduled(Duration),
     Timeout,
     MemoryLimit,
     Error(String),
+    // The isolate was terminated because of another request
+    // or was shut down, before this request could complete
+    Retry,
 }
 
 impl RunResult {
@@ -38,6 +62,10 @@ impl RunResult {
         matches!(self, RunResult::MemoryLimit)
     }
 
+    pub fn is_retry(&self) -> bool {
+        matches!(self, RunResult::Retry)
+    }
+
     pub fn as_error(self) -> String {
         if let RunResult::Error(error) = self {
             return error;
@@ -54,6 +82,14 @@ impl RunResult {
         panic!("RunResult is not a Response: {:?}", self);
     }
 
+    pub fn as_websocket(self) -> (Builder, WebSocketChannel) {
+        if let RunResult::WebSocket(response_builder, channel) = self {
+            return (response_builder, channel);
+        }
+
+        panic!("RunResult is not a WebSocket: {:?}", self);
+    }
+
     pub fn as_stream_done(self) -> bool {
         if let RunResult::Stream(StreamResult::Done(_)) = self {
             return true;
@@ -1,25 +1,25 @@
 use anyhow::{anyhow, Result};
-use hyper::{body::Bytes, http::request::Parts, Body, Method, Request};
+use hyper::{http::request::Parts, Body, Method, Request};
 use lagon_runtime_v8_utils::{
-    extract_v8_headers_object, extract_v8_string, v8_headers_object, v8_string,
+    extract_v8_headers_object, extract_v8_string, extract_v8_uint8array, v8_headers_object,
+    v8_string,
 };
 
 pub fn request_to_v8<'a>(
-    request: (Parts, Bytes),
+    parts: Parts,
+    body_stream_id: Option<u32>,
     scope: &mut v8::HandleScope<'a>,
 ) -> v8::Local<'a, v8::Object> {
-    let body_empty = request.1.is_empty();
-    let len = if body_empty { 3 } else { 4 };
+    let len = if body_stream_id.is_none() { 3 } else { 4 };
 
     let mut names = Vec::with_capacity(len);
     let mut values = Vec::with_capacity(len);
 
-    let host = request
-        .0
+    let host = parts
         .headers
         .get("host")
         .map_or("", |host| host.to_str().unwrap_or(""));
-    let uri = request.0.uri.to_string();
+    let uri = parts.uri.to_string();
     let uri = uri.as_str();
 
     let mut url = String::with_capacity(8 + host.len() + uri.len());
@@ -27,7 +27,7 @@ pub fn request_to_v8<'a>(
     url.push_str(host);
     url.push_str(uri);
 
-    let method = request.0.method.as_str();
+    let method = parts.method.as_str();
 
     names.push(v8_string(scope, "i").into());
     values.push(v8_string(scope, &url).into());
@@ -35,13 +35,14 @@ pub fn request_to_v8<'a>(
     names.push(v8_string(scope, "m").into());
     values.push(v8_string(scope, method).into());
 
-    if !body_empty {
+    // The body is streamed from the isolate using its id
+    if let Some(body_stream_id) = body_stream_id {
         names.push(v8_string(scope, "b").into());
-        values.push(v8_string(scope, unsafe { std::str::from_utf8_unchecked(&request.1) }).into());
+        values.push(v8::Integer::new_from_unsigned(scope, body_stream_id).into());
     }
 
     names.push(v8_string(scope, "h").into());
-    values.push(v8_headers_object(scope, request.0.headers).into());
+    values.push(v8_headers_object(scope, parts.headers).into());
 
     let null = v8::null(scope);
     v8::Object::with_prototype_and_properties(scope, null.into(), &names, &values)
@@ -63,7 +64,7 @@ pub fn request_from_v8<'a>(
 
     if let Some(body_value) = request.get(scope, body_key.into()) {
         if !body_value.is_null_or_undefined() {
-            body = Body::from(extract_v8_string(body_value, scope)?);
+            body = Body::from(extract_v8_uint8array(body_value)?);
         }
     }
 
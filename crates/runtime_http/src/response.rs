@@ -1,20 +1,23 @@
 use anyhow::{anyhow, Result};
-use hyper::{body::Bytes, http::response::Builder, Body, HeaderMap, Response};
+use hyper::{http::response::Builder, Body, HeaderMap, Response};
 use lagon_runtime_v8_utils::{
-    extract_v8_headers_object, extract_v8_integer, extract_v8_string, v8_headers_object,
+    extract_v8_headers_object, extract_v8_integer, extract_v8_uint8array, v8_headers_object,
     v8_integer, v8_string,
 };
 
 pub fn response_to_v8<'a>(
-    response: (u16, HeaderMap, Bytes),
+    response: (u16, HeaderMap, Option<u32>),
     scope: &mut v8::HandleScope<'a>,
 ) -> v8::Local<'a, v8::Object> {
-    let len = 3;
+    let len = if response.2.is_none() { 2 } else { 3 };
     let mut names = Vec::with_capacity(len);
     let mut values = Vec::with_capacity(len);
 
-    names.push(v8_string(scope, "b").into());
-    values.push(v8_string(scope, unsafe { std::str::from_utf8_unchecked(&response.2) }).into());
+    // The body is streamed from the isolate using its id
+    if let Some(body_stream_id) = response.2 {
+        names.push(v8_string(scope, "b").into());
+        values.push(v8::Integer::new_from_unsigned(scope, body_stream_id).into());
+    }
 
     names.push(v8_string(scope, "s").into());
     values.push(v8_integer(scope, response.0.into()).into());
@@ -72,7 +75,7 @@ pub fn response_from_v8<'a>(
         Some(body_value) => match body_value.is_null_or_undefined() {
             true => Ok((response_builder, Body::empty(), true)),
             false => {
-                let body = extract_v8_string(body_value, scope)?;
+                let body = extract_v8_uint8array(body_value)?;
 
                 Ok((response_builder, body.into(), false))
             }
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use hyper::{body, Body, HeaderMap, Response};
+use lagon_runtime_cache::{CacheStorage, CachedResponse};
+use lagon_runtime_http::{request_from_v8, response_from_v8};
+use lagon_runtime_v8_utils::extract_v8_string;
+
+use crate::{bindings::PromiseResult, Isolate};
+
+use super::BindingResult;
+
+fn get_storage(scope: &mut v8::HandleScope) -> Result<CacheStorage> {
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    state
+        .cache
+        .clone()
+        .ok_or_else(|| anyhow!("Cache is not available for this Function"))
+}
+
+// Only the URL and headers of requests are used to match entries
+fn extract_request(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<(String, HeaderMap)> {
+    let request = request_from_v8(scope, value)?;
+    let url = request.uri().to_string();
+    let (parts, _) = request.into_parts();
+
+    Ok((url, parts.headers))
+}
+
+type RequestArg = (CacheStorage, String, String, HeaderMap);
+
+fn extract_request_arg(
+    scope: &mut v8::HandleScope,
+    args: &v8::FunctionCallbackArguments,
+) -> Result<RequestArg> {
+    let storage = get_storage(scope)?;
+    let cache_name = extract_v8_string(args.get(0), scope)?;
+    let (url, headers) = extract_request(args.get(1), scope)?;
+
+    Ok((storage, cache_name, url, headers))
+}
+
+type MatchArg = (u32, RequestArg);
+
+pub fn cache_match_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<MatchArg> {
+    // Needed to register the body of the response
+    let id = scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value());
+
+    Ok((id, extract_request_arg(scope, &args)?))
+}
+
+pub async fn cache_match_binding(id: usize, arg: MatchArg) -> BindingResult {
+    let (request_id, (storage, cache_name, url, headers)) = arg;
+
+    let result = match storage.get(&cache_name, &url, &headers).await {
+        Ok(Some(response)) => {
+            let body = match response.body.is_empty() {
+                true => None,
+                false => Some(Body::from(response.body)),
+            };
+
+            PromiseResult::Response(request_id, (response.status, response.headers, body))
+        }
+        Ok(None) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+type PutArg = (RequestArg, Response<Body>);
+
+pub fn cache_put_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<PutArg> {
+    let request = extract_request_arg(scope, &args)?;
+    let (response_builder, body, _) = response_from_v8(scope, args.get(2))?;
+
+    Ok((request, response_builder.body(body)?))
+}
+
+pub async fn cache_put_binding(id: usize, arg: PutArg) -> BindingResult {
+    let ((storage, cache_name, url, headers), response) = arg;
+    let (parts, body) = response.into_parts();
+
+    let result = match body::to_bytes(body).await {
+        Ok(body) => {
+            let response = CachedResponse {
+                status: parts.status.as_u16(),
+                headers: parts.headers,
+                body,
+            };
+
+            match storage.put(&cache_name, &url, &headers, response).await {
+                Ok(()) => PromiseResult::Undefined,
+                Err(error) => PromiseResult::Error(error.to_string()),
+            }
+        }
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn cache_delete_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<RequestArg> {
+    extract_request_arg(scope, &args)
+}
+
+pub async fn cache_delete_binding(id: usize, arg: RequestArg) -> BindingResult {
+    let (storage, cache_name, url, headers) = arg;
+
+    let result = match storage.delete(&cache_name, &url, &headers).await {
+        Ok(deleted) => PromiseResult::Boolean(deleted),
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
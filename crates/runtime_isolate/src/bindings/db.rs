@@ -0,0 +1,272 @@
+use anyhow::{anyhow, Result};
+use lagon_runtime_db::{Database, Statement, Value};
+use lagon_runtime_v8_utils::{
+    extract_v8_string, extract_v8_uint32, extract_v8_uint8array, v8_string,
+};
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::{bindings::PromiseResult, Isolate};
+
+use super::BindingResult;
+
+// The largest integer that can be represented exactly as a JS number
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+fn get_request_id(scope: &mut v8::HandleScope) -> u32 {
+    scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value())
+}
+
+// Queries are interrupted when the request that made them times out,
+// or when the grace period of `ctx.waitUntil()` ends after the response
+fn get_database(scope: &mut v8::HandleScope) -> Result<(Database, Instant)> {
+    let request_id = get_request_id(scope);
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    let database = state
+        .db
+        .clone()
+        .ok_or_else(|| anyhow!("Database is not available for this Function"))?;
+    let deadline = match state.handler_results.get(&request_id) {
+        Some(handler_result) => match handler_result.response_time {
+            Some(response_time) => response_time + state.wait_until_timeout,
+            None => handler_result.start_time + state.total_timeout,
+        },
+        None => Instant::now() + state.total_timeout,
+    };
+
+    Ok((database, deadline))
+}
+
+fn extract_transaction(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<Option<u32>> {
+    match value.is_null_or_undefined() {
+        true => Ok(None),
+        false => Ok(Some(extract_v8_uint32(scope, value)?)),
+    }
+}
+
+fn extract_value(value: v8::Local<v8::Value>, scope: &mut v8::HandleScope) -> Result<Value> {
+    if value.is_null_or_undefined() {
+        return Ok(Value::Null);
+    }
+
+    if value.is_boolean() {
+        return Ok(Value::Integer(value.boolean_value(scope) as i64));
+    }
+
+    if value.is_big_int() {
+        return match value.to_big_int(scope).map(|value| value.i64_value()) {
+            Some((value, true)) => Ok(Value::Integer(value)),
+            _ => Err(anyhow!("BigInt value is too large")),
+        };
+    }
+
+    if value.is_number() {
+        let number = value.number_value(scope).unwrap_or(f64::NAN);
+
+        return match number.fract() == 0.0 && number.abs() <= MAX_SAFE_INTEGER {
+            true => Ok(Value::Integer(number as i64)),
+            false => Ok(Value::Real(number)),
+        };
+    }
+
+    if value.is_string() {
+        return Ok(Value::Text(extract_v8_string(value, scope)?));
+    }
+
+    if value.is_uint8_array() {
+        return Ok(Value::Blob(extract_v8_uint8array(value)?));
+    }
+
+    Err(anyhow!("Unsupported value type"))
+}
+
+// Statements are objects with the SQL query as `q`, and its parameters as `p`
+fn extract_statement(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<Statement> {
+    let statement = match value.to_object(scope) {
+        Some(statement) => statement,
+        None => return Err(anyhow!("Statement is not an object")),
+    };
+
+    let sql_key = v8_string(scope, "q");
+    let sql = match statement.get(scope, sql_key.into()) {
+        Some(sql) => extract_v8_string(sql, scope)?,
+        None => return Err(anyhow!("Could not find query")),
+    };
+
+    let params_key = v8_string(scope, "p");
+    let mut params = Vec::new();
+
+    if let Some(values) = statement.get(scope, params_key.into()) {
+        if let Ok(values) = v8::Local::<v8::Array>::try_from(values) {
+            for index in 0..values.length() {
+                if let Some(value) = values.get_index(scope, index) {
+                    params.push(extract_value(value, scope)?);
+                }
+            }
+        }
+    }
+
+    Ok(Statement { sql, params })
+}
+
+fn to_json_result<T: Serialize>(result: Result<T>) -> PromiseResult {
+    match result.and_then(|result| Ok(serde_json::to_string(&result)?)) {
+        Ok(json) => PromiseResult::Json(json),
+        Err(error) => PromiseResult::Error(error.to_string()),
+    }
+}
+
+type QueryArg = (Database, Instant, Option<u32>, Statement);
+
+pub fn db_query_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<QueryArg> {
+    let (database, deadline) = get_database(scope)?;
+    let transaction = extract_transaction(args.get(0), scope)?;
+    let statement = extract_statement(args.get(1), scope)?;
+
+    Ok((database, deadline, transaction, statement))
+}
+
+pub async fn db_query_binding(id: usize, arg: QueryArg) -> BindingResult {
+    let (database, deadline, transaction, statement) = arg;
+
+    BindingResult {
+        id,
+        result: to_json_result(database.query(transaction, statement, deadline).await),
+    }
+}
+
+type BatchArg = (Database, Instant, Option<u32>, Vec<Statement>);
+
+pub fn db_batch_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<BatchArg> {
+    let (database, deadline) = get_database(scope)?;
+    let transaction = extract_transaction(args.get(0), scope)?;
+
+    let values = match v8::Local::<v8::Array>::try_from(args.get(1)) {
+        Ok(values) => values,
+        Err(_) => return Err(anyhow!("Statements must be an array")),
+    };
+    let mut statements = Vec::with_capacity(values.length() as usize);
+
+    for index in 0..values.length() {
+        if let Some(value) = values.get_index(scope, index) {
+            statements.push(extract_statement(value, scope)?);
+        }
+    }
+
+    Ok((database, deadline, transaction, statements))
+}
+
+pub async fn db_batch_binding(id: usize, arg: BatchArg) -> BindingResult {
+    let (database, deadline, transaction, statements) = arg;
+
+    BindingResult {
+        id,
+        result: to_json_result(database.batch(transaction, statements, deadline).await),
+    }
+}
+
+type ExecArg = (Database, Instant, Option<u32>, String);
+
+pub fn db_exec_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<ExecArg> {
+    let (database, deadline) = get_database(scope)?;
+    let transaction = extract_transaction(args.get(0), scope)?;
+    let sql = extract_v8_string(args.get(1), scope)?;
+
+    Ok((database, deadline, transaction, sql))
+}
+
+pub async fn db_exec_binding(id: usize, arg: ExecArg) -> BindingResult {
+    let (database, deadline, transaction, sql) = arg;
+
+    BindingResult {
+        id,
+        result: to_json_result(database.exec(transaction, sql, deadline).await),
+    }
+}
+
+pub fn db_begin_init(
+    scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+) -> Result<(Database, u32)> {
+    let (database, _) = get_database(scope)?;
+    let request_id = get_request_id(scope);
+
+    Ok((database, request_id))
+}
+
+pub async fn db_begin_binding(id: usize, arg: (Database, u32)) -> BindingResult {
+    let (database, request_id) = arg;
+
+    BindingResult {
+        id,
+        result: to_json_result(database.begin(request_id).await),
+    }
+}
+
+type EndArg = (Database, Instant, u32);
+
+fn extract_end_arg(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<EndArg> {
+    let (database, deadline) = get_database(scope)?;
+    let transaction = extract_v8_uint32(scope, args.get(0))?;
+
+    Ok((database, deadline, transaction))
+}
+
+pub fn db_commit_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<EndArg> {
+    extract_end_arg(scope, args)
+}
+
+pub async fn db_commit_binding(id: usize, arg: EndArg) -> BindingResult {
+    let (database, deadline, transaction) = arg;
+
+    let result = match database.commit(transaction, deadline).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn db_rollback_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<EndArg> {
+    extract_end_arg(scope, args)
+}
+
+pub async fn db_rollback_binding(id: usize, arg: EndArg) -> BindingResult {
+    let (database, deadline, transaction) = arg;
+
+    let result = match database.rollback(transaction, deadline).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
@@ -1,16 +1,21 @@
 use anyhow::{anyhow, Result};
-use hyper::{Body, Request};
+use futures::StreamExt;
+use hyper::{body::HttpBody, header::CONTENT_LENGTH, Body, Request};
 use lagon_runtime_http::request_from_v8;
-use reqwest::{redirect::Policy, Client, ClientBuilder};
-use std::sync::OnceLock;
+use lagon_runtime_v8_utils::extract_v8_string;
+use reqwest::Url;
+use std::{error::Error, sync::Arc};
 
-use crate::{bindings::PromiseResult, Isolate};
+use crate::{
+    bindings::PromiseResult,
+    fetch_handler::{FetchHandler, ServiceBindingDepth},
+    fetch_policy::{FetchPolicy, FetchPolicyError},
+    Isolate,
+};
 
 use super::BindingResult;
 
-static CLIENT: OnceLock<Client> = OnceLock::new();
-
-type Arg = Request<Body>;
+type Arg = (u32, Request<Body>, Arc<FetchPolicy>, Arc<dyn FetchHandler>);
 
 pub fn fetch_init(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArguments) -> Result<Arg> {
     let id = scope
@@ -18,79 +23,134 @@ pub fn fetch_init(scope: &mut v8::HandleScope, args: v8::FunctionCallbackArgumen
         .to_uint32(scope)
         .map_or(0, |value| value.value());
 
+    // Requests sent with `ctx.env.<NAME>.fetch()` go
+    // through the service binding instead of the network
+    let service_binding = match args.get(1) {
+        value if value.is_string() => Some(extract_v8_string(value, scope)?),
+        _ => None,
+    };
+
     let state = Isolate::state(scope);
-    let fetch_calls = {
+    let (fetch_calls, service_binding_depth, policy, handler) = {
         let mut state = state.borrow_mut();
+        let policy = Arc::clone(&state.fetch_policy);
+        let handler = match &service_binding {
+            Some(name) => match state.service_bindings.get(name) {
+                Some(handler) => Arc::clone(handler),
+                None => {
+                    return Err(
+                        FetchPolicyError(format!("Service binding {name} doesn't exist")).into(),
+                    )
+                }
+            },
+            None => Arc::clone(&state.fetch_handler),
+        };
 
         if let Some(mut handler_result) = state.handler_results.get_mut(&id) {
             handler_result.context.fetch_calls += 1;
-            handler_result.context.fetch_calls
+            (
+                handler_result.context.fetch_calls,
+                handler_result.context.service_binding_depth,
+                policy,
+                handler,
+            )
         } else {
-            0
+            (0, 0, policy, handler)
         }
     };
 
-    if fetch_calls > 20 {
-        return Err(anyhow!("fetch() can only be called 20 times per requests"));
-    }
+    policy.check_calls("fetch()", fetch_calls)?;
 
     let request = match args.get(0).to_object(scope) {
         Some(request) => request,
         None => return Err(anyhow!("Invalid request")),
     };
 
-    request_from_v8(scope, request.into())
+    let mut request = request_from_v8(scope, request.into())?;
+
+    if service_binding.is_some() {
+        request
+            .extensions_mut()
+            .insert(ServiceBindingDepth(service_binding_depth + 1));
+    } else if let Ok(url) = Url::parse(&request.uri().to_string()) {
+        // Invalid URLs are reported by reqwest when sending the request
+        policy.check_url(&url)?;
+    }
+
+    Ok((id, request, policy, handler))
 }
 
 pub async fn fetch_binding(id: usize, arg: Arg) -> BindingResult {
-    let client = CLIENT.get_or_init(|| {
-        ClientBuilder::new()
-            .use_rustls_tls()
-            .redirect(Policy::custom(|attempt| {
-                if attempt.previous().len() >= 5 {
-                    attempt.error("Too many redirects")
-                } else {
-                    attempt.follow()
-                }
-            }))
-            .build()
-            .unwrap()
-    });
-
-    let (parts, body) = arg.into_parts();
-
-    match client
-        .request(parts.method.into(), parts.uri.to_string())
-        .headers(parts.headers)
-        .body(body)
-        .send()
-        .await
-    {
+    let (request_id, request, policy, handler) = arg;
+
+    match handler.fetch(request).await {
         Ok(response) => {
-            let status = response.status().as_u16();
-            let headers = response.headers().clone();
+            let (parts, body) = response.into_parts();
+            let status = parts.status.as_u16();
 
-            let bytes = match response.bytes().await {
-                Ok(bytes) => bytes,
-                Err(error) => {
+            let content_length = parts
+                .headers
+                .get(CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .or_else(|| body.size_hint().exact());
+
+            if let (Some(content_length), Some(max_response_size)) =
+                (content_length, policy.max_response_size)
+            {
+                if content_length > max_response_size as u64 {
                     return BindingResult {
                         id,
-                        result: PromiseResult::Error(format!(
-                            "Failed to read response body: {}",
-                            error
-                        )),
-                    }
+                        result: PromiseResult::TypeError(response_size_error(max_response_size)),
+                    };
                 }
+            }
+
+            // Resolve as soon as we have the headers, the body is
+            // then streamed chunk by chunk when read from JS
+            let body = match body.size_hint().exact() {
+                Some(0) => None,
+                _ => match policy.max_response_size {
+                    Some(max_response_size) => {
+                        // The content length might be missing or wrong,
+                        // so we also count the bytes while streaming
+                        let mut size = 0;
+
+                        Some(Body::wrap_stream(body.map(
+                            move |chunk| -> Result<_, Box<dyn Error + Send + Sync>> {
+                                let chunk = chunk?;
+                                size += chunk.len();
+
+                                if size > max_response_size {
+                                    return Err(FetchPolicyError(response_size_error(
+                                        max_response_size,
+                                    ))
+                                    .into());
+                                }
+
+                                Ok(chunk)
+                            },
+                        )))
+                    }
+                    None => Some(body),
+                },
             };
 
             BindingResult {
                 id,
-                result: PromiseResult::Response((status, headers, bytes)),
+                result: PromiseResult::Response(request_id, (status, parts.headers, body)),
             }
         }
         Err(error) => BindingResult {
             id,
-            result: PromiseResult::Error(error.without_url().to_string()),
+            result: match error.downcast_ref::<FetchPolicyError>() {
+                Some(policy_error) => PromiseResult::TypeError(policy_error.to_string()),
+                None => PromiseResult::Error(error.to_string()),
+            },
         },
     }
 }
+
+fn response_size_error(max_response_size: usize) -> String {
+    format!("fetch() response body exceeds the maximum size of {max_response_size} bytes")
+}
@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+use lagon_runtime_kv::{
+    ExpirationTtlTooLarge, KvNamespace, ListOptions, PutOptions, MAX_LIST_LIMIT,
+};
+use lagon_runtime_v8_utils::{extract_v8_string, extract_v8_uint8array};
+
+use crate::{bindings::PromiseResult, Isolate};
+
+use super::BindingResult;
+
+fn get_namespace(scope: &mut v8::HandleScope) -> Result<KvNamespace> {
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    state
+        .kv
+        .clone()
+        .ok_or_else(|| anyhow!("KV is not available for this Function"))
+}
+
+// `undefined` and `null` are both used for missing optional arguments
+fn extract_optional_string(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<Option<String>> {
+    match value.is_null_or_undefined() {
+        true => Ok(None),
+        false => Ok(Some(extract_v8_string(value, scope)?)),
+    }
+}
+
+fn extract_optional_number(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<Option<u64>> {
+    if value.is_null_or_undefined() {
+        return Ok(None);
+    }
+
+    match value.to_number(scope) {
+        Some(number) if number.value().is_finite() && number.value() >= 0.0 => {
+            Ok(Some(number.value() as u64))
+        }
+        _ => Err(anyhow!("Value is not a positive number")),
+    }
+}
+
+type KeyArg = (KvNamespace, String);
+
+pub fn kv_get_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<KeyArg> {
+    let namespace = get_namespace(scope)?;
+    let key = extract_v8_string(args.get(0), scope)?;
+
+    Ok((namespace, key))
+}
+
+pub async fn kv_get_binding(id: usize, arg: KeyArg) -> BindingResult {
+    let (namespace, key) = arg;
+
+    let result = match namespace.get(&key).await {
+        Ok(entry) => PromiseResult::KvEntry(entry),
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+type PutArg = (KvNamespace, String, Vec<u8>, PutOptions);
+
+pub fn kv_put_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<PutArg> {
+    let namespace = get_namespace(scope)?;
+    let key = extract_v8_string(args.get(0), scope)?;
+    let value = extract_v8_uint8array(args.get(1))?;
+    let options = PutOptions {
+        metadata: extract_optional_string(args.get(2), scope)?,
+        expiration: extract_optional_number(args.get(3), scope)?,
+        expiration_ttl: extract_optional_number(args.get(4), scope)?,
+    };
+
+    Ok((namespace, key, value, options))
+}
+
+pub async fn kv_put_binding(id: usize, arg: PutArg) -> BindingResult {
+    let (namespace, key, value, options) = arg;
+
+    let result = match namespace.put(&key, value, options).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) if error.is::<ExpirationTtlTooLarge>() => {
+            PromiseResult::TypeError(error.to_string())
+        }
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn kv_delete_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<KeyArg> {
+    let namespace = get_namespace(scope)?;
+    let key = extract_v8_string(args.get(0), scope)?;
+
+    Ok((namespace, key))
+}
+
+pub async fn kv_delete_binding(id: usize, arg: KeyArg) -> BindingResult {
+    let (namespace, key) = arg;
+
+    let result = match namespace.delete(&key).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+type ListArg = (KvNamespace, ListOptions);
+
+pub fn kv_list_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<ListArg> {
+    let namespace = get_namespace(scope)?;
+    let options = ListOptions {
+        prefix: extract_optional_string(args.get(0), scope)?.unwrap_or_default(),
+        limit: extract_optional_number(args.get(1), scope)?
+            .map_or(MAX_LIST_LIMIT, |limit| limit as usize),
+        cursor: extract_optional_string(args.get(2), scope)?,
+    };
+
+    Ok((namespace, options))
+}
+
+pub async fn kv_list_binding(id: usize, arg: ListArg) -> BindingResult {
+    let (namespace, options) = arg;
+
+    let result = match namespace.list(options).await {
+        Ok(list) => match serde_json::to_string(&list) {
+            Ok(list) => PromiseResult::Json(list),
+            Err(error) => PromiseResult::Error(error.to_string()),
+        },
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
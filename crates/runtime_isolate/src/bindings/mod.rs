@@ -1,4 +1,8 @@
-use crate::Isolate;
+use crate::{fetch_policy::FetchPolicyError, Isolate};
+use cache::{
+    cache_delete_binding, cache_delete_init, cache_match_binding, cache_match_init,
+    cache_put_binding, cache_put_init,
+};
 use compression::{
     compression_create_binding, compression_finish_binding, compression_write_binding,
 };
@@ -11,21 +15,51 @@ use crypto::{
 use crypto::{
     derive_bits_binding, derive_bits_init, digest_init, generate_key_binding, generate_key_init,
 };
+use db::{
+    db_batch_binding, db_batch_init, db_begin_binding, db_begin_init, db_commit_binding,
+    db_commit_init, db_exec_binding, db_exec_init, db_query_binding, db_query_init,
+    db_rollback_binding, db_rollback_init,
+};
 use fetch::{fetch_binding, fetch_init};
-use hyper::{body::Bytes, HeaderMap};
-use lagon_runtime_http::response_to_v8;
+use hyper::{Body, HeaderMap};
+use kv::{
+    kv_delete_binding, kv_delete_init, kv_get_binding, kv_get_init, kv_list_binding, kv_list_init,
+    kv_put_binding, kv_put_init,
+};
+use lagon_runtime_http::{response_to_v8, WebSocketMessage};
+use lagon_runtime_kv::KvEntry;
 use lagon_runtime_v8_utils::{v8_boolean, v8_string, v8_uint8array};
+use pull_body::{pull_body_binding, pull_body_init};
 use pull_stream::pull_stream_binding;
+use queue::{queue_send_binding, queue_send_init, queue_settle_binding};
 use queue_microtask::queue_microtask_binding;
 use sleep::{sleep_binding, sleep_init};
+use socket::{
+    socket_close_binding, socket_close_init, socket_connect_binding, socket_connect_init,
+    socket_read_binding, socket_read_init, socket_write_binding, socket_write_init, SocketStream,
+};
+use wait_until::wait_until_binding;
+use websocket::{
+    websocket_close_binding, websocket_connect_binding, websocket_connect_init,
+    websocket_create_binding, websocket_receive_binding, websocket_receive_init,
+    websocket_send_binding, WebSocketConnection,
+};
 
+pub mod cache;
 pub mod compression;
 pub mod console;
 pub mod crypto;
+pub mod db;
 pub mod fetch;
+pub mod kv;
+pub mod pull_body;
 pub mod pull_stream;
+pub mod queue;
 pub mod queue_microtask;
 pub mod sleep;
+pub mod socket;
+pub mod wait_until;
+pub mod websocket;
 
 pub struct BindingResult {
     pub id: usize,
@@ -33,20 +67,107 @@ pub struct BindingResult {
 }
 
 pub enum PromiseResult {
-    Response((u16, HeaderMap, Bytes)),
+    // The response body is registered as a body stream owned by the
+    // request that called fetch(), to be pulled from JS later
+    Response(u32, (u16, HeaderMap, Option<Body>)),
     ArrayBuffer(Vec<u8>),
     Boolean(bool),
     Error(String),
+    // Rejected as a `TypeError` object instead of a string
+    TypeError(String),
+    // Parsed into a JS value
+    Json(String),
+    // An object with the value and metadata, or `null` when missing
+    KvEntry(Option<KvEntry>),
+    // A socket opened with connect(), registered as owned by
+    // the request that opened it, resolving with its id
+    Socket(u32, Box<dyn SocketStream>),
+    // Same as `Socket`, for a WebSocket opened with `new WebSocket()`
+    WebSocket(u32, WebSocketConnection),
+    // A string, bytes, or an object with the close code and reason
+    WebSocketMessage(WebSocketMessage),
     Undefined,
 }
 
 impl PromiseResult {
     pub fn into_value<'a>(self, scope: &mut v8::HandleScope<'a>) -> v8::Local<'a, v8::Value> {
         match self {
-            PromiseResult::Response(response) => response_to_v8(response, scope).into(),
+            PromiseResult::Response(request_id, (status, headers, body)) => {
+                let body_stream_id = body.map(|body| {
+                    let state = Isolate::state(scope);
+                    let mut state = state.borrow_mut();
+
+                    state.register_body_stream(request_id, body)
+                });
+
+                response_to_v8((status, headers, body_stream_id), scope).into()
+            }
             PromiseResult::ArrayBuffer(bytes) => v8_uint8array(scope, bytes).into(),
             PromiseResult::Boolean(boolean) => v8_boolean(scope, boolean).into(),
             PromiseResult::Error(error) => v8_string(scope, &error).into(),
+            PromiseResult::TypeError(error) => {
+                let error = v8_string(scope, &error);
+                v8::Exception::type_error(scope, error)
+            }
+            PromiseResult::Json(json) => {
+                let json = v8_string(scope, &json);
+
+                v8::json::parse(scope, json).unwrap_or_else(|| v8::undefined(scope).into())
+            }
+            PromiseResult::KvEntry(entry) => match entry {
+                Some(entry) => {
+                    let metadata: v8::Local<v8::Value> = match entry.metadata {
+                        Some(metadata) => v8_string(scope, &metadata).into(),
+                        None => v8::undefined(scope).into(),
+                    };
+                    let names: [v8::Local<v8::Name>; 2] =
+                        [v8_string(scope, "v").into(), v8_string(scope, "m").into()];
+                    let values = [v8_uint8array(scope, entry.value).into(), metadata];
+                    let null = v8::null(scope).into();
+
+                    v8::Object::with_prototype_and_properties(scope, null, &names, &values).into()
+                }
+                None => v8::null(scope).into(),
+            },
+            PromiseResult::Socket(request_id, stream) => {
+                let state = Isolate::state(scope);
+                let socket_id = state.borrow_mut().register_socket(request_id, stream);
+
+                v8::Integer::new_from_unsigned(scope, socket_id).into()
+            }
+            PromiseResult::WebSocket(request_id, connection) => {
+                let state = Isolate::state(scope);
+                let websocket_id = state.borrow_mut().register_outbound_websocket(
+                    request_id,
+                    connection.sender,
+                    connection.incoming,
+                );
+
+                let names: [v8::Local<v8::Name>; 2] =
+                    [v8_string(scope, "i").into(), v8_string(scope, "p").into()];
+                let values = [
+                    v8::Integer::new_from_unsigned(scope, websocket_id).into(),
+                    v8_string(scope, &connection.protocol).into(),
+                ];
+                let null = v8::null(scope).into();
+
+                v8::Object::with_prototype_and_properties(scope, null, &names, &values).into()
+            }
+            PromiseResult::WebSocketMessage(message) => match message {
+                WebSocketMessage::Text(text) => v8_string(scope, &text).into(),
+                WebSocketMessage::Binary(bytes) => v8_uint8array(scope, bytes).into(),
+                WebSocketMessage::Close(code, reason) => {
+                    let names: [v8::Local<v8::Name>; 2] =
+                        [v8_string(scope, "c").into(), v8_string(scope, "r").into()];
+                    let values = [
+                        v8::Integer::new(scope, code.into()).into(),
+                        v8_string(scope, &reason).into(),
+                    ];
+                    let null = v8::null(scope).into();
+
+                    v8::Object::with_prototype_and_properties(scope, null, &names, &values).into()
+                }
+            },
             PromiseResult::Undefined => v8::undefined(scope).into(),
         }
     }
@@ -78,7 +199,11 @@ macro_rules! async_binding {
 
             let isolate_state = Isolate::state(scope);
             let mut state = isolate_state.borrow_mut();
-            let id = state.js_promises.len() + 1;
+
+            // Use a counter instead of the number of pending promises, since
+            // ids would otherwise be reused while some promises are still pending
+            state.js_promises_count += 1;
+            let id = state.js_promises_count;
 
             let global_promise = v8::Global::new(scope, promise);
             state.js_promises.insert(id, global_promise);
@@ -94,8 +219,14 @@ macro_rules! async_binding {
                     isolate_state.borrow_mut().promises.push(Box::pin(future));
                 }
                 Err(error) => {
-                    let error = v8_string(scope, &error.to_string());
-                    promise.reject(scope, error.into());
+                    let message = v8_string(scope, &error.to_string());
+
+                    let error = match error.downcast_ref::<FetchPolicyError>() {
+                        Some(_) => v8::Exception::type_error(scope, message),
+                        None => message.into(),
+                    };
+
+                    promise.reject(scope, error);
                 }
             }
         };
@@ -149,6 +280,22 @@ pub fn bind<'a>(
             compression_finish_binding
         );
 
+        binding!(
+            scope,
+            lagon_object,
+            "websocketCreate",
+            websocket_create_binding
+        );
+        binding!(scope, lagon_object, "websocketSend", websocket_send_binding);
+        binding!(
+            scope,
+            lagon_object,
+            "websocketClose",
+            websocket_close_binding
+        );
+        binding!(scope, lagon_object, "waitUntil", wait_until_binding);
+        binding!(scope, lagon_object, "queueSettle", queue_settle_binding);
+
         global.set(v8_string(scope, "LagonSync").into(), lagon_object.into());
     }
 
@@ -181,6 +328,13 @@ pub fn bind<'a>(
             decrypt_binding
         );
         async_binding!(scope, lagon_object, "sleep", sleep_init, sleep_binding);
+        async_binding!(
+            scope,
+            lagon_object,
+            "pullBody",
+            pull_body_init,
+            pull_body_binding
+        );
         async_binding!(
             scope,
             lagon_object,
@@ -188,6 +342,123 @@ pub fn bind<'a>(
             generate_key_init,
             generate_key_binding
         );
+        async_binding!(scope, lagon_object, "kvGet", kv_get_init, kv_get_binding);
+        async_binding!(scope, lagon_object, "kvPut", kv_put_init, kv_put_binding);
+        async_binding!(
+            scope,
+            lagon_object,
+            "kvDelete",
+            kv_delete_init,
+            kv_delete_binding
+        );
+        async_binding!(scope, lagon_object, "kvList", kv_list_init, kv_list_binding);
+        async_binding!(
+            scope,
+            lagon_object,
+            "queueSend",
+            queue_send_init,
+            queue_send_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "cacheMatch",
+            cache_match_init,
+            cache_match_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "cachePut",
+            cache_put_init,
+            cache_put_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "cacheDelete",
+            cache_delete_init,
+            cache_delete_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "dbQuery",
+            db_query_init,
+            db_query_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "dbBatch",
+            db_batch_init,
+            db_batch_binding
+        );
+        async_binding!(scope, lagon_object, "dbExec", db_exec_init, db_exec_binding);
+        async_binding!(
+            scope,
+            lagon_object,
+            "dbBegin",
+            db_begin_init,
+            db_begin_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "dbCommit",
+            db_commit_init,
+            db_commit_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "dbRollback",
+            db_rollback_init,
+            db_rollback_binding
+        );
+
+        async_binding!(
+            scope,
+            lagon_object,
+            "socketConnect",
+            socket_connect_init,
+            socket_connect_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "socketRead",
+            socket_read_init,
+            socket_read_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "socketWrite",
+            socket_write_init,
+            socket_write_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "socketClose",
+            socket_close_init,
+            socket_close_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "websocketConnect",
+            websocket_connect_init,
+            websocket_connect_binding
+        );
+        async_binding!(
+            scope,
+            lagon_object,
+            "websocketReceive",
+            websocket_receive_init,
+            websocket_receive_binding
+        );
 
         global.set(v8_string(scope, "LagonAsync").into(), lagon_object.into());
     }
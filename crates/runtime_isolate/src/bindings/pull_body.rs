@@ -0,0 +1,61 @@
+use anyhow::Result;
+use futures::lock::Mutex;
+use hyper::{body::HttpBody, Body};
+use lagon_runtime_v8_utils::extract_v8_uint32;
+use std::rc::Rc;
+
+use crate::{bindings::PromiseResult, fetch_policy::FetchPolicyError, Isolate};
+
+use super::BindingResult;
+
+type Arg = Option<Rc<Mutex<Body>>>;
+
+pub fn pull_body_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<Arg> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    Ok(state
+        .body_streams
+        .get(&id)
+        .map(|body_stream| Rc::clone(&body_stream.body)))
+}
+
+pub async fn pull_body_binding(id: usize, arg: Arg) -> BindingResult {
+    // The body stream might have already been removed if the
+    // request finished, in which case we close the stream
+    let body = match arg {
+        Some(body) => body,
+        None => {
+            return BindingResult {
+                id,
+                result: PromiseResult::Undefined,
+            }
+        }
+    };
+
+    let mut body = body.lock().await;
+
+    let result = match body.data().await {
+        Some(Ok(chunk)) => PromiseResult::ArrayBuffer(chunk.to_vec()),
+        Some(Err(error)) => {
+            let message = format!("Failed to read body: {}", error);
+
+            // Body streams from fetch() error when exceeding the maximum response size
+            match error
+                .into_cause()
+                .and_then(|cause| cause.downcast::<FetchPolicyError>().ok())
+            {
+                Some(policy_error) => PromiseResult::TypeError(policy_error.to_string()),
+                None => PromiseResult::Error(message),
+            }
+        }
+        None => PromiseResult::Undefined,
+    };
+
+    BindingResult { id, result }
+}
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use lagon_runtime_queue::{Queue, QueueOutcome};
+use lagon_runtime_v8_utils::{extract_v8_string, v8_exception};
+
+use crate::{bindings::PromiseResult, Isolate};
+
+use super::BindingResult;
+
+type Arg = (Queue, String);
+
+pub fn queue_send_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<Arg> {
+    let queue = {
+        let state = Isolate::state(scope);
+        let state = state.borrow();
+
+        state
+            .queue
+            .clone()
+            .ok_or_else(|| anyhow!("Queue is not available for this Function"))?
+    };
+    let body = extract_v8_string(args.get(0), scope)?;
+
+    Ok((queue, body))
+}
+
+pub async fn queue_send_binding(id: usize, arg: Arg) -> BindingResult {
+    let (queue, body) = arg;
+
+    let result = match queue.send(body).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(error.to_string()),
+    };
+
+    BindingResult { id, result }
+}
+
+// Explicitly acknowledge or retry a message of the batch being consumed
+pub fn queue_settle_binding(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    let id = match extract_v8_string(args.get(0), scope) {
+        Ok(id) => id,
+        Err(error) => {
+            let exception = v8_exception(scope, &error.to_string());
+            scope.throw_exception(exception);
+            return;
+        }
+    };
+    let outcome = match args.get(1).boolean_value(scope) {
+        true => QueueOutcome::Retry,
+        false => QueueOutcome::Ack,
+    };
+
+    let request_id = scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value());
+
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    // Once the batch has been consumed (or timed out), its
+    // messages have already been settled and this is a no-op
+    if let Some(outcomes) = state
+        .handler_results
+        .get(&request_id)
+        .and_then(|handler_result| handler_result.queue_outcomes.as_ref())
+    {
+        outcomes.send((id, outcome)).unwrap_or(());
+    }
+}
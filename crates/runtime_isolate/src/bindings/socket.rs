@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Result};
+use futures::lock::Mutex;
+use lagon_runtime_v8_utils::{extract_v8_string, extract_v8_uint32, extract_v8_uint8array};
+use std::{rc::Rc, sync::Arc};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio_rustls::{rustls::ServerName, TlsConnector};
+
+use crate::{
+    bindings::PromiseResult,
+    fetch_policy::{tls_config, FetchPolicy, FetchPolicyError},
+    Isolate,
+};
+
+use super::BindingResult;
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// Either a plain TCP stream, or a TLS stream when using `secureTransport: 'on'`
+pub trait SocketStream: AsyncRead + AsyncWrite + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> SocketStream for T {}
+
+pub type SocketReader = Rc<Mutex<ReadHalf<Box<dyn SocketStream>>>>;
+pub type SocketWriter = Rc<Mutex<WriteHalf<Box<dyn SocketStream>>>>;
+
+// Outbound connections count towards the same
+// per-request limit as the calls to fetch()
+pub fn count_connection(scope: &mut v8::HandleScope, api: &str) -> Result<(u32, Arc<FetchPolicy>)> {
+    let request_id = scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value());
+
+    let state = Isolate::state(scope);
+    let mut state = state.borrow_mut();
+    let policy = Arc::clone(&state.fetch_policy);
+
+    let calls = match state.handler_results.get_mut(&request_id) {
+        Some(handler_result) => {
+            handler_result.context.fetch_calls += 1;
+            handler_result.context.fetch_calls
+        }
+        None => 0,
+    };
+
+    policy.check_calls(api, calls)?;
+
+    Ok((request_id, policy))
+}
+
+fn to_promise_result(error: anyhow::Error, message: String) -> PromiseResult {
+    match error.downcast_ref::<FetchPolicyError>() {
+        Some(policy_error) => PromiseResult::TypeError(policy_error.to_string()),
+        None => PromiseResult::Error(format!("{message}: {error}")),
+    }
+}
+
+type ConnectArg = (u32, Arc<FetchPolicy>, String, u16, bool);
+
+pub fn socket_connect_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<ConnectArg> {
+    let hostname = extract_v8_string(args.get(0), scope)?;
+    let port = extract_v8_uint32(scope, args.get(1))?;
+    let port = u16::try_from(port)
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| anyhow!("Invalid port: {port}"))?;
+    let secure = args.get(2).is_true();
+
+    let (request_id, policy) = count_connection(scope, "connect()")?;
+
+    Ok((request_id, policy, hostname, port, secure))
+}
+
+async fn open_socket(
+    policy: &FetchPolicy,
+    hostname: &str,
+    port: u16,
+    secure: bool,
+) -> Result<Box<dyn SocketStream>> {
+    let stream = policy.connect("connect()", hostname, port).await?;
+
+    if !secure {
+        return Ok(Box::new(stream));
+    }
+
+    let server_name = ServerName::try_from(hostname)
+        .map_err(|_| anyhow!("Invalid hostname for TLS: {hostname}"))?;
+    let stream = TlsConnector::from(tls_config())
+        .connect(server_name, stream)
+        .await?;
+
+    Ok(Box::new(stream))
+}
+
+pub async fn socket_connect_binding(id: usize, arg: ConnectArg) -> BindingResult {
+    let (request_id, policy, hostname, port, secure) = arg;
+
+    let result = match open_socket(&policy, &hostname, port, secure).await {
+        Ok(stream) => PromiseResult::Socket(request_id, stream),
+        Err(error) => to_promise_result(error, format!("Could not connect to {hostname}:{port}")),
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn socket_read_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<Option<SocketReader>> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    Ok(state
+        .sockets
+        .get(&id)
+        .map(|socket| Rc::clone(&socket.reader)))
+}
+
+pub async fn socket_read_binding(id: usize, arg: Option<SocketReader>) -> BindingResult {
+    // The socket might have already been closed, or dropped
+    // when its request finished, in which case we end the stream
+    let reader = match arg {
+        Some(reader) => reader,
+        None => {
+            return BindingResult {
+                id,
+                result: PromiseResult::Undefined,
+            }
+        }
+    };
+
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+
+    let result = match reader.lock().await.read(&mut chunk).await {
+        Ok(0) => PromiseResult::Undefined,
+        Ok(size) => {
+            chunk.truncate(size);
+            PromiseResult::ArrayBuffer(chunk)
+        }
+        Err(error) => PromiseResult::Error(format!("Failed to read from socket: {error}")),
+    };
+
+    BindingResult { id, result }
+}
+
+type WriteArg = (SocketWriter, Vec<u8>);
+
+pub fn socket_write_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<WriteArg> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+    let data = extract_v8_uint8array(args.get(1))?;
+
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    let writer = state
+        .sockets
+        .get(&id)
+        .map(|socket| Rc::clone(&socket.writer))
+        .ok_or_else(|| anyhow!("Socket is closed"))?;
+
+    Ok((writer, data))
+}
+
+pub async fn socket_write_binding(id: usize, arg: WriteArg) -> BindingResult {
+    let (writer, data) = arg;
+
+    let result = match writer.lock().await.write_all(&data).await {
+        Ok(()) => PromiseResult::Undefined,
+        Err(error) => PromiseResult::Error(format!("Failed to write to socket: {error}")),
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn socket_close_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<Option<SocketWriter>> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+
+    let state = Isolate::state(scope);
+    let mut state = state.borrow_mut();
+
+    Ok(state.sockets.remove(&id).map(|socket| socket.writer))
+}
+
+pub async fn socket_close_binding(id: usize, arg: Option<SocketWriter>) -> BindingResult {
+    if let Some(writer) = arg {
+        writer.lock().await.shutdown().await.unwrap_or(());
+    }
+
+    BindingResult {
+        id,
+        result: PromiseResult::Undefined,
+    }
+}
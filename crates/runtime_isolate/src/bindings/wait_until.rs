@@ -0,0 +1,32 @@
+use lagon_runtime_v8_utils::v8_exception;
+
+use crate::Isolate;
+
+pub fn wait_until_binding(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    let promise = match v8::Local::<v8::Promise>::try_from(args.get(0)) {
+        Ok(promise) => v8::Global::new(scope, promise),
+        Err(_) => {
+            let exception = v8_exception(scope, "Parameter 1 is not of type 'Promise'");
+            scope.throw_exception(exception);
+            return;
+        }
+    };
+
+    let request_id = scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value());
+
+    let state = Isolate::state(scope);
+    let mut state = state.borrow_mut();
+
+    // Outside of a request (or once it timed out), there's
+    // no request to keep alive and the promise simply runs
+    if let Some(handler_result) = state.handler_results.get_mut(&request_id) {
+        handler_result.wait_until.push(promise);
+    }
+}
@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use lagon_runtime_http::WebSocketMessage;
+use lagon_runtime_v8_utils::{
+    extract_v8_integer, extract_v8_string, extract_v8_uint32, extract_v8_uint8array, v8_exception,
+};
+use reqwest::Url;
+use std::{sync::Arc, time::Duration};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    client_async_tls_with_config,
+    tungstenite::{
+        client::IntoClientRequest,
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue},
+        protocol::CloseFrame,
+        Message,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{
+    bindings::{socket::count_connection, BindingResult, PromiseResult},
+    fetch_policy::{tls_config, FetchPolicy, FetchPolicyError},
+    Isolate,
+};
+
+// https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1
+const CLOSE_GOING_AWAY: u16 = 1001;
+const CLOSE_NO_STATUS: u16 = 1005;
+const CLOSE_ABNORMAL: u16 = 1006;
+
+// How long to wait for the server to acknowledge our close frame
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn websocket_create_binding(
+    scope: &mut v8::HandleScope,
+    _args: v8::FunctionCallbackArguments,
+    mut retval: v8::ReturnValue,
+) {
+    let request_id = scope
+        .get_continuation_preserved_embedder_data()
+        .to_uint32(scope)
+        .map_or(0, |value| value.value());
+
+    let id = Isolate::state(scope)
+        .borrow_mut()
+        .register_websocket(request_id);
+
+    retval.set(v8::Integer::new_from_unsigned(scope, id).into());
+}
+
+fn extract_message(
+    scope: &mut v8::HandleScope,
+    args: &v8::FunctionCallbackArguments,
+) -> Result<(u32, WebSocketMessage)> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+    let data = args.get(1);
+
+    let message = if data.is_string() {
+        WebSocketMessage::Text(extract_v8_string(data, scope)?)
+    } else {
+        WebSocketMessage::Binary(extract_v8_uint8array(data)?)
+    };
+
+    Ok((id, message))
+}
+
+fn extract_close(
+    scope: &mut v8::HandleScope,
+    args: &v8::FunctionCallbackArguments,
+) -> Result<(u32, WebSocketMessage)> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+    let code = extract_v8_integer(args.get(1), scope)? as u16;
+    let reason = extract_v8_string(args.get(2), scope)?;
+
+    Ok((id, WebSocketMessage::Close(code, reason)))
+}
+
+fn send(scope: &mut v8::HandleScope, id: u32, message: WebSocketMessage) -> Result<()> {
+    let state = Isolate::state(scope);
+    let mut state = state.borrow_mut();
+
+    let is_close = matches!(message, WebSocketMessage::Close(..));
+    let websocket = state
+        .websockets
+        .get(&id)
+        .ok_or_else(|| anyhow!("WebSocket is closed"))?;
+
+    // Messages sent before the socket is accepted are queued
+    websocket.sender.send(message).unwrap_or(());
+
+    if is_close {
+        state.websockets.remove(&id);
+    }
+
+    Ok(())
+}
+
+pub fn websocket_send_binding(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    if let Err(error) =
+        extract_message(scope, &args).and_then(|(id, message)| send(scope, id, message))
+    {
+        let exception = v8_exception(scope, &error.to_string());
+        scope.throw_exception(exception);
+    }
+}
+
+pub fn websocket_close_binding(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut _retval: v8::ReturnValue,
+) {
+    if let Err(error) =
+        extract_close(scope, &args).and_then(|(id, message)| send(scope, id, message))
+    {
+        let exception = v8_exception(scope, &error.to_string());
+        scope.throw_exception(exception);
+    }
+}
+
+// A WebSocket opened with `new WebSocket()`, once the handshake succeeded
+pub struct WebSocketConnection {
+    pub sender: flume::Sender<WebSocketMessage>,
+    pub incoming: flume::Receiver<WebSocketMessage>,
+    // The subprotocol selected by the server
+    pub protocol: String,
+}
+
+type ConnectArg = (u32, Arc<FetchPolicy>, Url, String);
+
+pub fn websocket_connect_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<ConnectArg> {
+    let url = Url::parse(&extract_v8_string(args.get(0), scope)?)?;
+    // Comma-separated, as sent in the `Sec-WebSocket-Protocol` header
+    let protocols = extract_v8_string(args.get(1), scope)?;
+
+    let (request_id, policy) = count_connection(scope, "new WebSocket()")?;
+
+    Ok((request_id, policy, url, protocols))
+}
+
+type ClientStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn open_websocket(
+    policy: &FetchPolicy,
+    url: &Url,
+    protocols: &str,
+) -> Result<(ClientStream, String)> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("Invalid URL: {url}"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("Invalid URL: {url}"))?;
+
+    let stream = policy.connect("new WebSocket()", host, port).await?;
+
+    let mut request = url.as_str().into_client_request()?;
+
+    if !protocols.is_empty() {
+        request
+            .headers_mut()
+            .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_str(protocols)?);
+    }
+
+    let (stream, response) =
+        client_async_tls_with_config(request, stream, None, Some(Connector::Rustls(tls_config())))
+            .await?;
+
+    let protocol = response
+        .headers()
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|protocol| protocol.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    Ok((stream, protocol))
+}
+
+// Forward the messages between the server and the isolate, until either side
+// closes the socket. The isolate sends a close frame when it drops the socket
+async fn run_websocket_client(
+    stream: ClientStream,
+    outgoing: flume::Receiver<WebSocketMessage>,
+    incoming: flume::Sender<WebSocketMessage>,
+) {
+    let (mut sink, mut stream) = stream.split();
+
+    loop {
+        tokio::select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    incoming.send(WebSocketMessage::Text(text)).unwrap_or(());
+                }
+                Some(Ok(Message::Binary(bytes))) => {
+                    incoming.send(WebSocketMessage::Binary(bytes)).unwrap_or(());
+                }
+                Some(Ok(Message::Close(frame))) => {
+                    let (code, reason) = frame.map_or((CLOSE_NO_STATUS, String::new()), |frame| {
+                        (frame.code.into(), frame.reason.into_owned())
+                    });
+
+                    incoming.send(WebSocketMessage::Close(code, reason)).unwrap_or(());
+
+                    // Acknowledge the close frame
+                    sink.close().await.unwrap_or(());
+                    break;
+                }
+                // Pings are answered automatically
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => {
+                    incoming.send(WebSocketMessage::Close(CLOSE_ABNORMAL, String::new())).unwrap_or(());
+                    break;
+                }
+            },
+            message = outgoing.recv_async() => {
+                let message = match message {
+                    Ok(WebSocketMessage::Text(text)) => Message::Text(text),
+                    Ok(WebSocketMessage::Binary(bytes)) => Message::Binary(bytes),
+                    Ok(WebSocketMessage::Close(code, reason)) => Message::Close(Some(CloseFrame {
+                        code: code.into(),
+                        reason: reason.into(),
+                    })),
+                    // The socket was dropped, e.g because its request finished
+                    Err(_) => Message::Close(Some(CloseFrame {
+                        code: CLOSE_GOING_AWAY.into(),
+                        reason: "".into(),
+                    })),
+                };
+                let is_close = matches!(message, Message::Close(_));
+
+                if sink.send(message).await.is_err() {
+                    incoming.send(WebSocketMessage::Close(CLOSE_ABNORMAL, String::new())).unwrap_or(());
+                    break;
+                }
+
+                if is_close {
+                    // Wait for the server to acknowledge the close frame
+                    tokio::time::timeout(CLOSE_TIMEOUT, async {
+                        while let Some(Ok(_)) = stream.next().await {}
+                    })
+                    .await
+                    .unwrap_or(());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub async fn websocket_connect_binding(id: usize, arg: ConnectArg) -> BindingResult {
+    let (request_id, policy, url, protocols) = arg;
+
+    let result = match open_websocket(&policy, &url, &protocols).await {
+        Ok((stream, protocol)) => {
+            let (sender, outgoing) = flume::unbounded();
+            let (incoming_sender, incoming) = flume::unbounded();
+
+            tokio::spawn(run_websocket_client(stream, outgoing, incoming_sender));
+
+            PromiseResult::WebSocket(
+                request_id,
+                WebSocketConnection {
+                    sender,
+                    incoming,
+                    protocol,
+                },
+            )
+        }
+        Err(error) => match error.downcast_ref::<FetchPolicyError>() {
+            Some(policy_error) => PromiseResult::TypeError(policy_error.to_string()),
+            None => PromiseResult::Error(format!("Could not connect to {url}: {error}")),
+        },
+    };
+
+    BindingResult { id, result }
+}
+
+pub fn websocket_receive_init(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+) -> Result<Option<flume::Receiver<WebSocketMessage>>> {
+    let id = extract_v8_uint32(scope, args.get(0))?;
+
+    let state = Isolate::state(scope);
+    let state = state.borrow();
+
+    Ok(state
+        .websockets
+        .get(&id)
+        .and_then(|websocket| websocket.incoming.clone()))
+}
+
+pub async fn websocket_receive_binding(
+    id: usize,
+    arg: Option<flume::Receiver<WebSocketMessage>>,
+) -> BindingResult {
+    // Resolves with `undefined` once the socket is closed
+    let result = match arg {
+        Some(incoming) => match incoming.recv_async().await {
+            Ok(message) => PromiseResult::WebSocketMessage(message),
+            Err(_) => PromiseResult::Undefined,
+        },
+        None => PromiseResult::Undefined,
+    };
+
+    BindingResult { id, result }
+}
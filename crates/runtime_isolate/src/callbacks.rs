@@ -26,14 +26,28 @@ pub extern "C" fn promise_reject_callback(message: v8::PromiseRejectMessage) {
 
     match message.get_event() {
         v8::PromiseRejectEvent::PromiseRejectWithNoHandler => {
+            // The id of the request that caused this rejection, preserved
+            // across promise continuations. 0 when outside of any request
+            let request_id = scope
+                .get_continuation_preserved_embedder_data()
+                .to_uint32(scope)
+                .map_or(0, |value| value.value());
+
             let try_catch = &mut v8::TryCatch::new(scope);
 
             let exception_message = match message.get_value() {
-                Some(exception) => get_exception_message(try_catch, exception, state.lines),
+                Some(exception) => get_exception_message(
+                    try_catch,
+                    exception,
+                    state.lines,
+                    state.source_map.as_deref(),
+                ),
                 None => "Unknown error".to_string(),
             };
 
-            state.rejected_promises.insert(promise, exception_message);
+            state
+                .rejected_promises
+                .insert(promise, (request_id, exception_message));
         }
         v8::PromiseRejectEvent::PromiseHandlerAddedAfterReject => {
             state.rejected_promises.remove(&promise);
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hyper::{Body, Request, Response};
+use std::{error::Error, sync::Arc};
+
+use crate::fetch_policy::{FetchPolicy, FetchPolicyError};
+
+// Request extension counting how many service bindings were traversed
+// to send this request, so embedders can stop infinite call loops
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServiceBindingDepth(pub u32);
+
+// Sends the requests made with fetch(), once they passed the checks of the
+// isolate's fetch policy. Returning a `FetchPolicyError` rejects with a `TypeError`
+#[async_trait]
+pub trait FetchHandler: Send + Sync {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>>;
+}
+
+// The default handler, sending requests over the network
+// with the client configured by the fetch policy
+pub struct ReqwestFetchHandler {
+    policy: Arc<FetchPolicy>,
+}
+
+impl ReqwestFetchHandler {
+    pub fn new(policy: Arc<FetchPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+// Policy violations from the resolver or the redirect policy
+// are wrapped in reqwest's errors, so we look for them
+fn find_policy_error(error: &reqwest::Error) -> Option<&FetchPolicyError> {
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<FetchPolicyError>() {
+            return Some(error);
+        }
+
+        source = error.source();
+    }
+
+    None
+}
+
+#[async_trait]
+impl FetchHandler for ReqwestFetchHandler {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let (parts, body) = request.into_parts();
+
+        let response = self
+            .policy
+            .client()
+            .request(parts.method, parts.uri.to_string())
+            .headers(parts.headers)
+            .body(body)
+            .send()
+            .await
+            .map_err(|error| match find_policy_error(&error) {
+                Some(policy_error) => FetchPolicyError(policy_error.to_string()).into(),
+                None => anyhow!(error.without_url().to_string()),
+            })?;
+
+        let mut builder = Response::builder().status(response.status());
+
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+
+        // The body is streamed chunk by chunk when read from JS
+        let body = match response.content_length() {
+            Some(0) => Body::empty(),
+            _ => Body::wrap_stream(response.bytes_stream()),
+        };
+
+        Ok(builder.body(body)?)
+    }
+}
@@ -0,0 +1,515 @@
+use anyhow::{anyhow, Error, Result};
+use hyper::client::connect::dns::Name;
+use ipnet::IpNet;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect::Policy,
+    Client, ClientBuilder, Url,
+};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, OnceLock, Weak},
+};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+
+static DEFAULT_POLICY: OnceLock<Arc<FetchPolicy>> = OnceLock::new();
+static TLS_CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+// Returned when a fetch() call violates the isolate's fetch
+// policy, and rejected as a `TypeError` in JS
+#[derive(Debug)]
+pub struct FetchPolicyError(pub String);
+
+impl fmt::Display for FetchPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for FetchPolicyError {}
+
+// A host can either be a domain name, optionally starting with `*.` to match
+// all its subdomains, or an IP address / CIDR (e.g `10.0.0.0/8`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostRule {
+    Domain(String),
+    Network(IpNet),
+}
+
+impl HostRule {
+    fn matches(&self, host: &str, ip: Option<IpAddr>) -> bool {
+        match self {
+            HostRule::Domain(domain) => {
+                // `Example.com.` and `example.com` are the same host
+                let host = host.trim_end_matches('.').to_lowercase();
+
+                match domain.strip_prefix("*.") {
+                    Some(parent) => host
+                        .strip_suffix(parent)
+                        .map_or(false, |subdomain| subdomain.ends_with('.')),
+                    None => &host == domain,
+                }
+            }
+            HostRule::Network(network) => ip.map_or(false, |ip| network.contains(&ip)),
+        }
+    }
+}
+
+impl FromStr for HostRule {
+    type Err = Error;
+
+    fn from_str(rule: &str) -> Result<Self> {
+        let rule = rule.trim();
+
+        if rule.is_empty() {
+            return Err(anyhow!("Host rule can't be empty"));
+        }
+
+        if rule.contains('/') {
+            return Ok(HostRule::Network(rule.parse()?));
+        }
+
+        if let Ok(ip) = rule.parse::<IpAddr>() {
+            return Ok(HostRule::Network(ip.into()));
+        }
+
+        Ok(HostRule::Domain(rule.trim_end_matches('.').to_lowercase()))
+    }
+}
+
+// Restricts what an isolate can do with fetch(). The default policy only
+// limits the number of calls per request, which is what `lagon dev` needs to
+// call local servers. Hosts are checked before sending the request and on
+// each redirect, and addresses are checked again after DNS resolution.
+// Sockets opened with connect() and `new WebSocket()` follow the same rules
+#[derive(Debug)]
+pub struct FetchPolicy {
+    pub max_calls: usize,
+    pub allowed_hosts: Vec<HostRule>,
+    pub denied_hosts: Vec<HostRule>,
+    pub block_private_addresses: bool,
+    pub max_response_size: Option<usize>,
+    client: OnceLock<Client>,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        Self {
+            max_calls: 20,
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            block_private_addresses: false,
+            max_response_size: None,
+            client: OnceLock::new(),
+        }
+    }
+}
+
+impl FetchPolicy {
+    pub fn max_calls(mut self, max_calls: usize) -> Self {
+        self.max_calls = max_calls;
+        self
+    }
+
+    pub fn allowed_hosts(mut self, allowed_hosts: Vec<HostRule>) -> Self {
+        self.allowed_hosts = allowed_hosts;
+        self
+    }
+
+    pub fn denied_hosts(mut self, denied_hosts: Vec<HostRule>) -> Self {
+        self.denied_hosts = denied_hosts;
+        self
+    }
+
+    pub fn block_private_addresses(mut self, block_private_addresses: bool) -> Self {
+        self.block_private_addresses = block_private_addresses;
+        self
+    }
+
+    pub fn max_response_size(mut self, max_response_size: Option<usize>) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
+    pub(crate) fn default_policy() -> Arc<FetchPolicy> {
+        Arc::clone(DEFAULT_POLICY.get_or_init(|| Arc::new(FetchPolicy::default())))
+    }
+
+    // fetch(), connect() and `new WebSocket()` share the same limit
+    pub(crate) fn check_calls(&self, api: &str, calls: usize) -> Result<(), FetchPolicyError> {
+        if calls > self.max_calls {
+            return Err(FetchPolicyError(format!(
+                "{api} can only be called {} times per requests",
+                self.max_calls
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Check a single address, either from the URL or resolved from the DNS
+    fn check_address(&self, api: &str, host: &str, ip: IpAddr) -> Result<(), FetchPolicyError> {
+        let target = if host == ip.to_string() {
+            host.to_string()
+        } else {
+            format!("{host} ({ip})")
+        };
+
+        if self
+            .denied_hosts
+            .iter()
+            .any(|rule| rule.matches(host, Some(ip)))
+        {
+            return Err(FetchPolicyError(format!("{api} to {target} is denied")));
+        }
+
+        if !self.allowed_hosts.is_empty()
+            && !self
+                .allowed_hosts
+                .iter()
+                .any(|rule| rule.matches(host, Some(ip)))
+        {
+            return Err(FetchPolicyError(format!(
+                "{api} to {target} is not allowed"
+            )));
+        }
+
+        if self.block_private_addresses && is_private_address(ip) {
+            return Err(FetchPolicyError(format!(
+                "{api} to private address {target} is not allowed"
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Check the host of a URL before resolving it. Domains that don't match a
+    // rule might still resolve to an allowed network, so we only reject them
+    // here when the allowed hosts don't contain any network
+    pub fn check_url(&self, url: &Url) -> Result<(), FetchPolicyError> {
+        match url.host_str() {
+            Some(host) => self.check_host("fetch()", host),
+            None => Ok(()),
+        }
+    }
+
+    fn check_host(&self, api: &str, host: &str) -> Result<(), FetchPolicyError> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self.check_address(api, host, ip);
+        }
+
+        if self
+            .denied_hosts
+            .iter()
+            .any(|rule| rule.matches(host, None))
+        {
+            return Err(FetchPolicyError(format!("{api} to {host} is denied")));
+        }
+
+        let allowed = self.allowed_hosts.is_empty()
+            || self
+                .allowed_hosts
+                .iter()
+                .any(|rule| matches!(rule, HostRule::Network(_)) || rule.matches(host, None));
+
+        if !allowed {
+            return Err(FetchPolicyError(format!("{api} to {host} is not allowed")));
+        }
+
+        Ok(())
+    }
+
+    // Resolve a host and only keep the addresses allowed by the policy
+    async fn lookup(&self, api: &str, host: &str, port: u16) -> Result<Vec<SocketAddr>, BoxError> {
+        let addrs = tokio::net::lookup_host((host, port)).await?;
+
+        let mut allowed = Vec::new();
+        let mut last_error = None;
+
+        for addr in addrs {
+            match self.check_address(api, host, addr.ip()) {
+                Ok(()) => allowed.push(addr),
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        // Only fail if none of the resolved addresses can be used
+        match (allowed.is_empty(), last_error) {
+            (true, Some(error)) => Err(error.into()),
+            _ => Ok(allowed),
+        }
+    }
+
+    // Open a TCP connection for connect() or `new WebSocket()`, checking
+    // the host before and the resolved addresses after the DNS lookup
+    pub(crate) async fn connect(&self, api: &str, host: &str, port: u16) -> Result<TcpStream> {
+        self.check_host(api, host)?;
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs = self.lookup(api, host, port).await.map_err(|error| {
+            match error.downcast::<FetchPolicyError>() {
+                Ok(policy_error) => Error::from(*policy_error),
+                Err(error) => anyhow!("Could not resolve {host}: {error}"),
+            }
+        })?;
+
+        Ok(TcpStream::connect(&addrs[..]).await?)
+    }
+
+    // Clients are shared by all the isolates using the same policy. The resolver
+    // and redirect policy only keep a weak reference to avoid a reference cycle
+    pub(crate) fn client(self: &Arc<Self>) -> &Client {
+        self.client.get_or_init(|| {
+            let redirect_policy = Arc::downgrade(self);
+
+            ClientBuilder::new()
+                .use_rustls_tls()
+                .dns_resolver(Arc::new(PolicyResolver(Arc::downgrade(self))))
+                .redirect(Policy::custom(move |attempt| {
+                    if attempt.previous().len() >= 5 {
+                        return attempt.error("Too many redirects");
+                    }
+
+                    match redirect_policy.upgrade() {
+                        Some(policy) => match policy.check_url(attempt.url()) {
+                            Ok(()) => attempt.follow(),
+                            Err(error) => attempt.error(error),
+                        },
+                        None => attempt.stop(),
+                    }
+                }))
+                .build()
+                .unwrap()
+        })
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+struct PolicyResolver(Weak<FetchPolicy>);
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve(self.0.clone(), name))
+    }
+}
+
+async fn resolve(policy: Weak<FetchPolicy>, name: Name) -> Result<Addrs, BoxError> {
+    let policy = match policy.upgrade() {
+        Some(policy) => policy,
+        None => return Err(FetchPolicyError("fetch() policy was dropped".into()).into()),
+    };
+
+    let addrs = policy.lookup("fetch()", name.as_str(), 0).await?;
+
+    Ok(Box::new(addrs.into_iter()))
+}
+
+// Shared by all the TLS sockets, trusting the Mozilla root certificates
+pub(crate) fn tls_config() -> Arc<ClientConfig> {
+    Arc::clone(TLS_CONFIG.get_or_init(|| {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+
+        Arc::new(
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    }))
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, third, _] = ip.octets();
+
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8)
+        || first == 0
+        // Shared address space (100.64.0.0/10)
+        || (first == 100 && (second & 0b1100_0000) == 64)
+        // IETF protocol assignments (192.0.0.0/24)
+        || (first == 192 && second == 0 && third == 0)
+        // Benchmarking (198.18.0.0/15)
+        || (first == 198 && (second & 0b1111_1110) == 18)
+        // Reserved (240.0.0.0/4), including the broadcast address
+        || first >= 240
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_private_ipv4(ip);
+    }
+
+    let segments = ip.segments();
+
+    // NAT64 (64:ff9b::/96) embeds the IPv4 address in the last 32 bits
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        return is_private_ipv4(embedded_ipv4(segments[6], segments[7]));
+    }
+
+    // 6to4 (2002::/16) embeds the IPv4 address right after the prefix
+    if segments[0] == 0x2002 {
+        return is_private_ipv4(embedded_ipv4(segments[1], segments[2]));
+    }
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local (fe80::/10)
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Site-local (fec0::/10), deprecated but still routed by some networks
+        || (segments[0] & 0xffc0) == 0xfec0
+}
+
+fn embedded_ipv4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))
+}
+
+pub fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_rule_domain() {
+        for (rule, host, expected) in [
+            ("example.com", "example.com", true),
+            ("example.com", "Example.COM", true),
+            ("example.com", "example.com.", true),
+            ("example.com.", "example.com", true),
+            ("Example.com", "example.com", true),
+            ("example.com", "sub.example.com", false),
+            ("example.com", "example.org", false),
+            ("*.example.com", "sub.example.com", true),
+            ("*.example.com", "SUB.Example.com.", true),
+            ("*.example.com", "example.com", false),
+            ("*.example.com", "badexample.com", false),
+        ] {
+            assert_eq!(
+                rule.parse::<HostRule>().unwrap().matches(host, None),
+                expected,
+                "{rule} matching {host}"
+            );
+        }
+    }
+
+    #[test]
+    fn denied_hosts_trailing_dot() {
+        let policy = FetchPolicy::default().denied_hosts(vec!["example.com".parse().unwrap()]);
+
+        for url in [
+            "https://example.com/",
+            "https://example.com./",
+            "https://EXAMPLE.com./",
+        ] {
+            assert!(policy.check_url(&url.parse().unwrap()).is_err(), "{url}");
+        }
+
+        assert!(policy
+            .check_url(&"https://example.org/".parse().unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn private_ipv4() {
+        for (ip, expected) in [
+            // This network (0.0.0.0/8)
+            ("0.0.0.0", true),
+            ("0.1.2.3", true),
+            // Private (10.0.0.0/8, 172.16.0.0/12, 192.168.0.0/16)
+            ("10.0.0.1", true),
+            ("172.16.0.1", true),
+            ("172.31.255.255", true),
+            ("172.32.0.1", false),
+            ("192.168.1.1", true),
+            // Shared address space (100.64.0.0/10)
+            ("100.64.0.1", true),
+            ("100.127.255.255", true),
+            ("100.128.0.1", false),
+            // Loopback (127.0.0.0/8)
+            ("127.0.0.1", true),
+            // Link-local (169.254.0.0/16)
+            ("169.254.169.254", true),
+            // IETF protocol assignments (192.0.0.0/24)
+            ("192.0.0.1", true),
+            ("192.0.0.255", true),
+            ("192.0.1.1", false),
+            // Benchmarking (198.18.0.0/15)
+            ("198.18.0.1", true),
+            ("198.19.255.255", true),
+            ("198.17.255.255", false),
+            ("198.20.0.1", false),
+            // Multicast (224.0.0.0/4)
+            ("224.0.0.1", true),
+            ("239.255.255.255", true),
+            // Reserved (240.0.0.0/4)
+            ("240.0.0.1", true),
+            ("255.255.255.255", true),
+            // Public
+            ("1.1.1.1", false),
+            ("8.8.8.8", false),
+            ("223.255.255.255", false),
+        ] {
+            assert_eq!(is_private_address(ip.parse().unwrap()), expected, "{ip}");
+        }
+    }
+
+    #[test]
+    fn private_ipv6() {
+        for (ip, expected) in [
+            // Loopback and unspecified
+            ("::1", true),
+            ("::", true),
+            // IPv4-mapped
+            ("::ffff:127.0.0.1", true),
+            ("::ffff:10.0.0.1", true),
+            ("::ffff:8.8.8.8", false),
+            // NAT64 (64:ff9b::/96)
+            ("64:ff9b::127.0.0.1", true),
+            ("64:ff9b::a9fe:a9fe", true),
+            ("64:ff9b::8.8.8.8", false),
+            // 6to4 (2002::/16)
+            ("2002:7f00:1::", true),
+            ("2002:a9fe:a9fe::1", true),
+            ("2002:808:808::1", false),
+            // Unique local (fc00::/7)
+            ("fc00::1", true),
+            ("fd12:3456::1", true),
+            // Link-local (fe80::/10)
+            ("fe80::1", true),
+            // Site-local (fec0::/10)
+            ("fec0::1", true),
+            ("feff::1", true),
+            // Multicast (ff00::/8)
+            ("ff02::1", true),
+            // Public
+            ("2606:4700:4700::1111", false),
+            ("2001:4860:4860::8888", false),
+        ] {
+            assert_eq!(is_private_address(ip.parse().unwrap()), expected, "{ip}");
+        }
+    }
+}
@@ -1,29 +1,50 @@
 use bindings::compression::CompressionInner;
-use futures::{future::poll_fn, stream::FuturesUnordered, Future, StreamExt};
-use hyper::{body::Bytes, http::request::Parts};
-use lagon_runtime_http::{request_to_v8, response_from_v8, RunResult, StreamResult};
-use lagon_runtime_v8_utils::v8_string;
+use futures::{future::poll_fn, lock::Mutex, stream::FuturesUnordered, Future, StreamExt};
+use hyper::{body::HttpBody, http::request::Parts, Body};
+use lagon_runtime_cache::CacheStorage;
+use lagon_runtime_db::Database;
+use lagon_runtime_http::{
+    request_to_v8, response_from_v8, RunResult, StreamResult, WebSocketChannel, WebSocketMessage,
+};
+use lagon_runtime_kv::KvNamespace;
+use lagon_runtime_queue::{Queue, QueueMessage, QueueOutcome};
+use lagon_runtime_v8_utils::{v8_string, v8_uint8array};
 use linked_hash_map::LinkedHashMap;
+use log::error;
+use sourcemap::SourceMap;
 use std::{
     cell::{RefCell, RefMut},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     pin::Pin,
     rc::Rc,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
     task::{Context, Poll},
-    time::Instant,
+    time::{Duration, Instant},
 };
 use v8::MapFnTo;
 
 use self::{
-    bindings::{BindingResult, PromiseResult},
+    bindings::{
+        socket::{SocketReader, SocketStream, SocketWriter},
+        BindingResult, PromiseResult,
+    },
     callbacks::{heap_limit_callback, promise_reject_callback, resolve_module_callback},
-    options::{IsolateOptions, Metadata},
+    fetch_handler::{FetchHandler, ReqwestFetchHandler, ServiceBindingDepth},
+    fetch_policy::FetchPolicy,
+    options::{ColdStart, IsolateOptions, Metadata},
+    watchdog::{Heartbeat, WatchedIsolate},
 };
 
 mod bindings;
 mod callbacks;
+pub mod fetch_handler;
+pub mod fetch_policy;
 pub mod options;
+pub mod scheduler;
+mod watchdog;
 
 const RUNTIME_ONLY_SCRIPT_NAME: &str = "runtime.js";
 const CODE_ONLY_SCRIPT_NAME: &str = "code.js";
@@ -32,15 +53,40 @@ const ISOLATE_SCRIPT_NAME: &str = "isolate.js";
 #[derive(Debug, Default)]
 pub struct RequestContext {
     fetch_calls: usize,
+    service_binding_depth: u32,
 }
 
 pub struct IsolateRequest {
-    pub request: (Parts, Bytes),
+    pub request: (Parts, Body),
+    pub sender: flume::Sender<RunResult>,
+}
+
+// A cron trigger, calling the `scheduled` export
+pub struct IsolateScheduled {
+    // When the trigger was due, in milliseconds since the UNIX epoch
+    pub scheduled_time: u64,
+    // The name of the schedule that triggered, and its cron expression
+    pub name: String,
+    pub cron: String,
+    pub sender: flume::Sender<RunResult>,
+}
+
+// A batch of messages to consume, calling the `queue` export
+pub struct IsolateQueue {
+    pub queue: String,
+    pub messages: Vec<QueueMessage>,
+    // Receives the messages explicitly acknowledged or retried,
+    // and is dropped once the batch has been consumed
+    pub outcomes: flume::Sender<(String, QueueOutcome)>,
     pub sender: flume::Sender<RunResult>,
 }
 
 pub enum IsolateEvent {
     Request(IsolateRequest),
+    Scheduled(IsolateScheduled),
+    Queue(IsolateQueue),
+    // A message sent by the client of an accepted WebSocket
+    WebSocket(u32, WebSocketMessage),
     Terminate(String),
 }
 
@@ -52,6 +98,68 @@ pub struct HandlerResult {
     stream_response_sent: RefCell<bool>,
     stream_status: RefCell<StreamStatus>,
     context: RequestContext,
+    // Scheduled and queue events resolve without a response
+    is_scheduled: bool,
+    // Set when consuming a queue batch
+    queue_outcomes: Option<flume::Sender<(String, QueueOutcome)>>,
+    // The promises passed to `ctx.waitUntil()`
+    wait_until: Vec<v8::Global<v8::Promise>>,
+    // Set once the response has been sent while some
+    // `ctx.waitUntil()` promises were still pending
+    response_time: Option<Instant>,
+}
+
+impl HandlerResult {
+    fn new(sender: flume::Sender<RunResult>, context: RequestContext, is_scheduled: bool) -> Self {
+        Self {
+            promise: None,
+            sender,
+            start_time: Instant::now(),
+            stream_response_sent: RefCell::new(false),
+            stream_status: RefCell::new(StreamStatus::None),
+            context,
+            is_scheduled,
+            queue_outcomes: None,
+            wait_until: Vec::new(),
+            response_time: None,
+        }
+    }
+
+    // Whether the request should be kept after sending its response,
+    // to let the promises passed to `ctx.waitUntil()` settle
+    fn keep_for_wait_until(&mut self) -> bool {
+        if self.wait_until.is_empty() {
+            return false;
+        }
+
+        self.response_time = Some(Instant::now());
+        true
+    }
+}
+
+// A request or fetch() response body pulled chunk by chunk
+// from JS, dropped when the request that owns it finishes
+struct BodyStream {
+    request_id: u32,
+    body: Rc<Mutex<Body>>,
+}
+
+// A WebSocket created by a request. Once returned in a response, it
+// outlives the request until either the client or the Function closes it
+struct WebSocket {
+    request_id: u32,
+    sender: flume::Sender<WebSocketMessage>,
+    // Taken when the socket is returned in a response
+    receiver: Option<flume::Receiver<WebSocketMessage>>,
+    // The messages received by sockets opened with `new WebSocket()`
+    incoming: Option<flume::Receiver<WebSocketMessage>>,
+}
+
+// A TCP connection opened with connect()
+struct Socket {
+    request_id: u32,
+    reader: SocketReader,
+    writer: SocketWriter,
 }
 
 #[derive(Debug, Clone)]
@@ -61,14 +169,106 @@ pub struct IsolateState {
     global: Option<Global>,
     promises: FuturesUnordered<Pin<Box<dyn Future<Output = BindingResult>>>>,
     js_promises: HashMap<usize, v8::Global<v8::PromiseResolver>>,
+    js_promises_count: usize,
     handler_results: HashMap<u32, HandlerResult>,
     stream_sender: flume::Sender<(u32, StreamResult)>,
     metadata: Rc<Metadata>,
-    rejected_promises: LinkedHashMap<v8::Global<v8::Promise>, String>,
+    rejected_promises: LinkedHashMap<v8::Global<v8::Promise>, (u32, String)>,
     lines: usize,
+    source_map: Option<Rc<SourceMap>>,
+    fetch_policy: Arc<FetchPolicy>,
+    fetch_handler: Arc<dyn FetchHandler>,
+    service_bindings: HashMap<String, Arc<dyn FetchHandler>>,
+    kv: Option<KvNamespace>,
+    queue: Option<Queue>,
+    cache: Option<CacheStorage>,
+    db: Option<Database>,
+    // Used by the bindings that need to stop before the request times out
+    total_timeout: Duration,
+    wait_until_timeout: Duration,
     requests_count: u32,
     log_sender: Option<flume::Sender<(String, String, Metadata)>>,
     compression_table: HashMap<String, CompressionInner>,
+    body_streams: HashMap<u32, BodyStream>,
+    body_streams_count: u32,
+    websockets: HashMap<u32, WebSocket>,
+    websockets_count: u32,
+    sockets: HashMap<u32, Socket>,
+    sockets_count: u32,
+}
+
+impl IsolateState {
+    fn register_body_stream(&mut self, request_id: u32, body: Body) -> u32 {
+        self.body_streams_count += 1;
+        let body_stream_id = self.body_streams_count;
+
+        self.body_streams.insert(
+            body_stream_id,
+            BodyStream {
+                request_id,
+                body: Rc::new(Mutex::new(body)),
+            },
+        );
+
+        body_stream_id
+    }
+
+    fn register_websocket(&mut self, request_id: u32) -> u32 {
+        self.websockets_count += 1;
+        let websocket_id = self.websockets_count;
+        let (sender, receiver) = flume::unbounded();
+
+        self.websockets.insert(
+            websocket_id,
+            WebSocket {
+                request_id,
+                sender,
+                receiver: Some(receiver),
+                incoming: None,
+            },
+        );
+
+        websocket_id
+    }
+
+    fn register_outbound_websocket(
+        &mut self,
+        request_id: u32,
+        sender: flume::Sender<WebSocketMessage>,
+        incoming: flume::Receiver<WebSocketMessage>,
+    ) -> u32 {
+        self.websockets_count += 1;
+        let websocket_id = self.websockets_count;
+
+        self.websockets.insert(
+            websocket_id,
+            WebSocket {
+                request_id,
+                sender,
+                receiver: None,
+                incoming: Some(incoming),
+            },
+        );
+
+        websocket_id
+    }
+
+    fn register_socket(&mut self, request_id: u32, stream: Box<dyn SocketStream>) -> u32 {
+        self.sockets_count += 1;
+        let socket_id = self.sockets_count;
+        let (reader, writer) = tokio::io::split(stream);
+
+        self.sockets.insert(
+            socket_id,
+            Socket {
+                request_id,
+                reader: Rc::new(Mutex::new(reader)),
+                writer: Rc::new(Mutex::new(writer)),
+            },
+        );
+
+        socket_id
+    }
 }
 
 #[derive(Debug)]
@@ -84,33 +284,24 @@ impl StreamStatus {
     }
 }
 
-#[derive(Debug)]
-enum Heartbeat {
-    None,
-    Some,
-    Waiting,
-}
-
-impl Heartbeat {
-    pub fn is_waiting(&self) -> bool {
-        matches!(self, Heartbeat::Waiting)
-    }
-
-    pub fn is_none(&self) -> bool {
-        matches!(self, Heartbeat::None)
-    }
-}
-
 pub struct Isolate {
     options: IsolateOptions,
     isolate: Option<v8::OwnedIsolate>,
     master_handler: Option<v8::Global<v8::Function>>,
+    websocket_handler: Option<v8::Global<v8::Function>>,
+    master_scheduled_handler: Option<v8::Global<v8::Function>>,
+    master_queue_handler: Option<v8::Global<v8::Function>>,
     handler: Option<v8::Global<v8::Value>>,
+    scheduled: Option<v8::Global<v8::Value>>,
+    queue: Option<v8::Global<v8::Value>>,
     compilation_error: Option<String>,
     stream_receiver: flume::Receiver<(u32, StreamResult)>,
     termination_result: Arc<RwLock<Option<RunResult>>>,
     heartbeat: Arc<RwLock<Heartbeat>>,
-    rx: flume::Receiver<IsolateEvent>,
+    // Whether the isolate is entered, i.e currently running on its worker thread
+    running: Arc<AtomicBool>,
+    watched: Option<Arc<WatchedIsolate>>,
+    rx: flume::r#async::RecvStream<'static, IsolateEvent>,
     near_heap_limit_callback_data: Option<Box<RefCell<dyn std::any::Any>>>,
     last_statistic_sent: Instant,
 }
@@ -155,6 +346,21 @@ impl Isolate {
             v8::ExternalReference {
                 function: bindings::compression::compression_write_binding.map_fn_to(),
             },
+            v8::ExternalReference {
+                function: bindings::websocket::websocket_create_binding.map_fn_to(),
+            },
+            v8::ExternalReference {
+                function: bindings::websocket::websocket_send_binding.map_fn_to(),
+            },
+            v8::ExternalReference {
+                function: bindings::websocket::websocket_close_binding.map_fn_to(),
+            },
+            v8::ExternalReference {
+                function: bindings::wait_until::wait_until_binding.map_fn_to(),
+            },
+            v8::ExternalReference {
+                function: bindings::queue::queue_settle_binding.map_fn_to(),
+            },
         ];
 
         let refs = v8::ExternalReferences::new(&references);
@@ -179,6 +385,24 @@ impl Isolate {
 
         let (stream_sender, stream_receiver) = flume::unbounded();
 
+        let source_map = options.source_map.as_ref().and_then(|source_map| {
+            match SourceMap::from_slice(source_map.as_bytes()) {
+                Ok(source_map) => Some(Rc::new(source_map)),
+                Err(error) => {
+                    error!("Failed to parse source map: {}", error);
+                    None
+                }
+            }
+        });
+
+        let fetch_policy = options
+            .fetch_policy
+            .clone()
+            .unwrap_or_else(FetchPolicy::default_policy);
+        let fetch_handler = options.fetch_handler.clone().unwrap_or_else(|| {
+            Arc::new(ReqwestFetchHandler::new(Arc::clone(&fetch_policy))) as Arc<dyn FetchHandler>
+        });
+
         let state: IsolateState = {
             let isolate_scope = &mut v8::HandleScope::new(&mut isolate);
             let global = if options.snapshot {
@@ -198,29 +422,57 @@ impl Isolate {
                 global: Some(Global(global)),
                 promises: FuturesUnordered::new(),
                 js_promises: HashMap::new(),
+                js_promises_count: 0,
                 handler_results: HashMap::new(),
                 stream_sender,
                 metadata: Rc::clone(&options.metadata),
                 rejected_promises: LinkedHashMap::new(),
                 lines: 0,
+                source_map,
+                fetch_policy,
+                fetch_handler,
+                service_bindings: options.service_bindings.clone(),
+                kv: options.kv.clone(),
+                queue: options.queue.clone(),
+                cache: options.cache.clone(),
+                db: options.db.clone(),
+                total_timeout: options.total_timeout,
+                wait_until_timeout: options.wait_until_timeout,
                 requests_count: 0,
                 log_sender: options.log_sender.clone(),
                 compression_table: HashMap::<String, CompressionInner>::new(),
+                body_streams: HashMap::new(),
+                body_streams_count: 0,
+                websockets: HashMap::new(),
+                websockets_count: 0,
+                sockets: HashMap::new(),
+                sockets_count: 0,
             }
         };
 
         isolate.set_slot(Rc::new(RefCell::new(state)));
 
+        // Isolates are entered when created, but many isolates can run on the same
+        // thread: we exit it right away and only enter it when running code
+        unsafe { isolate.exit() };
+
         let mut this = Self {
             options,
             isolate: Some(isolate),
             master_handler: None,
+            websocket_handler: None,
+            master_scheduled_handler: None,
+            master_queue_handler: None,
             handler: None,
+            scheduled: None,
+            queue: None,
             compilation_error: None,
             stream_receiver,
             termination_result: Arc::new(RwLock::new(None)),
             heartbeat: Arc::new(RwLock::new(Heartbeat::None)),
-            rx,
+            running: Arc::new(AtomicBool::new(false)),
+            watched: None,
+            rx: rx.into_stream(),
             near_heap_limit_callback_data: None,
             last_statistic_sent: Instant::now(),
         };
@@ -279,7 +531,31 @@ impl Isolate {
         s.clone()
     }
 
+    // Make this isolate the current one of the thread. Every enter
+    // must be followed by an exit before another isolate is entered
+    fn enter(&mut self) {
+        if let Some(isolate) = self.isolate.as_mut() {
+            unsafe { isolate.enter() };
+        }
+
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    fn exit(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+
+        if let Some(isolate) = self.isolate.as_mut() {
+            unsafe { isolate.exit() };
+        }
+    }
+
     pub fn evaluate(&mut self) {
+        self.enter();
+        self.evaluate_module();
+        self.exit();
+    }
+
+    fn evaluate_module(&mut self) {
         let isolate_state = Isolate::state(self.isolate.as_ref().unwrap());
         let global = {
             let state = isolate_state.borrow();
@@ -290,6 +566,10 @@ impl Isolate {
             &mut v8::HandleScope::with_context(self.isolate.as_mut().unwrap(), global.clone());
         let try_catch = &mut v8::TryCatch::new(scope);
 
+        if !self.options.snapshot {
+            set_environment_variables(try_catch, self.options.environment_variables.as_ref());
+        }
+
         let (code, lines) = self.options.get_runtime_code(try_catch);
         let resource_name = v8_string(
             try_catch,
@@ -303,81 +583,81 @@ impl Isolate {
         );
         let source_map_url = v8_string(try_catch, "");
         isolate_state.borrow_mut().lines = lines;
+        let source_map = isolate_state.borrow().source_map.clone();
+        let source_map = source_map.as_deref();
 
-        let source = v8::script_compiler::Source::new(
-            code,
-            Some(&v8::ScriptOrigin::new(
-                try_catch,
-                resource_name.into(),
-                0,
-                0,
-                false,
-                i32::from(self.options.snapshot_blob.is_some()),
-                source_map_url.into(),
-                false,
-                false,
-                true,
-            )),
+        let origin = v8::ScriptOrigin::new(
+            try_catch,
+            resource_name.into(),
+            0,
+            0,
+            false,
+            i32::from(self.options.snapshot_blob.is_some()),
+            source_map_url.into(),
+            false,
+            false,
+            true,
         );
 
-        let thread_safe_handle = try_catch.thread_safe_handle();
-        let termination_result = Arc::clone(&self.termination_result);
-        let tick_timeout = self.options.tick_timeout;
-        let heartbeat = Arc::clone(&self.heartbeat);
-
-        std::thread::spawn(move || {
-            // Isolates are terminated when they miss at least two heartbeats. The heartbeat
-            // missed count isn't reset to zero when a heartbeat has been successfully received:
-            // instead, the heartbeat missed count is decremented by one, allowing to safely
-            // terminate faulty isolates that are stuck in an infinite loop, and not randomly
-            // terminate isolates that just happen to be "slow"
-            let mut missed_heartbeat = 0;
-
-            loop {
-                std::thread::sleep(tick_timeout);
-
-                let heartbeat_value = heartbeat.read().unwrap();
-
-                if heartbeat_value.is_waiting() {
-                    continue;
-                }
-
-                if heartbeat_value.is_none() {
-                    missed_heartbeat += 1;
-                } else if missed_heartbeat > 0 {
-                    missed_heartbeat -= 1;
-                }
+        // Snapshots contain the compiled runtime already
+        let code_cache = match self.options.snapshot {
+            true => None,
+            false => self.options.code_cache.as_deref(),
+        };
+        let (mut source, compile_options) = match code_cache {
+            Some(code_cache) => (
+                v8::script_compiler::Source::new_with_cached_data(
+                    code,
+                    Some(&origin),
+                    v8::CachedData::new(code_cache),
+                ),
+                v8::script_compiler::CompileOptions::ConsumeCodeCache,
+            ),
+            None => (
+                v8::script_compiler::Source::new(code, Some(&origin)),
+                v8::script_compiler::CompileOptions::NoCompileOptions,
+            ),
+        };
 
-                if missed_heartbeat >= 2 {
-                    termination_result
-                        .write()
-                        .unwrap()
-                        .replace(RunResult::Timeout);
+        let watched = Arc::new(WatchedIsolate {
+            tick_timeout: self.options.tick_timeout,
+            heartbeat: Arc::clone(&self.heartbeat),
+            running: Arc::clone(&self.running),
+            termination_result: Arc::clone(&self.termination_result),
+            handle: try_catch.thread_safe_handle(),
+        });
+        watchdog::watch(&watched);
+        self.watched = Some(watched);
 
-                    if !thread_safe_handle.is_execution_terminating() {
-                        thread_safe_handle.terminate_execution();
-                    }
+        let start_time = Instant::now();
+        let module = v8::script_compiler::compile_module2(
+            try_catch,
+            &mut source,
+            compile_options,
+            v8::script_compiler::NoCacheReason::NoReason,
+        );
 
-                    break;
-                } else {
-                    drop(heartbeat_value);
-                    *heartbeat.write().unwrap() = Heartbeat::None;
-                }
-            }
-        });
+        // V8 rejects code caches created from another source or
+        // another V8 version, in which case we create a new one
+        let cache_hit = code_cache.is_some()
+            && source
+                .get_cached_data()
+                .map_or(false, |cached_data| !cached_data.rejected());
 
-        match v8::script_compiler::compile_module(try_catch, source) {
+        match module {
             Some(module) => {
                 if module
                     .instantiate_module(try_catch, resolve_module_callback)
                     .is_none()
                 {
-                    self.compilation_error = Some(handle_error(try_catch, lines).as_error());
+                    self.compilation_error =
+                        Some(handle_error(try_catch, lines, source_map).as_error());
                     return;
                 }
 
                 if module.evaluate(try_catch).is_none() {
-                    self.compilation_error = Some(handle_error(try_catch, lines).as_error());
+                    self.compilation_error =
+                        Some(handle_error(try_catch, lines, source_map).as_error());
                     return;
                 }
 
@@ -389,6 +669,18 @@ impl Isolate {
 
                     self.handler = Some(handler);
 
+                    let scheduled_key = v8_string(try_catch, "scheduled");
+                    let scheduled = namespace.get(try_catch, scheduled_key.into()).unwrap();
+                    let scheduled = v8::Global::new(try_catch, scheduled);
+
+                    self.scheduled = Some(scheduled);
+
+                    let queue_key = v8_string(try_catch, "queue");
+                    let queue = namespace.get(try_catch, queue_key.into()).unwrap();
+                    let queue = v8::Global::new(try_catch, queue);
+
+                    self.queue = Some(queue);
+
                     let global = global.open(try_catch);
                     let global = global.global(try_catch);
                     let handler_key = v8_string(try_catch, "masterHandler");
@@ -397,10 +689,54 @@ impl Isolate {
                     let handler = v8::Global::new(try_catch, handler);
 
                     self.master_handler = Some(handler);
+
+                    let websocket_handler_key = v8_string(try_catch, "masterWebSocketHandler");
+                    let websocket_handler = global
+                        .get(try_catch, websocket_handler_key.into())
+                        .and_then(|handler| v8::Local::<v8::Function>::try_from(handler).ok())
+                        .map(|handler| v8::Global::new(try_catch, handler));
+
+                    self.websocket_handler = websocket_handler;
+
+                    let scheduled_handler_key = v8_string(try_catch, "masterScheduledHandler");
+                    let master_scheduled_handler = global
+                        .get(try_catch, scheduled_handler_key.into())
+                        .and_then(|handler| v8::Local::<v8::Function>::try_from(handler).ok())
+                        .map(|handler| v8::Global::new(try_catch, handler));
+
+                    self.master_scheduled_handler = master_scheduled_handler;
+
+                    let queue_handler_key = v8_string(try_catch, "masterQueueHandler");
+                    let master_queue_handler = global
+                        .get(try_catch, queue_handler_key.into())
+                        .and_then(|handler| v8::Local::<v8::Function>::try_from(handler).ok())
+                        .map(|handler| v8::Global::new(try_catch, handler));
+
+                    self.master_queue_handler = master_queue_handler;
+
+                    if let Some(on_cold_start) = &self.options.on_cold_start {
+                        let code_cache = match cache_hit {
+                            true => None,
+                            false => module
+                                .get_unbound_module_script(try_catch)
+                                .create_code_cache()
+                                .map(|code_cache| code_cache.to_vec()),
+                        };
+
+                        on_cold_start(
+                            Rc::clone(&self.options.metadata),
+                            ColdStart {
+                                duration: start_time.elapsed(),
+                                cache_hit,
+                                code_cache,
+                            },
+                        );
+                    }
                 }
             }
             None => {
-                self.compilation_error = Some(handle_error(try_catch, lines).as_error());
+                self.compilation_error =
+                    Some(handle_error(try_catch, lines, source_map).as_error());
             }
         };
     }
@@ -408,13 +744,22 @@ impl Isolate {
     pub fn handle_event(&mut self, event: IsolateEvent, state: &Rc<RefCell<IsolateState>>) {
         match event {
             IsolateEvent::Request(IsolateRequest { request, sender }) => {
-                let (global, requests_count) = {
+                let (parts, body) = request;
+                let (global, requests_count, body_stream_id) = {
                     let mut isolate_state = state.borrow_mut();
                     let global = isolate_state.global.as_ref().unwrap().0.clone();
 
                     isolate_state.requests_count += 1;
+                    let requests_count = isolate_state.requests_count;
 
-                    (global, isolate_state.requests_count)
+                    // Bodies are pulled lazily from JS, so we only
+                    // register a stream when there's something to read
+                    let body_stream_id = match body.is_end_stream() {
+                        true => None,
+                        false => Some(isolate_state.register_body_stream(requests_count, body)),
+                    };
+
+                    (global, requests_count, body_stream_id)
                 };
                 let scope = &mut v8::HandleScope::with_context(
                     self.isolate.as_mut().unwrap(),
@@ -431,47 +776,148 @@ impl Isolate {
                 let global = global.open(try_catch);
                 let global = global.global(try_catch);
 
-                let request = request_to_v8(request, try_catch);
+                let service_binding_depth = parts
+                    .extensions
+                    .get::<ServiceBindingDepth>()
+                    .map_or(0, |depth| depth.0);
+                let bindings = create_bindings(try_catch, &state.borrow());
+
+                let request = request_to_v8(parts, body_stream_id, try_catch);
                 let id = v8::Integer::new(try_catch, requests_count as i32);
-                try_catch.set_continuation_preserved_embedder_data(id.into());
 
-                state.borrow_mut().handler_results.insert(
+                call_master_handler(
+                    try_catch,
+                    state,
+                    &self.termination_result,
+                    master_handler,
+                    global,
                     requests_count,
-                    HandlerResult {
-                        promise: None,
+                    HandlerResult::new(
                         sender,
-                        start_time: Instant::now(),
-                        stream_response_sent: RefCell::new(false),
-                        stream_status: RefCell::new(StreamStatus::None),
-                        context: RequestContext::default(),
-                    },
+                        RequestContext {
+                            service_binding_depth,
+                            ..RequestContext::default()
+                        },
+                        false,
+                    ),
+                    &[id.into(), handler, request.into(), bindings.into()],
                 );
+            }
+            IsolateEvent::Scheduled(IsolateScheduled {
+                scheduled_time,
+                name,
+                cron,
+                sender,
+            }) => {
+                let (global, requests_count) = {
+                    let mut isolate_state = state.borrow_mut();
+                    let global = isolate_state.global.as_ref().unwrap().0.clone();
 
-                match master_handler.call(
+                    isolate_state.requests_count += 1;
+
+                    (global, isolate_state.requests_count)
+                };
+                let scope = &mut v8::HandleScope::with_context(
+                    self.isolate.as_mut().unwrap(),
+                    global.clone(),
+                );
+                let try_catch = &mut v8::TryCatch::new(scope);
+
+                let master_handler = self.master_scheduled_handler.as_ref().unwrap();
+                let master_handler = master_handler.open(try_catch);
+
+                let scheduled = self.scheduled.as_ref().unwrap();
+                let scheduled = v8::Local::new(try_catch, scheduled);
+
+                let handler = self.handler.as_ref().unwrap();
+                let handler = v8::Local::new(try_catch, handler);
+
+                let global = global.open(try_catch);
+                let global = global.global(try_catch);
+
+                let bindings = create_bindings(try_catch, &state.borrow());
+
+                let event = v8::Object::new(try_catch);
+                let time_key = v8_string(try_catch, "t");
+                let time = v8::Number::new(try_catch, scheduled_time as f64);
+                event.set(try_catch, time_key.into(), time.into());
+                let cron_key = v8_string(try_catch, "c");
+                let cron = v8_string(try_catch, &cron);
+                event.set(try_catch, cron_key.into(), cron.into());
+                let name_key = v8_string(try_catch, "n");
+                let name = v8_string(try_catch, &name);
+                event.set(try_catch, name_key.into(), name.into());
+
+                call_master_handler(
                     try_catch,
-                    global.into(),
-                    &[id.into(), handler, request.into()],
-                ) {
-                    Some(response) => {
-                        let promise = v8::Local::<v8::Promise>::try_from(response)
-                            .expect("Handler did not return a promise");
-                        let promise = v8::Global::new(try_catch, promise);
-
-                        if let Some(handler_result) =
-                            state.borrow_mut().handler_results.get_mut(&requests_count)
-                        {
-                            handler_result.promise = Some(promise);
-                        }
-                    }
-                    None => {
-                        // Use the current termination result (e.g timeout or memory),
-                        // or try to handle an error
-                        self.termination_result
-                            .write()
-                            .unwrap()
-                            .get_or_insert_with(|| handle_error(try_catch, 0));
-                    }
+                    state,
+                    &self.termination_result,
+                    master_handler,
+                    global,
+                    requests_count,
+                    HandlerResult::new(sender, RequestContext::default(), true),
+                    &[scheduled, handler, event.into(), bindings.into()],
+                );
+            }
+            IsolateEvent::Queue(IsolateQueue {
+                queue,
+                messages,
+                outcomes,
+                sender,
+            }) => {
+                let (global, requests_count) = {
+                    let mut isolate_state = state.borrow_mut();
+                    let global = isolate_state.global.as_ref().unwrap().0.clone();
+
+                    isolate_state.requests_count += 1;
+
+                    (global, isolate_state.requests_count)
                 };
+                let scope = &mut v8::HandleScope::with_context(
+                    self.isolate.as_mut().unwrap(),
+                    global.clone(),
+                );
+                let try_catch = &mut v8::TryCatch::new(scope);
+
+                let master_handler = self.master_queue_handler.as_ref().unwrap();
+                let master_handler = master_handler.open(try_catch);
+
+                let handler = self.queue.as_ref().unwrap();
+                let handler = v8::Local::new(try_catch, handler);
+
+                let global = global.open(try_catch);
+                let global = global.global(try_catch);
+
+                let bindings = create_bindings(try_catch, &state.borrow());
+
+                let batch = v8::Object::new(try_catch);
+                let queue_key = v8_string(try_catch, "q");
+                let queue = v8_string(try_catch, &queue);
+                batch.set(try_catch, queue_key.into(), queue.into());
+                let messages_key = v8_string(try_catch, "m");
+                let messages = serde_json::to_string(&messages).unwrap_or_else(|_| "[]".into());
+                let messages = v8_string(try_catch, &messages);
+                let messages = v8::json::parse(try_catch, messages)
+                    .unwrap_or_else(|| v8::Array::new(try_catch, 0).into());
+                batch.set(try_catch, messages_key.into(), messages);
+
+                let mut handler_result =
+                    HandlerResult::new(sender, RequestContext::default(), true);
+                handler_result.queue_outcomes = Some(outcomes);
+
+                call_master_handler(
+                    try_catch,
+                    state,
+                    &self.termination_result,
+                    master_handler,
+                    global,
+                    requests_count,
+                    handler_result,
+                    &[handler, batch.into(), bindings.into()],
+                );
+            }
+            IsolateEvent::WebSocket(id, message) => {
+                self.handle_websocket_message(id, message, state);
             }
             IsolateEvent::Terminate(reason) => {
                 self.terminate(RunResult::Error(reason));
@@ -479,6 +925,90 @@ impl Isolate {
         }
     }
 
+    // Dispatch a message from the client to the socket's event listeners. Closing
+    // the socket removes it, and any message received after that is ignored
+    fn handle_websocket_message(
+        &mut self,
+        id: u32,
+        message: WebSocketMessage,
+        state: &Rc<RefCell<IsolateState>>,
+    ) {
+        let global = {
+            let mut isolate_state = state.borrow_mut();
+
+            if !isolate_state.websockets.contains_key(&id) {
+                return;
+            }
+
+            if let WebSocketMessage::Close(..) = message {
+                isolate_state.websockets.remove(&id);
+            }
+
+            isolate_state.global.as_ref().unwrap().0.clone()
+        };
+
+        let websocket_handler = match &self.websocket_handler {
+            Some(websocket_handler) => websocket_handler.clone(),
+            None => return,
+        };
+
+        let scope =
+            &mut v8::HandleScope::with_context(self.isolate.as_mut().unwrap(), global.clone());
+        let try_catch = &mut v8::TryCatch::new(scope);
+
+        let websocket_handler = websocket_handler.open(try_catch);
+        let global = global.open(try_catch);
+        let global = global.global(try_catch);
+
+        let id = v8::Integer::new_from_unsigned(try_catch, id).into();
+        let args: Vec<v8::Local<v8::Value>> = match message {
+            WebSocketMessage::Text(text) => vec![
+                id,
+                v8_string(try_catch, "message").into(),
+                v8_string(try_catch, &text).into(),
+            ],
+            WebSocketMessage::Binary(bytes) => vec![
+                id,
+                v8_string(try_catch, "message").into(),
+                v8_uint8array(try_catch, bytes).into(),
+            ],
+            WebSocketMessage::Close(code, reason) => vec![
+                id,
+                v8_string(try_catch, "close").into(),
+                v8::Integer::new_from_unsigned(try_catch, code.into()).into(),
+                v8_string(try_catch, &reason).into(),
+            ],
+        };
+
+        // Sockets outlive the request that accepted them, so
+        // their listeners run outside of any request
+        let request_id = v8::Integer::new(try_catch, 0);
+        try_catch.set_continuation_preserved_embedder_data(request_id.into());
+
+        if websocket_handler
+            .call(try_catch, global.into(), &args)
+            .is_none()
+        {
+            let isolate_state = state.borrow();
+            let error = handle_error(
+                try_catch,
+                isolate_state.lines,
+                isolate_state.source_map.as_deref(),
+            );
+
+            if let (RunResult::Error(error), Some(log_sender)) = (error, &isolate_state.log_sender)
+            {
+                log_sender
+                    .send((
+                        String::from("error"),
+                        error,
+                        isolate_state.metadata.as_ref().clone(),
+                    ))
+                    .unwrap_or(());
+            }
+        }
+    }
+
     fn poll_v8(&mut self, global: &v8::Global<v8::Context>) {
         let scope = &mut v8::HandleScope::with_context(self.isolate.as_mut().unwrap(), global);
 
@@ -515,7 +1045,10 @@ impl Isolate {
 
             for (result, promise) in promises {
                 let promise = promise.open(scope);
-                let should_reject = matches!(result, PromiseResult::Error(_));
+                let should_reject = matches!(
+                    result,
+                    PromiseResult::Error(_) | PromiseResult::TypeError(_)
+                );
                 let value = result.into_value(scope);
 
                 if should_reject {
@@ -560,36 +1093,66 @@ impl Isolate {
     }
 
     fn poll_event_loop(&mut self, cx: &mut Context) -> Poll<()> {
+        self.enter();
+        let poll = self.poll_isolate(cx);
+        self.exit();
+
+        poll
+    }
+
+    fn poll_isolate(&mut self, cx: &mut Context) -> Poll<()> {
         if let Some(compilation_error) = &self.compilation_error {
-            if let Ok(IsolateEvent::Request(IsolateRequest { sender, .. })) = self.rx.try_recv() {
-                let termination_result = match self.termination_result.write().unwrap().take() {
-                    Some(termination_result) => termination_result,
-                    None => RunResult::Error(compilation_error.to_string()),
-                };
+            return match self.rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(
+                    IsolateEvent::Request(IsolateRequest { sender, .. })
+                    | IsolateEvent::Scheduled(IsolateScheduled { sender, .. })
+                    | IsolateEvent::Queue(IsolateQueue { sender, .. }),
+                )) => {
+                    let termination_result = match self.termination_result.write().unwrap().take() {
+                        Some(termination_result) => termination_result,
+                        None => RunResult::Error(compilation_error.to_string()),
+                    };
 
-                sender.send(termination_result).unwrap_or(());
-            }
+                    sender.send(termination_result).unwrap_or(());
 
-            return Poll::Ready(());
+                    Poll::Ready(())
+                }
+                Poll::Ready(_) => Poll::Ready(()),
+                Poll::Pending => Poll::Pending,
+            };
         }
 
         let state = Isolate::state(self.isolate.as_ref().unwrap());
 
-        // If no requests are being processed, we can block this thread (`rx.recv`)
-        // while we wait for a new request. The heartbeat status is set to Waiting
-        // to avoid the isolate being terminated. If we are already processing requests,
-        // try to receive any other request
-        if state.borrow().handler_results.is_empty() {
+        // If no requests are being processed, we wait for a new request without
+        // being polled again, letting other isolates on the same thread run. The
+        // heartbeat status is set to Waiting to avoid the isolate being terminated.
+        // If we are already processing requests, try to receive any other request.
+        // Open WebSockets and sockets count as requests, since their listeners can run anytime
+        let is_idle = {
+            let state = state.borrow();
+            state.handler_results.is_empty()
+                && state.websockets.is_empty()
+                && state.sockets.is_empty()
+        };
+
+        if is_idle {
             *self.heartbeat.write().unwrap() = Heartbeat::Waiting;
 
-            if let Ok(event) = self.rx.recv() {
-                *self.heartbeat.write().unwrap() = Heartbeat::Some;
-                self.handle_event(event, &state);
+            match self.rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(event)) => {
+                    *self.heartbeat.write().unwrap() = Heartbeat::Some;
+                    self.handle_event(event, &state);
+                }
+                // All the senders have been dropped, no
+                // more requests will be sent to this isolate
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Pending => return Poll::Pending,
             }
         } else {
             *self.heartbeat.write().unwrap() = Heartbeat::Some;
 
-            while let Ok(event) = self.rx.try_recv() {
+            while let Poll::Ready(Some(event)) = self.rx.poll_next_unpin(cx) {
                 self.handle_event(event, &state);
             }
         }
@@ -606,29 +1169,67 @@ impl Isolate {
         self.poll_stream(&state);
 
         if let Some(termination_result) = self.termination_result.write().unwrap().take() {
-            if let Some(handler_result) = state.handler_results.values().next() {
-                handler_result.sender.send(termination_result).unwrap_or(());
+            // When a limit is reached, the request that was running is the one
+            // stored in the continuation data. Every other in-flight request
+            // didn't do anything wrong, and can safely be retried elsewhere
+            let culprit = match termination_result {
+                RunResult::Timeout | RunResult::MemoryLimit => {
+                    let scope = &mut v8::HandleScope::with_context(
+                        self.isolate.as_mut().unwrap(),
+                        global.clone(),
+                    );
+
+                    scope
+                        .get_continuation_preserved_embedder_data()
+                        .to_uint32(scope)
+                        .map(|id| id.value())
+                }
+                _ => None,
+            };
+
+            let mut termination_result = Some(termination_result);
+
+            for (id, handler_result) in state.handler_results.drain() {
+                let run_result = match culprit == Some(id) {
+                    true => termination_result.take().unwrap_or(RunResult::Retry),
+                    false => RunResult::Retry,
+                };
+
+                handler_result.sender.send(run_result).unwrap_or(());
             }
 
             return Poll::Ready(());
         }
 
-        if !state.rejected_promises.is_empty() {
-            let key = state.rejected_promises.keys().last().unwrap().clone();
-            let content = state.rejected_promises.remove(&key).unwrap();
-
-            // TODO: only send the error to the request that caused it
-            for handler_result in state.handler_results.values() {
-                handler_result
-                    .sender
-                    .send(RunResult::Error(content.clone()))
-                    .unwrap_or(());
+        let rejected_promises = state
+            .rejected_promises
+            .drain()
+            .map(|(_, rejection)| rejection)
+            .collect::<Vec<_>>();
+
+        for (request_id, content) in rejected_promises {
+            match state
+                .handler_results
+                .get(&request_id)
+                .filter(|handler_result| handler_result.response_time.is_none())
+            {
+                Some(handler_result) => {
+                    handler_result
+                        .sender
+                        .send(RunResult::Error(content))
+                        .unwrap_or(());
+                }
+                // The rejection happened outside of a request (e.g in a timer that
+                // outlived its request), or after its response has been sent, so
+                // there's no response to send it to
+                None => send_log(&state.log_sender, &state.metadata, "error", content),
             }
         }
 
         let scope = &mut v8::HandleScope::with_context(self.isolate.as_mut().unwrap(), global);
         let try_catch = &mut v8::TryCatch::new(scope);
         let lines = state.lines;
+        let source_map = state.source_map.clone();
         let options = &self.options;
 
         let should_send_statistics =
@@ -640,14 +1241,63 @@ impl Isolate {
                 false => false,
             };
 
+        let state = &mut *state;
+        let websockets = &mut state.websockets;
+        let log_sender = &state.log_sender;
+        let metadata = &state.metadata;
+
         state.handler_results.retain(|_, handler_result| {
+            // The response has already been sent, only the promises
+            // passed to `ctx.waitUntil()` are still running
+            if let Some(response_time) = handler_result.response_time {
+                handler_result.wait_until.retain(|promise| {
+                    let promise = promise.open(try_catch);
+
+                    match promise.state() {
+                        v8::PromiseState::Pending => true,
+                        v8::PromiseState::Fulfilled => false,
+                        v8::PromiseState::Rejected => {
+                            let exception = promise.result(try_catch);
+                            let content = get_exception_message(
+                                try_catch,
+                                exception,
+                                lines,
+                                source_map.as_deref(),
+                            );
+
+                            send_log(log_sender, metadata, "error", content);
+                            false
+                        }
+                    }
+                });
+
+                if handler_result.wait_until.is_empty() {
+                    return false;
+                }
+
+                if response_time.elapsed() >= options.wait_until_timeout {
+                    send_log(
+                        log_sender,
+                        metadata,
+                        "warn",
+                        format!(
+                            "ctx.waitUntil() promises did not settle within {}ms after the response",
+                            options.wait_until_timeout.as_millis()
+                        ),
+                    );
+                    return false;
+                }
+
+                return true;
+            }
+
             if *handler_result.stream_response_sent.borrow() {
                 if handler_result.stream_status.borrow().is_done() {
                     if should_send_statistics {
                         send_statistics(options, try_catch);
                     }
 
-                    return false;
+                    return handler_result.keep_for_wait_until();
                 }
 
                 if handler_result.start_time.elapsed() >= options.total_timeout {
@@ -663,7 +1313,49 @@ impl Isolate {
 
             match promise.state() {
                 v8::PromiseState::Fulfilled => {
+                    if handler_result.is_scheduled {
+                        handler_result
+                            .sender
+                            .send(RunResult::Scheduled(handler_result.start_time.elapsed()))
+                            .unwrap_or(());
+
+                        if should_send_statistics {
+                            send_statistics(options, try_catch);
+                        }
+
+                        return handler_result.keep_for_wait_until();
+                    }
+
                     let response = promise.result(try_catch);
+
+                    if let Some(websocket_id) = get_websocket_id(try_catch, response) {
+                        let receiver = websockets
+                            .get_mut(&websocket_id)
+                            .and_then(|websocket| websocket.receiver.take());
+
+                        let run_result = match (response_from_v8(try_catch, response), receiver) {
+                            (Ok((response_builder, ..)), Some(receiver)) => RunResult::WebSocket(
+                                response_builder,
+                                WebSocketChannel {
+                                    id: websocket_id,
+                                    receiver,
+                                },
+                            ),
+                            (Err(error), _) => RunResult::Error(error.to_string()),
+                            (_, None) => RunResult::Error(
+                                "WebSocket has already been accepted or closed".into(),
+                            ),
+                        };
+
+                        handler_result.sender.send(run_result).unwrap_or(());
+
+                        if should_send_statistics {
+                            send_statistics(options, try_catch);
+                        }
+
+                        return handler_result.keep_for_wait_until();
+                    }
+
                     let (run_result, is_streaming) = match response_from_v8(try_catch, response) {
                         Ok((response_builder, body, is_streaming)) => (
                             RunResult::Response(
@@ -695,7 +1387,7 @@ impl Isolate {
                         send_statistics(options, try_catch);
                     }
 
-                    false
+                    handler_result.keep_for_wait_until()
                 }
                 v8::PromiseState::Rejected => {
                     let exception = promise.result(try_catch);
@@ -703,7 +1395,10 @@ impl Isolate {
                     handler_result
                         .sender
                         .send(RunResult::Error(get_exception_message(
-                            try_catch, exception, lines,
+                            try_catch,
+                            exception,
+                            lines,
+                            source_map.as_deref(),
                         )))
                         .unwrap_or(());
 
@@ -711,7 +1406,7 @@ impl Isolate {
                         send_statistics(options, try_catch);
                     }
 
-                    false
+                    handler_result.keep_for_wait_until()
                 }
                 v8::PromiseState::Pending => {
                     if handler_result.start_time.elapsed() >= options.total_timeout {
@@ -724,6 +1419,50 @@ impl Isolate {
             }
         });
 
+        // Drop the bodies that haven't been fully read by requests that are
+        // now finished. Bodies created outside of a request (e.g when calling
+        // fetch() at the top-level) don't have a request id and are kept
+        let handler_results = &state.handler_results;
+        state.body_streams.retain(|_, body_stream| {
+            body_stream.request_id == 0 || handler_results.contains_key(&body_stream.request_id)
+        });
+
+        // Outbound connections are closed when the request that opened them
+        // finishes, unless it accepted a WebSocket (e.g to proxy it)
+        let accepted_requests = state
+            .websockets
+            .values()
+            .filter(|websocket| websocket.receiver.is_none() && websocket.incoming.is_none())
+            .map(|websocket| websocket.request_id)
+            .collect::<HashSet<_>>();
+        let is_request_open = |request_id: u32| {
+            request_id == 0
+                || handler_results.contains_key(&request_id)
+                || accepted_requests.contains(&request_id)
+        };
+
+        // Drop the sockets that were never returned by their request, and
+        // the accepted ones whose connection is gone (e.g the client left)
+        state.websockets.retain(
+            |_, websocket| match (&websocket.receiver, &websocket.incoming) {
+                (Some(_), _) => {
+                    websocket.request_id == 0 || handler_results.contains_key(&websocket.request_id)
+                }
+                (None, None) => !websocket.sender.is_disconnected(),
+                (None, Some(_)) => {
+                    !websocket.sender.is_disconnected() && is_request_open(websocket.request_id)
+                }
+            },
+        );
+        state
+            .sockets
+            .retain(|_, socket| is_request_open(socket.request_id));
+
+        // Roll back the transactions left open by finished requests
+        if let Some(db) = &state.db {
+            db.retain_transactions(|request_id| handler_results.contains_key(&request_id));
+        }
+
         cx.waker().wake_by_ref();
         Poll::Pending
     }
@@ -742,6 +1481,9 @@ impl Isolate {
         state.js_promises.clear();
         state.global.take();
 
+        // The snapshot creator expects the isolate to be entered
+        self.enter();
+
         self.isolate
             .take()
             .unwrap()
@@ -757,6 +1499,12 @@ impl Drop for Isolate {
         if let Some(on_drop) = &self.options.on_drop {
             on_drop(Rc::clone(&self.options.metadata));
         }
+
+        // Stop watching this isolate's heartbeats
+        self.watched.take();
+
+        // The isolate is exited when dropped
+        self.enter();
     }
 }
 
@@ -769,19 +1517,168 @@ pub fn send_statistics(options: &IsolateOptions, isolate: &mut v8::Isolate) {
     }
 }
 
+// Maps the name of each binding available in `ctx.env` to its kind
+fn create_bindings<'a>(
+    scope: &mut v8::HandleScope<'a>,
+    state: &IsolateState,
+) -> v8::Local<'a, v8::Object> {
+    let mut names: Vec<v8::Local<v8::Name>> = Vec::new();
+    let mut kinds: Vec<v8::Local<v8::Value>> = Vec::new();
+
+    // Service bindings named like the bindings below (KV, QUEUE and DB) are
+    // rejected when loading deployments and by `lagon dev`
+    for name in state.service_bindings.keys() {
+        names.push(v8_string(scope, name).into());
+        kinds.push(v8_string(scope, "service").into());
+    }
+
+    if state.kv.is_some() {
+        names.push(v8_string(scope, "KV").into());
+        kinds.push(v8_string(scope, "kv").into());
+    }
+
+    if state.queue.is_some() {
+        names.push(v8_string(scope, "QUEUE").into());
+        kinds.push(v8_string(scope, "queue").into());
+    }
+
+    if state.db.is_some() {
+        names.push(v8_string(scope, "DB").into());
+        kinds.push(v8_string(scope, "db").into());
+    }
+
+    let null = v8::null(scope).into();
+    v8::Object::with_prototype_and_properties(scope, null, &names, &kinds)
+}
+
+// Start handling a request (or a scheduled or queue event) by calling its master
+// handler, and keep its promise until it settles. The request id is
+// preserved across promise continuations, to know who made each call
+#[allow(clippy::too_many_arguments)]
+fn call_master_handler(
+    scope: &mut v8::TryCatch<v8::HandleScope>,
+    state: &Rc<RefCell<IsolateState>>,
+    termination_result: &RwLock<Option<RunResult>>,
+    master_handler: &v8::Function,
+    global: v8::Local<v8::Object>,
+    request_id: u32,
+    handler_result: HandlerResult,
+    args: &[v8::Local<v8::Value>],
+) {
+    let id = v8::Integer::new(scope, request_id as i32);
+    scope.set_continuation_preserved_embedder_data(id.into());
+
+    state
+        .borrow_mut()
+        .handler_results
+        .insert(request_id, handler_result);
+
+    match master_handler.call(scope, global.into(), args) {
+        Some(response) => {
+            let promise = v8::Local::<v8::Promise>::try_from(response)
+                .expect("Handler did not return a promise");
+            let promise = v8::Global::new(scope, promise);
+
+            if let Some(handler_result) = state.borrow_mut().handler_results.get_mut(&request_id) {
+                handler_result.promise = Some(promise);
+            }
+        }
+        None => {
+            // Use the current termination result (e.g timeout or memory),
+            // or try to handle an error
+            termination_result
+                .write()
+                .unwrap()
+                .get_or_insert_with(|| handle_error(scope, 0, None));
+        }
+    };
+}
+
+fn send_log(
+    log_sender: &Option<flume::Sender<(String, String, Metadata)>>,
+    metadata: &Metadata,
+    level: &str,
+    content: String,
+) {
+    if let Some(log_sender) = log_sender {
+        log_sender
+            .send((level.to_string(), content, metadata.clone()))
+            .unwrap_or(());
+    }
+}
+
+// Environment variables are set on `process.env` as a frozen object before
+// evaluating any code. The JS runtime keeps `process.env` if it already exists
+fn set_environment_variables(
+    scope: &mut v8::HandleScope,
+    environment_variables: Option<&HashMap<String, String>>,
+) {
+    let env = v8::Object::new(scope);
+
+    for (key, value) in environment_variables.into_iter().flatten() {
+        let key = v8_string(scope, key);
+        let value = v8_string(scope, value);
+
+        // Define the properties instead of setting them, to
+        // not trigger any setter (e.g for a `__proto__` key)
+        env.create_data_property(scope, key.into(), value.into());
+    }
+
+    env.set_integrity_level(scope, v8::IntegrityLevel::Frozen);
+
+    let global = scope.get_current_context().global(scope);
+    let process_key = v8_string(scope, "process");
+    let process = global
+        .get(scope, process_key.into())
+        .and_then(|process| v8::Local::<v8::Object>::try_from(process).ok());
+
+    let process = match process {
+        Some(process) => process,
+        None => {
+            let process = v8::Object::new(scope);
+            global.set(scope, process_key.into(), process.into());
+            process
+        }
+    };
+
+    let env_key = v8_string(scope, "env");
+    process.set(scope, env_key.into(), env.into());
+}
+
+// Rewrite a location of the bundled code to the original
+// file, line and column using the deployment's source map
+fn map_location(
+    source_map: &SourceMap,
+    line: usize,
+    column: usize,
+) -> Option<(String, Option<String>)> {
+    let token = source_map.lookup_token(
+        line.saturating_sub(1) as u32,
+        column.saturating_sub(1) as u32,
+    )?;
+    let location = format!(
+        "{}:{}:{}",
+        token.get_source().unwrap_or(CODE_ONLY_SCRIPT_NAME),
+        token.get_src_line() + 1,
+        token.get_src_col() + 1,
+    );
+
+    Some((location, token.get_name().map(String::from)))
+}
+
 pub fn get_exception_message(
     scope: &mut v8::TryCatch<v8::HandleScope>,
     exception: v8::Local<v8::Value>,
     lines: usize,
+    source_map: Option<&SourceMap>,
 ) -> String {
     let exception_message = v8::Exception::create_message(scope, exception);
     let message = exception_message.get(scope).to_rust_string_lossy(scope);
 
     if let Some(stack_trace) = exception_message.get_stack_trace(scope) {
-        let frames = stack_trace.get_frame_count();
-        let mut formatted = String::new();
+        let mut frames = Vec::new();
 
-        for i in 0..frames {
+        for i in 0..stack_trace.get_frame_count() {
             if let Some(frame) = stack_trace.get_frame(scope, i) {
                 let script_name = frame
                     .get_script_name(scope)
@@ -793,20 +1690,54 @@ pub fn get_exception_message(
                     continue;
                 }
 
-                let location =
-                    format!("{}:{}", frame.get_line_number() - lines, frame.get_column());
+                let function_name = frame
+                    .get_function_name(scope)
+                    .map(|function_name| function_name.to_rust_string_lossy(scope));
 
-                let frame = if let Some(function_name) = frame.get_function_name(scope) {
-                    format!(
-                        "\n  at {} ({})",
-                        function_name.to_rust_string_lossy(scope),
-                        location,
-                    )
-                } else {
-                    format!("\n  at {location}")
+                frames.push((
+                    frame.get_line_number() - lines,
+                    frame.get_column(),
+                    function_name,
+                ));
+            }
+        }
+
+        let mut formatted = String::new();
+
+        for (index, (line, column, function_name)) in frames.iter().enumerate() {
+            let (location, function_name) =
+                match source_map.and_then(|source_map| map_location(source_map, *line, *column)) {
+                    Some((location, _)) => {
+                        // The original name of a function can be found at the location
+                        // where it was called, which is the location of the next frame
+                        let original_name = frames.get(index + 1).and_then(|(line, column, _)| {
+                            map_location(source_map.unwrap(), *line, *column)?.1
+                        });
+
+                        (location, original_name.or_else(|| function_name.clone()))
+                    }
+                    None => (format!("{line}:{column}"), function_name.clone()),
                 };
 
-                formatted.push_str(&frame);
+            let frame = if let Some(function_name) = function_name {
+                format!("\n  at {function_name} ({location})")
+            } else {
+                format!("\n  at {location}")
+            };
+
+            formatted.push_str(&frame);
+        }
+
+        // Errors thrown while compiling don't have a stack trace,
+        // but we can still point to their original location
+        if frames.is_empty() {
+            if let Some(location) = source_map.and_then(|source_map| {
+                let line = exception_message.get_line_number(scope)?;
+                let column = exception_message.get_start_column() + 1;
+
+                map_location(source_map, line.checked_sub(lines)?, column)
+            }) {
+                formatted.push_str(&format!("\n  at {}", location.0));
             }
         }
 
@@ -820,9 +1751,25 @@ pub fn get_exception_message(
     message
 }
 
-fn handle_error(scope: &mut v8::TryCatch<v8::HandleScope>, lines: usize) -> RunResult {
+// Responses accepting a WebSocket contain the id of the socket
+fn get_websocket_id(scope: &mut v8::HandleScope, response: v8::Local<v8::Value>) -> Option<u32> {
+    let response = response.to_object(scope)?;
+    let websocket_key = v8_string(scope, "w");
+    let websocket_id = response.get(scope, websocket_key.into())?;
+
+    match websocket_id.is_null_or_undefined() {
+        true => None,
+        false => websocket_id.uint32_value(scope),
+    }
+}
+
+fn handle_error(
+    scope: &mut v8::TryCatch<v8::HandleScope>,
+    lines: usize,
+    source_map: Option<&SourceMap>,
+) -> RunResult {
     if let Some(exception) = scope.exception() {
-        return RunResult::Error(get_exception_message(scope, exception, lines));
+        return RunResult::Error(get_exception_message(scope, exception, lines, source_map));
     }
 
     RunResult::Error("Unknown error".into())
@@ -1,22 +1,56 @@
+use lagon_runtime_cache::CacheStorage;
+use lagon_runtime_db::Database;
+use lagon_runtime_kv::KvNamespace;
+use lagon_runtime_queue::Queue;
 use lagon_runtime_v8_utils::v8_string;
-use std::{collections::HashMap, rc::Rc, time::Duration};
+use std::{collections::HashMap, rc::Rc, sync::Arc, time::Duration};
+
+use crate::{fetch_handler::FetchHandler, fetch_policy::FetchPolicy};
 
 const JS_RUNTIME: &str = include_str!("../runtime.js");
 
 pub type Metadata = Option<(String, String)>;
 type OnIsolateDropCallback = Box<dyn Fn(Rc<Metadata>)>;
 type OnIsolateStatisticsCallback = Box<dyn Fn(Rc<Metadata>, usize)>;
+type OnIsolateColdStartCallback = Box<dyn Fn(Rc<Metadata>, ColdStart)>;
+
+// Sent once the isolate's code has been evaluated. `code_cache` contains
+// a new code cache when none was given, or when V8 rejected it
+pub struct ColdStart {
+    pub duration: Duration,
+    pub cache_hit: bool,
+    pub code_cache: Option<Vec<u8>>,
+}
 
 pub struct IsolateOptions {
     pub code: String,
+    pub source_map: Option<String>,
+    pub code_cache: Option<Vec<u8>>,
     pub environment_variables: Option<HashMap<String, String>>,
+    pub fetch_policy: Option<Arc<FetchPolicy>>,
+    // Defaults to sending requests over the network
+    pub fetch_handler: Option<Arc<dyn FetchHandler>>,
+    // Exposed as `ctx.env.<NAME>.fetch()` to the handler
+    pub service_bindings: HashMap<String, Arc<dyn FetchHandler>>,
+    // Exposed as `ctx.env.KV` to the handler
+    pub kv: Option<KvNamespace>,
+    // Exposed as `ctx.env.QUEUE` to the handler
+    pub queue: Option<Queue>,
+    // Exposed as `caches` to the code
+    pub cache: Option<CacheStorage>,
+    // Exposed as `ctx.env.DB` to the handler
+    pub db: Option<Database>,
     pub memory: usize, // in MB (MegaBytes)
     pub tick_timeout: Duration,
     pub total_timeout: Duration,
+    // How long the promises passed to `ctx.waitUntil()` can
+    // keep running once the response has been sent
+    pub wait_until_timeout: Duration,
     pub statistics_interval: Duration,
     pub metadata: Rc<Metadata>,
     pub on_drop: Option<OnIsolateDropCallback>,
     pub on_statistics: Option<OnIsolateStatisticsCallback>,
+    pub on_cold_start: Option<OnIsolateColdStartCallback>,
     pub log_sender: Option<flume::Sender<(String, String, Metadata)>>,
     pub snapshot: bool,
     pub snapshot_blob: Option<&'static [u8]>,
@@ -28,25 +62,84 @@ impl IsolateOptions {
     pub fn new(code: String) -> Self {
         Self {
             code,
+            source_map: None,
+            code_cache: None,
             environment_variables: None,
+            fetch_policy: None,
+            fetch_handler: None,
+            service_bindings: HashMap::new(),
+            kv: None,
+            queue: None,
+            cache: None,
+            db: None,
             tick_timeout: Duration::from_millis(200),
             total_timeout: Duration::from_secs(1),
+            wait_until_timeout: Duration::from_secs(30),
             statistics_interval: Duration::from_secs(1),
             memory: 128,
             metadata: Rc::new(None),
             on_drop: None,
             on_statistics: None,
+            on_cold_start: None,
             snapshot: false,
             snapshot_blob: None,
             log_sender: None,
         }
     }
 
+    pub fn source_map(mut self, source_map: Option<String>) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    pub fn code_cache(mut self, code_cache: Option<Vec<u8>>) -> Self {
+        self.code_cache = code_cache;
+        self
+    }
+
     pub fn environment_variables(mut self, environment_variables: HashMap<String, String>) -> Self {
         self.environment_variables = Some(environment_variables);
         self
     }
 
+    pub fn fetch_policy(mut self, fetch_policy: Arc<FetchPolicy>) -> Self {
+        self.fetch_policy = Some(fetch_policy);
+        self
+    }
+
+    pub fn fetch_handler(mut self, fetch_handler: Arc<dyn FetchHandler>) -> Self {
+        self.fetch_handler = Some(fetch_handler);
+        self
+    }
+
+    pub fn service_bindings(
+        mut self,
+        service_bindings: HashMap<String, Arc<dyn FetchHandler>>,
+    ) -> Self {
+        self.service_bindings = service_bindings;
+        self
+    }
+
+    pub fn kv(mut self, kv: Option<KvNamespace>) -> Self {
+        self.kv = kv;
+        self
+    }
+
+    pub fn queue(mut self, queue: Option<Queue>) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    pub fn cache(mut self, cache: Option<CacheStorage>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn db(mut self, db: Option<Database>) -> Self {
+        self.db = db;
+        self
+    }
+
     pub fn tick_timeout(mut self, tick_timeout: Duration) -> Self {
         self.tick_timeout = tick_timeout;
         self
@@ -57,6 +150,11 @@ impl IsolateOptions {
         self
     }
 
+    pub fn wait_until_timeout(mut self, wait_until_timeout: Duration) -> Self {
+        self.wait_until_timeout = wait_until_timeout;
+        self
+    }
+
     pub fn memory(mut self, memory: usize) -> Self {
         self.memory = memory;
         self
@@ -77,6 +175,11 @@ impl IsolateOptions {
         self
     }
 
+    pub fn on_cold_start_callback(mut self, on_cold_start: OnIsolateColdStartCallback) -> Self {
+        self.on_cold_start = Some(on_cold_start);
+        self
+    }
+
     pub fn log_sender(mut self, log_sender: flume::Sender<(String, String, Metadata)>) -> Self {
         self.log_sender = Some(log_sender);
         self
@@ -104,34 +207,14 @@ impl IsolateOptions {
     ) -> (v8::Local<'a, v8::String>, usize) {
         let IsolateOptions {
             code,
-            environment_variables,
             snapshot,
             snapshot_blob,
             ..
         } = self;
 
-        let environment_variables = match environment_variables {
-            Some(environment_variables) => environment_variables
-                .iter()
-                .map(|(k, v)| format!("globalThis.process.env.{k} = '{v}'"))
-                .collect::<Vec<String>>()
-                .join("\n"),
-            None => "".to_string(),
-        };
-
         if snapshot_blob.is_some() {
             // If we have a snapshot, only return the isolate's code
-            // and the environment variables
-            (
-                v8_string(
-                    scope,
-                    &format!(
-                        r"{environment_variables}
-{code}"
-                    ),
-                ),
-                environment_variables.lines().count() + 1,
-            )
+            (v8_string(scope, code), 0)
         } else if *snapshot {
             // If we are currently making a snapshot, only return
             // the js runtime code
@@ -144,11 +227,10 @@ impl IsolateOptions {
                     scope,
                     &format!(
                         r"{JS_RUNTIME}
-{environment_variables}
 {code}"
                     ),
                 ),
-                JS_RUNTIME.lines().count() + environment_variables.lines().count() + 2,
+                JS_RUNTIME.lines().count() + 1,
             )
         }
     }
@@ -0,0 +1,95 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::available_parallelism,
+};
+use tokio::{runtime::Handle, task::LocalSet};
+
+use crate::Isolate;
+
+type CreateIsolate = Box<dyn FnOnce() -> Isolate + Send>;
+
+struct Worker {
+    sender: flume::Sender<CreateIsolate>,
+    isolates: Arc<AtomicUsize>,
+}
+
+// Runs many isolates on a fixed number of threads. Each worker thread drives
+// its isolates' event loops cooperatively on a `LocalSet`: an isolate that
+// doesn't have anything to do yields to the other isolates of the same thread.
+//
+// Isolates never move between threads once created, so they only need to be
+// entered and exited around each poll instead of being locked.
+pub struct Scheduler {
+    workers: Vec<Worker>,
+}
+
+impl Scheduler {
+    pub fn new(threads: usize) -> Self {
+        let handle = Handle::current();
+        let mut workers = Vec::with_capacity(threads);
+
+        for index in 0..threads.max(1) {
+            let (sender, receiver) = flume::unbounded::<CreateIsolate>();
+            let isolates = Arc::new(AtomicUsize::new(0));
+            let isolates_handle = Arc::clone(&isolates);
+            let handle = handle.clone();
+
+            std::thread::Builder::new()
+                .name(format!("isolates-worker-{index}"))
+                .spawn(move || {
+                    let local = LocalSet::new();
+
+                    handle.block_on(local.run_until(async move {
+                        while let Ok(create_isolate) = receiver.recv_async().await {
+                            let isolates = Arc::clone(&isolates_handle);
+
+                            tokio::task::spawn_local(async move {
+                                let mut isolate = create_isolate();
+                                isolate.evaluate();
+                                isolate.run_event_loop().await;
+
+                                // Make sure the isolate is dropped before
+                                // decrementing the number of isolates
+                                drop(isolate);
+                                isolates.fetch_sub(1, Ordering::Relaxed);
+                            });
+                        }
+                    }));
+                })
+                .unwrap();
+
+            workers.push(Worker { sender, isolates });
+        }
+
+        Self { workers }
+    }
+
+    // Use as many worker threads as there are available cores
+    pub fn with_available_parallelism() -> Self {
+        Self::new(available_parallelism().map_or(1, |threads| threads.get()))
+    }
+
+    // Create a new isolate on the worker thread running the least
+    // isolates, then evaluate it and run its event loop until it
+    // is terminated. `create_isolate` is called on the worker thread.
+    pub fn spawn<F>(&self, create_isolate: F)
+    where
+        F: FnOnce() -> Isolate + Send + 'static,
+    {
+        let worker = self
+            .workers
+            .iter()
+            .min_by_key(|worker| worker.isolates.load(Ordering::Relaxed))
+            .unwrap();
+
+        worker.isolates.fetch_add(1, Ordering::Relaxed);
+        worker.sender.send(Box::new(create_isolate)).unwrap_or(());
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+}
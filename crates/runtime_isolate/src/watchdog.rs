@@ -0,0 +1,144 @@
+use lagon_runtime_http::RunResult;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+// How long the watchdog thread sleeps when it doesn't watch any isolate
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+static WATCHDOG: OnceLock<flume::Sender<Weak<WatchedIsolate>>> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum Heartbeat {
+    None,
+    Some,
+    Waiting,
+}
+
+impl Heartbeat {
+    pub fn is_waiting(&self) -> bool {
+        matches!(self, Heartbeat::Waiting)
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Heartbeat::None)
+    }
+}
+
+pub struct WatchedIsolate {
+    pub tick_timeout: Duration,
+    pub heartbeat: Arc<RwLock<Heartbeat>>,
+    pub running: Arc<AtomicBool>,
+    pub termination_result: Arc<RwLock<Option<RunResult>>>,
+    pub handle: v8::IsolateHandle,
+}
+
+struct Entry {
+    isolate: Weak<WatchedIsolate>,
+    next_check: Instant,
+    missed_heartbeat: u32,
+}
+
+impl Entry {
+    // Returns false when the isolate has been dropped or
+    // terminated, meaning we don't need to watch it anymore
+    fn check(&mut self) -> bool {
+        let isolate = match self.isolate.upgrade() {
+            Some(isolate) => isolate,
+            None => return false,
+        };
+
+        self.next_check += isolate.tick_timeout;
+
+        let heartbeat_value = isolate.heartbeat.read().unwrap();
+
+        // An isolate that isn't running can't send heartbeats, e.g when another
+        // isolate of the same worker thread is blocking it. Only the isolate
+        // currently running on a thread can miss heartbeats
+        if heartbeat_value.is_waiting() || !isolate.running.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if heartbeat_value.is_none() {
+            self.missed_heartbeat += 1;
+        } else if self.missed_heartbeat > 0 {
+            self.missed_heartbeat -= 1;
+        }
+
+        if self.missed_heartbeat >= 2 {
+            isolate
+                .termination_result
+                .write()
+                .unwrap()
+                .replace(RunResult::Timeout);
+
+            if !isolate.handle.is_execution_terminating() {
+                isolate.handle.terminate_execution();
+            }
+
+            return false;
+        }
+
+        drop(heartbeat_value);
+        *isolate.heartbeat.write().unwrap() = Heartbeat::None;
+
+        true
+    }
+}
+
+// A single thread watches the heartbeats of all the isolates, instead
+// of spawning one thread per isolate.
+//
+// Isolates are terminated when they miss at least two heartbeats. The heartbeat
+// missed count isn't reset to zero when a heartbeat has been successfully received:
+// instead, the heartbeat missed count is decremented by one, allowing to safely
+// terminate faulty isolates that are stuck in an infinite loop, and not randomly
+// terminate isolates that just happen to be "slow"
+fn run(receiver: flume::Receiver<Weak<WatchedIsolate>>) {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    loop {
+        let deadline = entries
+            .iter()
+            .map(|entry| entry.next_check)
+            .min()
+            .unwrap_or_else(|| Instant::now() + IDLE_INTERVAL);
+
+        match receiver.recv_deadline(deadline) {
+            Ok(isolate) => {
+                if let Some(tick_timeout) = isolate.upgrade().map(|isolate| isolate.tick_timeout) {
+                    entries.push(Entry {
+                        isolate,
+                        next_check: Instant::now() + tick_timeout,
+                        missed_heartbeat: 0,
+                    });
+                }
+            }
+            Err(flume::RecvTimeoutError::Timeout) => {}
+            Err(flume::RecvTimeoutError::Disconnected) => return,
+        }
+
+        let now = Instant::now();
+
+        entries.retain_mut(|entry| entry.next_check > now || entry.check());
+    }
+}
+
+pub fn watch(isolate: &Arc<WatchedIsolate>) {
+    let sender = WATCHDOG.get_or_init(|| {
+        let (sender, receiver) = flume::unbounded();
+
+        std::thread::Builder::new()
+            .name(String::from("isolates-watchdog"))
+            .spawn(move || run(receiver))
+            .unwrap();
+
+        sender
+    });
+
+    sender.send(Arc::downgrade(isolate)).unwrap_or(());
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, sync::Mutex};
+
+use crate::{list_entries, KvEntry, KvStore, ListOptions, ListResult};
+
+// Persists each namespace as a JSON file inside a local directory, so
+// data survives restarts of `lagon dev`. Not meant for large datasets,
+// since a namespace is read and written entirely on each operation
+pub struct DiskKvStore {
+    root: PathBuf,
+    // Prevents concurrent writes from overwriting each other
+    lock: Mutex<()>,
+}
+
+impl DiskKvStore {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    // Namespace names are validated by `KvNamespace`,
+    // so they can safely be used as file names
+    fn path(&self, namespace: &str) -> PathBuf {
+        self.root.join(format!("{namespace}.json"))
+    }
+
+    async fn read(&self, namespace: &str) -> Result<BTreeMap<String, KvEntry>> {
+        match fs::read(self.path(namespace)).await {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn write(&self, namespace: &str, entries: &BTreeMap<String, KvEntry>) -> Result<()> {
+        fs::create_dir_all(&self.root).await?;
+
+        // Write to a temporary file first, so a crash can't leave a corrupted namespace
+        let path = self.path(namespace);
+        let tmp_path = path.with_extension("json.tmp");
+
+        fs::write(&tmp_path, serde_json::to_vec(entries)?).await?;
+        fs::rename(tmp_path, path).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl KvStore for DiskKvStore {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<KvEntry>> {
+        let _lock = self.lock.lock().await;
+        let mut entries = self.read(namespace).await?;
+
+        Ok(entries.remove(key))
+    }
+
+    async fn put(&self, namespace: &str, key: &str, entry: KvEntry) -> Result<()> {
+        let _lock = self.lock.lock().await;
+        let mut entries = self.read(namespace).await?;
+
+        entries.insert(key.to_string(), entry);
+        self.write(namespace, &entries).await
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<()> {
+        let _lock = self.lock.lock().await;
+        let mut entries = self.read(namespace).await?;
+
+        if entries.remove(key).is_some() {
+            self.write(namespace, &entries).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, namespace: &str, options: ListOptions) -> Result<ListResult> {
+        let _lock = self.lock.lock().await;
+        let entries = self.read(namespace).await?;
+
+        Ok(list_entries(&entries, &options))
+    }
+}
@@ -0,0 +1,451 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Bound,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+mod disk;
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "redis")]
+pub use crate::redis::RedisKvStore;
+pub use disk::DiskKvStore;
+pub use memory::MemoryKvStore;
+
+pub const MAX_KEY_SIZE: usize = 512;
+pub const MAX_VALUE_SIZE: usize = 25 * 1024 * 1024; // 25MB
+pub const MAX_METADATA_SIZE: usize = 1024;
+pub const MAX_LIST_LIMIT: usize = 1000;
+
+// Returned when `now + expiration_ttl` overflows, and rejected as a
+// `TypeError` in JS
+#[derive(Debug)]
+pub struct ExpirationTtlTooLarge;
+
+impl fmt::Display for ExpirationTtlTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KV expirationTtl is too large")
+    }
+}
+
+impl std::error::Error for ExpirationTtlTooLarge {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvEntry {
+    #[serde(with = "base64_bytes")]
+    pub value: Vec<u8>,
+    // Serialized as JSON by the JS runtime
+    pub metadata: Option<String>,
+    // Unix timestamp, in seconds
+    pub expiration: Option<u64>,
+}
+
+impl KvEntry {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiration
+            .map_or(false, |expiration| expiration <= now)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KvKey {
+    pub name: String,
+    pub metadata: Option<String>,
+    pub expiration: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PutOptions {
+    pub metadata: Option<String>,
+    // Unix timestamp, in seconds
+    pub expiration: Option<u64>,
+    // In seconds, from now
+    pub expiration_ttl: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    pub prefix: String,
+    pub limit: usize,
+    pub cursor: Option<String>,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            limit: MAX_LIST_LIMIT,
+            cursor: None,
+        }
+    }
+}
+
+// Keys are listed in lexicographic order. `cursor` is set when there
+// might be more keys to list, and should be passed to the next call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ListResult {
+    pub keys: Vec<KvKey>,
+    pub cursor: Option<String>,
+}
+
+// A storage backend shared by all the namespaces. Backends don't need to
+// validate keys and values, which is done by `KvNamespace` beforehand
+#[async_trait]
+pub trait KvStore: Send + Sync {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<KvEntry>>;
+    async fn put(&self, namespace: &str, key: &str, entry: KvEntry) -> Result<()>;
+    async fn delete(&self, namespace: &str, key: &str) -> Result<()>;
+    async fn list(&self, namespace: &str, options: ListOptions) -> Result<ListResult>;
+}
+
+// The only way isolates can access a store. Each Function gets its own
+// namespace, so tenants can never read or write each other's keys
+#[derive(Clone)]
+pub struct KvNamespace {
+    store: Arc<dyn KvStore>,
+    name: String,
+}
+
+impl KvNamespace {
+    pub fn new(store: Arc<dyn KvStore>, name: String) -> Result<Self> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+        {
+            return Err(anyhow!("Invalid KV namespace name {}", name));
+        }
+
+        Ok(Self { store, name })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<KvEntry>> {
+        validate_key(key)?;
+
+        let entry = self.store.get(&self.name, key).await?;
+
+        Ok(entry.filter(|entry| !entry.is_expired(now())))
+    }
+
+    pub async fn put(&self, key: &str, value: Vec<u8>, options: PutOptions) -> Result<()> {
+        validate_key(key)?;
+
+        if value.len() > MAX_VALUE_SIZE {
+            return Err(anyhow!(
+                "KV value exceeds the maximum size of {} bytes",
+                MAX_VALUE_SIZE
+            ));
+        }
+
+        if let Some(metadata) = &options.metadata {
+            if metadata.len() > MAX_METADATA_SIZE {
+                return Err(anyhow!(
+                    "KV metadata exceeds the maximum size of {} bytes",
+                    MAX_METADATA_SIZE
+                ));
+            }
+        }
+
+        let now = now();
+        let expiration = match (options.expiration, options.expiration_ttl) {
+            (_, Some(expiration_ttl)) => Some(
+                now.checked_add(expiration_ttl)
+                    .ok_or(ExpirationTtlTooLarge)?,
+            ),
+            (Some(expiration), None) => Some(expiration),
+            (None, None) => None,
+        };
+
+        if let Some(expiration) = expiration {
+            if expiration <= now {
+                return Err(anyhow!("KV expiration must be in the future"));
+            }
+        }
+
+        self.store
+            .put(
+                &self.name,
+                key,
+                KvEntry {
+                    value,
+                    metadata: options.metadata,
+                    expiration,
+                },
+            )
+            .await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        validate_key(key)?;
+
+        self.store.delete(&self.name, key).await
+    }
+
+    pub async fn list(&self, options: ListOptions) -> Result<ListResult> {
+        if options.limit == 0 || options.limit > MAX_LIST_LIMIT {
+            return Err(anyhow!(
+                "KV list limit must be between 1 and {}",
+                MAX_LIST_LIMIT
+            ));
+        }
+
+        self.store.list(&self.name, options).await
+    }
+}
+
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(anyhow!("KV key can't be empty"));
+    }
+
+    if key.len() > MAX_KEY_SIZE {
+        return Err(anyhow!(
+            "KV key exceeds the maximum size of {} bytes",
+            MAX_KEY_SIZE
+        ));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+// Used by the backends keeping a namespace in a sorted map
+pub(crate) fn list_entries(
+    entries: &BTreeMap<String, KvEntry>,
+    options: &ListOptions,
+) -> ListResult {
+    let now = now();
+    let start = match &options.cursor {
+        Some(cursor) if cursor.as_str() >= options.prefix.as_str() => {
+            Bound::Excluded(cursor.clone())
+        }
+        _ => Bound::Included(options.prefix.clone()),
+    };
+
+    let mut keys = Vec::new();
+    let mut cursor = None;
+
+    for (name, entry) in entries.range((start, Bound::Unbounded)) {
+        if !name.starts_with(&options.prefix) {
+            break;
+        }
+
+        if entry.is_expired(now) {
+            continue;
+        }
+
+        if keys.len() == options.limit {
+            cursor = keys.last().map(|key: &KvKey| key.name.clone());
+            break;
+        }
+
+        keys.push(KvKey {
+            name: name.clone(),
+            metadata: entry.metadata.clone(),
+            expiration: entry.expiration,
+        });
+    }
+
+    ListResult { keys, cursor }
+}
+
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        STANDARD.decode(value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn namespace() -> KvNamespace {
+        KvNamespace::new(Arc::new(MemoryKvStore::default()), "function".into()).unwrap()
+    }
+
+    #[test]
+    fn invalid_namespace_name() {
+        let store = Arc::new(MemoryKvStore::default());
+
+        assert!(KvNamespace::new(store.clone(), "".into()).is_err());
+        assert!(KvNamespace::new(store, "a:b".into()).is_err());
+    }
+
+    #[tokio::test]
+    async fn put_get_delete() {
+        let namespace = namespace();
+
+        namespace
+            .put(
+                "key",
+                b"value".to_vec(),
+                PutOptions {
+                    metadata: Some("{\"a\":1}".into()),
+                    ..PutOptions::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let entry = namespace.get("key").await.unwrap().unwrap();
+        assert_eq!(entry.value, b"value");
+        assert_eq!(entry.metadata, Some("{\"a\":1}".into()));
+
+        namespace.delete("key").await.unwrap();
+        assert_eq!(namespace.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn namespaces_are_isolated() {
+        let store: Arc<dyn KvStore> = Arc::new(MemoryKvStore::default());
+        let first = KvNamespace::new(Arc::clone(&store), "first".into()).unwrap();
+        let second = KvNamespace::new(store, "second".into()).unwrap();
+
+        first
+            .put("key", b"value".to_vec(), PutOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(second.get("key").await.unwrap(), None);
+        assert!(second
+            .list(ListOptions::default())
+            .await
+            .unwrap()
+            .keys
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_entries() {
+        let namespace = namespace();
+
+        assert!(namespace
+            .put(
+                "key",
+                b"value".to_vec(),
+                PutOptions {
+                    expiration: Some(1),
+                    ..PutOptions::default()
+                },
+            )
+            .await
+            .is_err());
+
+        namespace
+            .store
+            .put(
+                "function",
+                "key",
+                KvEntry {
+                    value: b"value".to_vec(),
+                    metadata: None,
+                    expiration: Some(now() - 1),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(namespace.get("key").await.unwrap(), None);
+        assert!(namespace
+            .list(ListOptions::default())
+            .await
+            .unwrap()
+            .keys
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn expiration_ttl_overflow() {
+        let namespace = namespace();
+
+        let error = namespace
+            .put(
+                "key",
+                b"value".to_vec(),
+                PutOptions {
+                    expiration_ttl: Some(u64::MAX),
+                    ..PutOptions::default()
+                },
+            )
+            .await
+            .unwrap_err();
+
+        assert!(error.is::<ExpirationTtlTooLarge>());
+        assert_eq!(namespace.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn list_with_prefix_and_cursor() {
+        let namespace = namespace();
+
+        for key in ["a", "user:1", "user:2", "user:3", "z"] {
+            namespace
+                .put(key, Vec::new(), PutOptions::default())
+                .await
+                .unwrap();
+        }
+
+        let result = namespace
+            .list(ListOptions {
+                prefix: "user:".into(),
+                limit: 2,
+                cursor: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result
+                .keys
+                .iter()
+                .map(|key| key.name.as_str())
+                .collect::<Vec<_>>(),
+            ["user:1", "user:2"]
+        );
+        assert_eq!(result.cursor, Some("user:2".into()));
+
+        let result = namespace
+            .list(ListOptions {
+                prefix: "user:".into(),
+                limit: 2,
+                cursor: result.cursor,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result
+                .keys
+                .iter()
+                .map(|key| key.name.as_str())
+                .collect::<Vec<_>>(),
+            ["user:3"]
+        );
+        assert_eq!(result.cursor, None);
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::{list_entries, now, KvEntry, KvStore, ListOptions, ListResult};
+
+// Keeps everything in memory, used for tests and `lagon dev`
+#[derive(Default)]
+pub struct MemoryKvStore {
+    namespaces: Mutex<HashMap<String, BTreeMap<String, KvEntry>>>,
+}
+
+#[async_trait]
+impl KvStore for MemoryKvStore {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<KvEntry>> {
+        let mut namespaces = self.namespaces.lock().unwrap();
+
+        let entries = match namespaces.get_mut(namespace) {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+
+        // Expired entries are removed lazily
+        if entries
+            .get(key)
+            .map_or(false, |entry| entry.is_expired(now()))
+        {
+            entries.remove(key);
+        }
+
+        Ok(entries.get(key).cloned())
+    }
+
+    async fn put(&self, namespace: &str, key: &str, entry: KvEntry) -> Result<()> {
+        self.namespaces
+            .lock()
+            .unwrap()
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(key.to_string(), entry);
+
+        Ok(())
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<()> {
+        if let Some(entries) = self.namespaces.lock().unwrap().get_mut(namespace) {
+            entries.remove(key);
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, namespace: &str, options: ListOptions) -> Result<ListResult> {
+        let namespaces = self.namespaces.lock().unwrap();
+
+        Ok(match namespaces.get(namespace) {
+            Some(entries) => list_entries(entries, &options),
+            None => ListResult {
+                keys: Vec::new(),
+                cursor: None,
+            },
+        })
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, Client};
+
+use crate::{now, KvEntry, KvKey, KvStore, ListOptions, ListResult};
+
+// Each entry is a hash with its value, metadata and expiration, which Redis expires
+// on its own. A sorted set per namespace keeps the keys in lexicographic order
+// to list them, where expired keys are removed lazily when listing
+pub struct RedisKvStore {
+    connection: ConnectionManager,
+}
+
+impl RedisKvStore {
+    pub async fn new(url: String) -> Result<Self> {
+        let client = Client::open(url)?;
+        let connection = ConnectionManager::new(client).await?;
+
+        Ok(Self { connection })
+    }
+}
+
+fn entry_key(namespace: &str, key: &str) -> String {
+    format!("lagon:kv:{namespace}:entries:{key}")
+}
+
+fn index_key(namespace: &str) -> String {
+    format!("lagon:kv:{namespace}:keys")
+}
+
+type RawEntry = (Option<Vec<u8>>, Option<String>, Option<u64>);
+
+#[async_trait]
+impl KvStore for RedisKvStore {
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<KvEntry>> {
+        let mut connection = self.connection.clone();
+
+        let (value, metadata, expiration): RawEntry = redis::cmd("HMGET")
+            .arg(entry_key(namespace, key))
+            .arg("v")
+            .arg("m")
+            .arg("e")
+            .query_async(&mut connection)
+            .await?;
+
+        Ok(value.map(|value| KvEntry {
+            value,
+            metadata,
+            expiration,
+        }))
+    }
+
+    async fn put(&self, namespace: &str, key: &str, entry: KvEntry) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let entry_key = entry_key(namespace, key);
+
+        let mut pipeline = redis::pipe();
+        pipeline.atomic().del(&entry_key).ignore();
+        pipeline.hset(&entry_key, "v", entry.value).ignore();
+
+        if let Some(metadata) = entry.metadata {
+            pipeline.hset(&entry_key, "m", metadata).ignore();
+        }
+
+        if let Some(expiration) = entry.expiration {
+            pipeline.hset(&entry_key, "e", expiration).ignore();
+            pipeline
+                .cmd("EXPIREAT")
+                .arg(&entry_key)
+                .arg(expiration)
+                .ignore();
+        }
+
+        pipeline.zadd(index_key(namespace), key, 0).ignore();
+        pipeline.query_async(&mut connection).await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<()> {
+        let mut connection = self.connection.clone();
+
+        redis::pipe()
+            .atomic()
+            .del(entry_key(namespace, key))
+            .ignore()
+            .zrem(index_key(namespace), key)
+            .ignore()
+            .query_async(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list(&self, namespace: &str, options: ListOptions) -> Result<ListResult> {
+        let mut connection = self.connection.clone();
+        let index_key = index_key(namespace);
+        let now = now();
+
+        let mut min = match &options.cursor {
+            Some(cursor) if cursor.as_str() >= options.prefix.as_str() => format!("({cursor}"),
+            _ => format!("[{}", options.prefix),
+        };
+        // Fetch one more key than needed to know if there are more keys to list
+        let batch_size = options.limit + 1;
+        let mut keys = Vec::new();
+
+        loop {
+            let names: Vec<String> = redis::cmd("ZRANGEBYLEX")
+                .arg(&index_key)
+                .arg(&min)
+                .arg("+")
+                .arg("LIMIT")
+                .arg(0)
+                .arg(batch_size)
+                .query_async(&mut connection)
+                .await?;
+
+            let last = match names.last() {
+                Some(last) => format!("({last}"),
+                None => return Ok(ListResult { keys, cursor: None }),
+            };
+            let mut pipeline = redis::pipe();
+
+            for name in &names {
+                pipeline
+                    .cmd("HMGET")
+                    .arg(entry_key(namespace, name))
+                    .arg("v")
+                    .arg("m")
+                    .arg("e");
+            }
+
+            let entries: Vec<RawEntry> = pipeline.query_async(&mut connection).await?;
+            let mut expired = Vec::new();
+            let mut result = None;
+
+            for (name, (value, metadata, expiration)) in names.iter().zip(entries) {
+                if !name.starts_with(&options.prefix) {
+                    result = Some(None);
+                    break;
+                }
+
+                if value.is_none() || expiration.map_or(false, |expiration| expiration <= now) {
+                    expired.push(name);
+                    continue;
+                }
+
+                if keys.len() == options.limit {
+                    result = Some(keys.last().map(|key: &KvKey| key.name.clone()));
+                    break;
+                }
+
+                keys.push(KvKey {
+                    name: name.clone(),
+                    metadata,
+                    expiration,
+                });
+            }
+
+            if !expired.is_empty() {
+                redis::cmd("ZREM")
+                    .arg(&index_key)
+                    .arg(&expired)
+                    .query_async(&mut connection)
+                    .await?;
+            }
+
+            if result.is_none() && names.len() < batch_size {
+                result = Some(None);
+            }
+
+            match result {
+                Some(cursor) => return Ok(ListResult { keys, cursor }),
+                None => min = last,
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "redis")]
+pub use crate::redis::RedisQueueStore;
+pub use memory::MemoryQueueStore;
+
+pub const MAX_MESSAGE_SIZE: usize = 128 * 1024; // 128KB
+pub const MAX_BATCH_SIZE: usize = 100;
+// A message failing on its last attempt is moved to the dead-letter queue
+pub const MAX_ATTEMPTS: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueMessage {
+    pub id: String,
+    // Serialized as JSON by the JS runtime
+    pub body: String,
+    // When the message was first sent, in milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    // Starts at 1, and is incremented each time the message is retried
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueOutcome {
+    Ack,
+    Retry,
+}
+
+// How the messages of a batch were settled once consumed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SettleResult {
+    pub acked: usize,
+    pub retried: usize,
+    pub dead_lettered: usize,
+}
+
+// A storage backend shared by all the queues. Backends don't need to
+// validate messages, which is done by `Queue` beforehand
+#[async_trait]
+pub trait QueueStore: Send + Sync {
+    // Add a message at the end of the queue
+    async fn send(&self, queue: &str, body: &str, timestamp: u64, attempts: u32) -> Result<()>;
+    // Reserve up to `limit` messages, until they are acknowledged
+    async fn receive(&self, queue: &str, limit: usize) -> Result<Vec<QueueMessage>>;
+    async fn ack(&self, queue: &str, ids: &[String]) -> Result<()>;
+    // Acknowledge a message and move it to the dead-letter queue
+    async fn dead_letter(&self, queue: &str, message: &QueueMessage) -> Result<()>;
+    // The queues that received at least one message
+    async fn queues(&self) -> Result<Vec<String>>;
+}
+
+// The only way isolates and consumers can access a store. Each Function
+// gets its own queue, consumed by its production deployment
+#[derive(Clone)]
+pub struct Queue {
+    store: Arc<dyn QueueStore>,
+    name: String,
+}
+
+impl Queue {
+    pub fn new(store: Arc<dyn QueueStore>, name: String) -> Result<Self> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+        {
+            return Err(anyhow!("Invalid queue name {}", name));
+        }
+
+        Ok(Self { store, name })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn send(&self, body: String) -> Result<()> {
+        if body.len() > MAX_MESSAGE_SIZE {
+            return Err(anyhow!(
+                "Queue message exceeds the maximum size of {} bytes",
+                MAX_MESSAGE_SIZE
+            ));
+        }
+
+        self.store.send(&self.name, &body, now(), 1).await
+    }
+
+    pub async fn receive(&self, limit: usize) -> Result<Vec<QueueMessage>> {
+        if limit == 0 || limit > MAX_BATCH_SIZE {
+            return Err(anyhow!(
+                "Queue batch size must be between 1 and {}",
+                MAX_BATCH_SIZE
+            ));
+        }
+
+        self.store.receive(&self.name, limit).await
+    }
+
+    // Messages without an explicit outcome are acknowledged when the
+    // batch was consumed successfully, and retried otherwise. Retried
+    // messages are sent again at the end of the queue, until they
+    // reach their last attempt and go to the dead-letter queue
+    pub async fn settle(
+        &self,
+        messages: Vec<QueueMessage>,
+        outcomes: &HashMap<String, QueueOutcome>,
+        success: bool,
+    ) -> Result<SettleResult> {
+        let mut result = SettleResult::default();
+        let mut ids = Vec::new();
+
+        for message in messages {
+            let outcome = outcomes.get(&message.id).copied().unwrap_or(match success {
+                true => QueueOutcome::Ack,
+                false => QueueOutcome::Retry,
+            });
+
+            match outcome {
+                QueueOutcome::Ack => {
+                    result.acked += 1;
+                }
+                QueueOutcome::Retry if message.attempts >= MAX_ATTEMPTS => {
+                    self.store.dead_letter(&self.name, &message).await?;
+                    result.dead_lettered += 1;
+                    continue;
+                }
+                QueueOutcome::Retry => {
+                    self.store
+                        .send(
+                            &self.name,
+                            &message.body,
+                            message.timestamp,
+                            message.attempts + 1,
+                        )
+                        .await?;
+                    result.retried += 1;
+                }
+            }
+
+            ids.push(message.id);
+        }
+
+        if !ids.is_empty() {
+            self.store.ack(&self.name, &ids).await?;
+        }
+
+        Ok(result)
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue() -> (Arc<MemoryQueueStore>, Queue) {
+        let store = Arc::new(MemoryQueueStore::default());
+        let queue = Queue::new(Arc::clone(&store) as Arc<dyn QueueStore>, "function".into());
+
+        (store, queue.unwrap())
+    }
+
+    #[test]
+    fn invalid_queue_name() {
+        let store = Arc::new(MemoryQueueStore::default());
+
+        assert!(Queue::new(store.clone(), "".into()).is_err());
+        assert!(Queue::new(store, "a:b".into()).is_err());
+    }
+
+    #[tokio::test]
+    async fn send_receive() {
+        let (_, queue) = queue();
+
+        queue.send("1".into()).await.unwrap();
+        queue.send("2".into()).await.unwrap();
+        queue.send("3".into()).await.unwrap();
+
+        let messages = queue.receive(2).await.unwrap();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.body.as_str())
+                .collect::<Vec<_>>(),
+            ["1", "2"]
+        );
+        assert!(messages.iter().all(|message| message.attempts == 1));
+
+        // Reserved messages aren't received again
+        assert_eq!(queue.receive(10).await.unwrap().len(), 1);
+        assert!(queue.receive(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn message_too_large() {
+        let (_, queue) = queue();
+
+        assert!(queue.send("a".repeat(MAX_MESSAGE_SIZE + 1)).await.is_err());
+        assert!(queue.receive(MAX_BATCH_SIZE + 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn queues_are_isolated() {
+        let store: Arc<dyn QueueStore> = Arc::new(MemoryQueueStore::default());
+        let first = Queue::new(Arc::clone(&store), "first".into()).unwrap();
+        let second = Queue::new(Arc::clone(&store), "second".into()).unwrap();
+
+        first.send("message".into()).await.unwrap();
+
+        assert!(second.receive(10).await.unwrap().is_empty());
+        assert_eq!(store.queues().await.unwrap(), ["first"]);
+    }
+
+    #[tokio::test]
+    async fn settle_outcomes() {
+        let (_, queue) = queue();
+
+        for body in ["1", "2", "3"] {
+            queue.send(body.into()).await.unwrap();
+        }
+
+        let messages = queue.receive(10).await.unwrap();
+        let outcomes = HashMap::from([
+            (messages[0].id.clone(), QueueOutcome::Retry),
+            (messages[1].id.clone(), QueueOutcome::Ack),
+        ]);
+
+        assert_eq!(
+            queue.settle(messages, &outcomes, true).await.unwrap(),
+            SettleResult {
+                acked: 2,
+                retried: 1,
+                dead_lettered: 0,
+            }
+        );
+
+        let messages = queue.receive(10).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].body, "1");
+        assert_eq!(messages[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn settle_failed_batch() {
+        let (store, queue) = queue();
+
+        queue.send("message".into()).await.unwrap();
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let messages = queue.receive(10).await.unwrap();
+            assert_eq!(messages[0].attempts, attempt);
+
+            queue
+                .settle(messages, &HashMap::new(), false)
+                .await
+                .unwrap();
+        }
+
+        assert!(queue.receive(10).await.unwrap().is_empty());
+
+        let dead_letters = store.dead_letters("function");
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].body, "message");
+        assert_eq!(dead_letters[0].attempts, MAX_ATTEMPTS);
+    }
+}
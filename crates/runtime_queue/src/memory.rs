@@ -0,0 +1,101 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{QueueMessage, QueueStore};
+
+#[derive(Default)]
+struct MemoryQueue {
+    messages: VecDeque<QueueMessage>,
+    // Received but not acknowledged yet
+    reserved: HashMap<String, QueueMessage>,
+    dead_letters: Vec<QueueMessage>,
+}
+
+// Keeps everything in memory, used for tests and `lagon dev`. Reserved
+// messages are never delivered again, since consumers run in-process
+#[derive(Default)]
+pub struct MemoryQueueStore {
+    queues: Mutex<BTreeMap<String, MemoryQueue>>,
+    messages_count: AtomicU64,
+}
+
+impl MemoryQueueStore {
+    pub fn dead_letters(&self, queue: &str) -> Vec<QueueMessage> {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(queue)
+            .map_or_else(Vec::new, |queue| queue.dead_letters.clone())
+    }
+}
+
+#[async_trait]
+impl QueueStore for MemoryQueueStore {
+    async fn send(&self, queue: &str, body: &str, timestamp: u64, attempts: u32) -> Result<()> {
+        let id = (self.messages_count.fetch_add(1, Ordering::Relaxed) + 1).to_string();
+
+        self.queues
+            .lock()
+            .unwrap()
+            .entry(queue.to_string())
+            .or_default()
+            .messages
+            .push_back(QueueMessage {
+                id,
+                body: body.to_string(),
+                timestamp,
+                attempts,
+            });
+
+        Ok(())
+    }
+
+    async fn receive(&self, queue: &str, limit: usize) -> Result<Vec<QueueMessage>> {
+        let mut queues = self.queues.lock().unwrap();
+
+        let queue = match queues.get_mut(queue) {
+            Some(queue) => queue,
+            None => return Ok(Vec::new()),
+        };
+
+        let count = limit.min(queue.messages.len());
+        let messages = queue.messages.drain(..count).collect::<Vec<_>>();
+
+        for message in &messages {
+            queue.reserved.insert(message.id.clone(), message.clone());
+        }
+
+        Ok(messages)
+    }
+
+    async fn ack(&self, queue: &str, ids: &[String]) -> Result<()> {
+        if let Some(queue) = self.queues.lock().unwrap().get_mut(queue) {
+            for id in ids {
+                queue.reserved.remove(id);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn dead_letter(&self, queue: &str, message: &QueueMessage) -> Result<()> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(queue.to_string()).or_default();
+
+        queue.reserved.remove(&message.id);
+        queue.dead_letters.push(message.clone());
+
+        Ok(())
+    }
+
+    async fn queues(&self) -> Result<Vec<String>> {
+        Ok(self.queues.lock().unwrap().keys().cloned().collect())
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use redis::{
+    aio::ConnectionManager,
+    streams::{StreamClaimReply, StreamId, StreamPendingCountReply, StreamReadReply},
+    Client,
+};
+use std::{collections::HashSet, sync::Mutex, time::Duration};
+
+use crate::{QueueMessage, QueueStore};
+
+const QUEUES_KEY: &str = "lagon:queues";
+const GROUP: &str = "lagon";
+// Received messages not acknowledged after this delay are claimed
+// again, e.g when the node consuming them crashed in the meantime
+const VISIBILITY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// Only the most recent dead-lettered messages are kept
+const MAX_DEAD_LETTERS: usize = 10_000;
+
+// Each queue is a stream read through a consumer group, so messages are
+// delivered to a single node and stay pending until acknowledged. Acknowledged
+// messages are deleted from the stream, and retries are added at its end
+pub struct RedisQueueStore {
+    connection: ConnectionManager,
+    // Identifies this node in the consumer groups
+    consumer: String,
+    // The queues we already created the consumer group of
+    groups: Mutex<HashSet<String>>,
+}
+
+impl RedisQueueStore {
+    pub async fn new(url: String, consumer: String) -> Result<Self> {
+        let client = Client::open(url)?;
+        let connection = ConnectionManager::new(client).await?;
+
+        Ok(Self {
+            connection,
+            consumer,
+            groups: Mutex::new(HashSet::new()),
+        })
+    }
+
+    async fn create_group(&self, queue: &str) -> Result<()> {
+        if self.groups.lock().unwrap().contains(queue) {
+            return Ok(());
+        }
+
+        let mut connection = self.connection.clone();
+
+        let result: redis::RedisResult<()> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(messages_key(queue))
+            .arg(GROUP)
+            .arg(0)
+            .arg("MKSTREAM")
+            .query_async(&mut connection)
+            .await;
+
+        // The group already exists when created by another node
+        if let Err(error) = result {
+            if error.code() != Some("BUSYGROUP") {
+                return Err(error.into());
+            }
+        }
+
+        self.groups.lock().unwrap().insert(queue.to_string());
+
+        Ok(())
+    }
+}
+
+fn messages_key(queue: &str) -> String {
+    format!("lagon:queue:{queue}:messages")
+}
+
+fn dead_letters_key(queue: &str) -> String {
+    format!("lagon:queue:{queue}:dead")
+}
+
+fn to_message(stream_id: StreamId) -> Option<QueueMessage> {
+    Some(QueueMessage {
+        body: stream_id.get("b")?,
+        timestamp: stream_id.get("t")?,
+        attempts: stream_id.get("a")?,
+        id: stream_id.id,
+    })
+}
+
+#[async_trait]
+impl QueueStore for RedisQueueStore {
+    async fn send(&self, queue: &str, body: &str, timestamp: u64, attempts: u32) -> Result<()> {
+        let mut connection = self.connection.clone();
+
+        redis::pipe()
+            .sadd(QUEUES_KEY, queue)
+            .ignore()
+            .cmd("XADD")
+            .arg(messages_key(queue))
+            .arg("*")
+            .arg("b")
+            .arg(body)
+            .arg("t")
+            .arg(timestamp)
+            .arg("a")
+            .arg(attempts)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn receive(&self, queue: &str, limit: usize) -> Result<Vec<QueueMessage>> {
+        self.create_group(queue).await?;
+
+        let mut connection = self.connection.clone();
+        let key = messages_key(queue);
+        let visibility_timeout = VISIBILITY_TIMEOUT.as_millis() as usize;
+
+        // Claim the messages pending for too long before reading new ones
+        let pending: StreamPendingCountReply = redis::cmd("XPENDING")
+            .arg(&key)
+            .arg(GROUP)
+            .arg("-")
+            .arg("+")
+            .arg(limit)
+            .query_async(&mut connection)
+            .await?;
+
+        let expired = pending
+            .ids
+            .into_iter()
+            .filter(|pending| pending.last_delivered_ms >= visibility_timeout)
+            .map(|pending| pending.id)
+            .collect::<Vec<_>>();
+
+        let mut messages = Vec::new();
+
+        if !expired.is_empty() {
+            let claimed: StreamClaimReply = redis::cmd("XCLAIM")
+                .arg(&key)
+                .arg(GROUP)
+                .arg(&self.consumer)
+                .arg(visibility_timeout)
+                .arg(&expired)
+                .query_async(&mut connection)
+                .await?;
+
+            messages.extend(claimed.ids.into_iter().filter_map(to_message));
+        }
+
+        if messages.len() < limit {
+            let reply: Option<StreamReadReply> = redis::cmd("XREADGROUP")
+                .arg("GROUP")
+                .arg(GROUP)
+                .arg(&self.consumer)
+                .arg("COUNT")
+                .arg(limit - messages.len())
+                .arg("STREAMS")
+                .arg(&key)
+                .arg(">")
+                .query_async(&mut connection)
+                .await?;
+
+            if let Some(reply) = reply {
+                messages.extend(
+                    reply
+                        .keys
+                        .into_iter()
+                        .flat_map(|stream| stream.ids)
+                        .filter_map(to_message),
+                );
+            }
+        }
+
+        Ok(messages)
+    }
+
+    async fn ack(&self, queue: &str, ids: &[String]) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let key = messages_key(queue);
+
+        redis::pipe()
+            .atomic()
+            .cmd("XACK")
+            .arg(&key)
+            .arg(GROUP)
+            .arg(ids)
+            .ignore()
+            .cmd("XDEL")
+            .arg(&key)
+            .arg(ids)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn dead_letter(&self, queue: &str, message: &QueueMessage) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let key = messages_key(queue);
+
+        redis::pipe()
+            .atomic()
+            .cmd("XADD")
+            .arg(dead_letters_key(queue))
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(MAX_DEAD_LETTERS)
+            .arg("*")
+            .arg("b")
+            .arg(&message.body)
+            .arg("t")
+            .arg(message.timestamp)
+            .arg("a")
+            .arg(message.attempts)
+            .ignore()
+            .cmd("XACK")
+            .arg(&key)
+            .arg(GROUP)
+            .arg(&message.id)
+            .ignore()
+            .cmd("XDEL")
+            .arg(&key)
+            .arg(&message.id)
+            .ignore()
+            .query_async::<_, ()>(&mut connection)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn queues(&self) -> Result<Vec<String>> {
+        let mut connection = self.connection.clone();
+
+        let queues = redis::cmd("SMEMBERS")
+            .arg(QUEUES_KEY)
+            .query_async(&mut connection)
+            .await?;
+
+        Ok(queues)
+    }
+}
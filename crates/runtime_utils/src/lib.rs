@@ -1,15 +1,18 @@
 use anyhow::{anyhow, Result};
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     env,
     fs::{self, File},
     io::Write,
     path::Path,
+    str::FromStr,
 };
 
 pub mod assets;
+pub mod request;
 pub mod response;
+pub mod websocket;
 
 #[cfg(not(feature = "test"))]
 pub const DEPLOYMENTS_DIR: &str = "deployments";
@@ -17,6 +20,44 @@ pub const DEPLOYMENTS_DIR: &str = "deployments";
 #[cfg(feature = "test")]
 pub const DEPLOYMENTS_DIR: &str = "deployments_test";
 
+pub const DEFAULT_CRON_NAME: &str = "default";
+
+// Bindings added to `env` by the runtime, which service bindings can't use
+pub const RESERVED_BINDINGS: [&str; 3] = ["KV", "QUEUE", "DB"];
+
+// What to do when a cron execution is triggered while the previous one is still running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CronOverlap {
+    #[default]
+    Skip,
+    Queue,
+    Allow,
+}
+
+impl FromStr for CronOverlap {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "skip" => Ok(Self::Skip),
+            "queue" => Ok(Self::Queue),
+            "allow" => Ok(Self::Allow),
+            _ => Err(anyhow!("Invalid cron overlap policy: {}", value)),
+        }
+    }
+}
+
+// Same limit as the dashboard, also enforced when loading deployments
+pub const CRON_MAX_RETRIES: u32 = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CronOptions {
+    pub max_retries: u32,
+    pub overlap: CronOverlap,
+    // IANA timezone name, UTC when not set
+    pub timezone: Option<String>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Deployment {
     pub id: String,
@@ -29,7 +70,12 @@ pub struct Deployment {
     pub tick_timeout: usize,  // in ms (MilliSeconds)
     pub total_timeout: usize, // in ms (MilliSeconds)
     pub is_production: bool,
-    pub cron: Option<String>,
+    // Schedule name to its cron expression. The Function's
+    // main expression is named `DEFAULT_CRON_NAME`
+    pub crons: BTreeMap<String, String>,
+    pub cron_options: CronOptions,
+    // Binding name to the name of the Function it calls
+    pub service_bindings: HashMap<String, String>,
 }
 
 impl Deployment {
@@ -56,8 +102,26 @@ impl Deployment {
         domains
     }
 
+    pub fn check_service_bindings(&self) -> Result<()> {
+        match self
+            .service_bindings
+            .keys()
+            .find(|name| RESERVED_BINDINGS.contains(&name.as_str()))
+        {
+            Some(name) => Err(anyhow!(
+                "Service binding {} conflicts with a reserved binding",
+                name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_cron(&self) -> bool {
+        !self.crons.is_empty()
+    }
+
     pub fn should_run_cron(&self) -> bool {
-        self.is_production && self.cron.is_some()
+        self.is_production && self.is_cron()
     }
 
     pub fn get_code(&self) -> Result<String> {
@@ -69,6 +133,24 @@ impl Deployment {
         Ok(code)
     }
 
+    // Source maps are optional, so we don't fail if there's none
+    pub fn get_source_map(&self) -> Option<String> {
+        let path = Path::new(env::current_dir().ok()?.as_path())
+            .join(DEPLOYMENTS_DIR)
+            .join(self.id.clone() + ".js.map");
+
+        fs::read_to_string(path).ok()
+    }
+
+    // The V8 code cache created by the first isolate of this deployment
+    pub fn get_code_cache(&self) -> Option<Vec<u8>> {
+        let path = Path::new(env::current_dir().ok()?.as_path())
+            .join(DEPLOYMENTS_DIR)
+            .join(self.id.clone() + ".js.cache");
+
+        fs::read(path).ok()
+    }
+
     pub fn has_code(&self) -> bool {
         let path = Path::new(DEPLOYMENTS_DIR).join(self.id.clone() + ".js");
 
@@ -83,6 +165,30 @@ impl Deployment {
         Ok(())
     }
 
+    pub fn write_source_map(&self, source_map: &[u8]) -> Result<()> {
+        let mut file = File::create(Path::new(DEPLOYMENTS_DIR).join(self.id.clone() + ".js.map"))?;
+
+        file.write_all(source_map)?;
+
+        Ok(())
+    }
+
+    pub fn write_code_cache(&self, code_cache: &[u8]) -> Result<()> {
+        let path = Path::new(DEPLOYMENTS_DIR).join(self.id.clone() + ".js.cache");
+        // Many isolates can write the code cache at the same time, so
+        // we write to a temporary file first to never read a partial cache
+        let tmp_path = Path::new(DEPLOYMENTS_DIR).join(format!(
+            "{}.js.cache.{}",
+            self.id,
+            uuid::Uuid::new_v4()
+        ));
+
+        fs::write(&tmp_path, code_cache)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
     pub fn write_asset(&self, asset: &str, content: &[u8]) -> Result<()> {
         let asset = asset.replace("public/", "");
         let asset = asset.as_str();
@@ -121,7 +227,9 @@ mod tests {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: false,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         };
 
         assert_eq!(deployment.get_domains(), vec!["123.lagon.test".to_owned()]);
@@ -142,12 +250,44 @@ mod tests {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: false,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         };
 
         assert_eq!(deployment.get_domains(), vec!["123.lagon.test".to_owned(),]);
     }
 
+    #[test]
+    fn deployment_reserved_service_bindings() {
+        let mut deployment = Deployment {
+            id: "123".into(),
+            function_id: "456".into(),
+            function_name: "hello".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::from([("AUTH".to_owned(), "auth".to_owned())]),
+        };
+
+        assert!(deployment.check_service_bindings().is_ok());
+
+        for name in RESERVED_BINDINGS {
+            deployment.service_bindings = HashMap::from([(name.to_owned(), "auth".to_owned())]);
+
+            assert_eq!(
+                deployment.check_service_bindings().unwrap_err().to_string(),
+                format!("Service binding {name} conflicts with a reserved binding")
+            );
+        }
+    }
+
     #[test]
     fn deployment_domains_production() {
         env::set_var("LAGON_ROOT_DOMAIN", "lagon.test");
@@ -163,7 +303,9 @@ mod tests {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         };
 
         assert_eq!(
@@ -175,4 +317,12 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn cron_overlap_from_str() {
+        assert_eq!("skip".parse::<CronOverlap>().unwrap(), CronOverlap::Skip);
+        assert_eq!("queue".parse::<CronOverlap>().unwrap(), CronOverlap::Queue);
+        assert_eq!("allow".parse::<CronOverlap>().unwrap(), CronOverlap::Allow);
+        assert!("never".parse::<CronOverlap>().is_err());
+    }
 }
@@ -0,0 +1,297 @@
+use anyhow::{anyhow, Result};
+use futures::{ready, stream, StreamExt};
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::CONTENT_LENGTH,
+    http::request::Parts,
+    Body,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::Poll,
+};
+
+// 10 MB (MegaBytes)
+pub const DEFAULT_MAX_REQUEST_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+// Check the Content-Length header (when present) to reject
+// requests that are too large before reading their body
+pub fn is_body_too_large(parts: &Parts, max_size: usize) -> bool {
+    parts
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse::<usize>().ok())
+        .map_or(false, |content_length| content_length > max_size)
+}
+
+// Whether a body wrapped by `limit_body` was larger than `max_size`, which
+// is only known once it has been read for chunked requests. Reading it then
+// fails in the isolate, but the client should get a 413 response
+pub fn is_body_limit_reached(bytes_read: &AtomicUsize, max_size: usize) -> bool {
+    bytes_read.load(Ordering::Relaxed) > max_size
+}
+
+// Wrap the body so it can be streamed into an isolate, while counting
+// the number of bytes read. The stream returns an error as soon as more
+// than `max_size` bytes have been read, since chunked requests don't
+// have a Content-Length header we could check beforehand.
+pub fn limit_body(body: Body, max_size: usize, bytes_read: Arc<AtomicUsize>) -> Body {
+    // Keep empty bodies as-is so isolates can know
+    // that the request doesn't have a body
+    if body.is_end_stream() {
+        return body;
+    }
+
+    Body::wrap_stream(body.map(move |chunk| -> Result<Bytes> {
+        let chunk = chunk?;
+        let total = bytes_read.fetch_add(chunk.len(), Ordering::Relaxed) + chunk.len();
+
+        if total > max_size {
+            return Err(anyhow!("Request body is larger than {} bytes", max_size));
+        }
+
+        Ok(chunk)
+    }))
+}
+
+// How much of a request body is kept once the isolate started reading it, to
+// send it again to another isolate. Larger bodies can't be replayed
+pub const MAX_REPLAY_BODY_SIZE: usize = 64 * 1024; // 64KB
+
+struct ReplayState {
+    // The body limited by `limit_body`, None when reading it failed
+    body: Option<Body>,
+    // The chunks already read by the isolate, None once
+    // they are larger than `MAX_REPLAY_BODY_SIZE`
+    chunks: Option<Vec<Bytes>>,
+    size: usize,
+}
+
+// Allows sending a request body again to another isolate
+pub struct BodyReplay(Option<Arc<Mutex<ReplayState>>>);
+
+impl BodyReplay {
+    // Returns the original body if the isolate didn't read it, or the chunks it
+    // read followed by the rest of the body. Returns None when the body can't be
+    // sent again, e.g because the chunks already read were too large to be kept
+    // or because reading the body failed
+    pub fn body(self) -> Option<Body> {
+        let state = match self.0 {
+            Some(state) => state,
+            None => return Some(Body::empty()),
+        };
+
+        let mut state = state.lock().unwrap();
+        let chunks = state.chunks.take()?;
+        let body = state.body.take()?;
+
+        if chunks.is_empty() {
+            return Some(body);
+        }
+
+        Some(Body::wrap_stream(
+            stream::iter(chunks.into_iter().map(Ok)).chain(body),
+        ))
+    }
+}
+
+// Same as `limit_body`, but the body can be sent again to another isolate. The
+// body is only read when the isolate reads it, and we keep a copy of what has
+// been read as long as it's smaller than `MAX_REPLAY_BODY_SIZE`
+pub fn replayable_body(
+    body: Body,
+    max_size: usize,
+    bytes_read: Arc<AtomicUsize>,
+) -> (Body, BodyReplay) {
+    if body.is_end_stream() {
+        return (body, BodyReplay(None));
+    }
+
+    let state = Arc::new(Mutex::new(ReplayState {
+        body: Some(limit_body(body, max_size, bytes_read)),
+        chunks: Some(Vec::new()),
+        size: 0,
+    }));
+    let stream_state = Arc::clone(&state);
+
+    let stream = stream::poll_fn(move |cx| {
+        let mut state = stream_state.lock().unwrap();
+
+        // The body has been taken to be sent to another isolate
+        let body = match state.body.as_mut() {
+            Some(body) => body,
+            None => return Poll::Ready(None),
+        };
+
+        let result = ready!(body.poll_next_unpin(cx));
+
+        match &result {
+            Some(Ok(chunk)) => {
+                state.size += chunk.len();
+
+                if state.size > MAX_REPLAY_BODY_SIZE {
+                    state.chunks = None;
+                } else if let Some(chunks) = state.chunks.as_mut() {
+                    chunks.push(chunk.clone());
+                }
+            }
+            Some(Err(_)) => {
+                state.body = None;
+                state.chunks = None;
+            }
+            None => {}
+        }
+
+        Poll::Ready(result)
+    });
+
+    (Body::wrap_stream(stream), BodyReplay(Some(state)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{body::to_bytes, Request};
+
+    #[test]
+    fn body_too_large_content_length() {
+        let (parts, _) = Request::builder()
+            .header(CONTENT_LENGTH, "11")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        assert!(is_body_too_large(&parts, 10));
+        assert!(!is_body_too_large(&parts, 11));
+    }
+
+    #[test]
+    fn body_too_large_without_content_length() {
+        let (parts, _) = Request::builder().body(()).unwrap().into_parts();
+
+        assert!(!is_body_too_large(&parts, 0));
+    }
+
+    #[tokio::test]
+    async fn limit_body_under_limit() {
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let body = limit_body(Body::from("Hello world"), 11, Arc::clone(&bytes_read));
+
+        assert_eq!(to_bytes(body).await.unwrap(), Bytes::from("Hello world"));
+        assert_eq!(bytes_read.load(Ordering::Relaxed), 11);
+    }
+
+    #[tokio::test]
+    async fn limit_body_over_limit() {
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let body = limit_body(Body::from("Hello world"), 5, Arc::clone(&bytes_read));
+
+        assert!(!is_body_limit_reached(&bytes_read, 5));
+        assert!(to_bytes(body).await.is_err());
+        assert!(is_body_limit_reached(&bytes_read, 5));
+    }
+
+    #[test]
+    fn limit_body_empty() {
+        let body = limit_body(Body::empty(), 5, Arc::new(AtomicUsize::new(0)));
+
+        assert!(body.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn replayable_body_copy() {
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let (body, replay) =
+            replayable_body(Body::from("Hello world"), 11, Arc::clone(&bytes_read));
+
+        assert_eq!(to_bytes(body).await.unwrap(), Bytes::from("Hello world"));
+        assert_eq!(
+            to_bytes(replay.body().unwrap()).await.unwrap(),
+            Bytes::from("Hello world")
+        );
+        assert_eq!(bytes_read.load(Ordering::Relaxed), 11);
+    }
+
+    #[tokio::test]
+    async fn replayable_body_not_read() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let chunks = vec![Ok::<_, std::io::Error>("Hello "), Ok("world")];
+        let stream = stream::iter(chunks).inspect({
+            let polls = Arc::clone(&polls);
+            move |_| {
+                polls.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let (body, replay) =
+            replayable_body(Body::wrap_stream(stream), 11, Arc::clone(&bytes_read));
+
+        drop(body);
+        tokio::task::yield_now().await;
+
+        // The body isn't read when the isolate doesn't read it
+        assert_eq!(polls.load(Ordering::Relaxed), 0);
+        assert_eq!(bytes_read.load(Ordering::Relaxed), 0);
+
+        assert_eq!(
+            to_bytes(replay.body().unwrap()).await.unwrap(),
+            Bytes::from("Hello world")
+        );
+        assert_eq!(polls.load(Ordering::Relaxed), 2);
+        assert_eq!(bytes_read.load(Ordering::Relaxed), 11);
+    }
+
+    #[tokio::test]
+    async fn replayable_body_partially_read() {
+        let chunks = vec![Ok::<_, std::io::Error>("Hello "), Ok("world")];
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let (mut body, replay) = replayable_body(
+            Body::wrap_stream(stream::iter(chunks)),
+            11,
+            Arc::clone(&bytes_read),
+        );
+
+        assert_eq!(body.next().await.unwrap().unwrap(), Bytes::from("Hello "));
+        drop(body);
+
+        assert_eq!(
+            to_bytes(replay.body().unwrap()).await.unwrap(),
+            Bytes::from("Hello world")
+        );
+        assert_eq!(bytes_read.load(Ordering::Relaxed), 11);
+    }
+
+    #[tokio::test]
+    async fn replayable_body_too_large_to_replay() {
+        let size = MAX_REPLAY_BODY_SIZE + 1;
+        let (body, replay) = replayable_body(
+            Body::from(vec![0; size]),
+            size,
+            Arc::new(AtomicUsize::new(0)),
+        );
+
+        assert_eq!(to_bytes(body).await.unwrap().len(), size);
+        assert!(replay.body().is_none());
+    }
+
+    #[tokio::test]
+    async fn replayable_body_over_limit() {
+        let (body, replay) =
+            replayable_body(Body::from("Hello world"), 5, Arc::new(AtomicUsize::new(0)));
+
+        assert!(to_bytes(body).await.is_err());
+        assert!(replay.body().is_none());
+    }
+
+    #[tokio::test]
+    async fn replayable_body_empty() {
+        let (body, replay) = replayable_body(Body::empty(), 5, Arc::new(AtomicUsize::new(0)));
+
+        assert!(body.is_end_stream());
+        assert!(replay.body().unwrap().is_end_stream());
+    }
+}
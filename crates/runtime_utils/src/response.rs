@@ -3,6 +3,7 @@ use anyhow::Result;
 use flume::Receiver;
 use hyper::{
     body::{Bytes, HttpBody},
+    header::RETRY_AFTER,
     http::response::Builder,
     Body, Response,
 };
@@ -11,7 +12,9 @@ use std::{future::Future, sync::Arc};
 
 pub const PAGE_404: &str = include_str!("../public/404.html");
 pub const PAGE_403: &str = include_str!("../public/403.html");
+pub const PAGE_413: &str = include_str!("../public/413.html");
 pub const PAGE_502: &str = include_str!("../public/502.html");
+pub const PAGE_503: &str = include_str!("../public/503.html");
 pub const PAGE_500: &str = include_str!("../public/500.html");
 pub const FAVICON_URL: &str = "/favicon.ico";
 
@@ -51,6 +54,20 @@ where
 {
     let result = rx.recv_async().await?;
 
+    handle_run_result(result, rx, deployment, on_event).await
+}
+
+// Same as `handle_response`, but with the first result already received
+// from `rx`, allowing the caller to inspect it first (e.g to retry)
+pub async fn handle_run_result<F>(
+    result: RunResult,
+    rx: Receiver<RunResult>,
+    deployment: Arc<Deployment>,
+    on_event: impl FnOnce(ResponseEvent) -> F + Send + Sync + 'static,
+) -> Result<Response<Body>>
+where
+    F: Future<Output = Result<()>> + Send,
+{
     match result {
         RunResult::Stream(stream_result) => {
             let (stream_tx, stream_rx) = flume::unbounded::<Result<Bytes, std::io::Error>>();
@@ -134,7 +151,21 @@ where
 
             Ok(Response::builder().status(502).body(PAGE_502.into())?)
         }
-        RunResult::Error(_) => {
+        // The request was interrupted by another request and couldn't be sent
+        // again to another isolate, so the client can safely retry it
+        RunResult::Retry => {
+            let event = ResponseEvent::Error(result);
+            on_event(event).await?;
+
+            Ok(Response::builder()
+                .status(503)
+                .header(RETRY_AFTER, "1")
+                .body(PAGE_503.into())?)
+        }
+        // WebSockets need the request's connection, so they are
+        // handled by the caller with `websocket::handle_websocket`.
+        // Scheduled events are never the result of an HTTP request
+        RunResult::Error(_) | RunResult::WebSocket(..) | RunResult::Scheduled(_) => {
             let event = ResponseEvent::Error(result);
             on_event(event).await?;
 
@@ -357,4 +388,31 @@ mod tests {
 
         handle.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn retry() {
+        let (tx, rx) = flume::unbounded::<RunResult>();
+
+        let handle = tokio::spawn(async move {
+            let deployment = Arc::new(Deployment::default());
+            let mut response = handle_response(rx, deployment, |event| async move {
+                assert!(matches!(event, ResponseEvent::Error(RunResult::Retry)));
+
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(response.status(), 503);
+            assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "1");
+            assert_eq!(
+                to_bytes(response.body_mut()).await.unwrap(),
+                Bytes::from(PAGE_503)
+            );
+        });
+
+        tx.send_async(RunResult::Retry).await.unwrap();
+
+        handle.await.unwrap();
+    }
 }
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use hyper::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE},
+    http::response::Builder,
+    upgrade::{OnUpgrade, Upgraded},
+    Body, Request, Response, StatusCode,
+};
+use lagon_runtime_http::{WebSocketChannel, WebSocketMessage};
+use std::time::Duration;
+use tokio::time::{sleep, sleep_until, Instant};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::derive_accept_key,
+        protocol::{CloseFrame, Role},
+        Message,
+    },
+    WebSocketStream,
+};
+
+// https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1
+const CLOSE_NO_STATUS: u16 = 1005;
+const CLOSE_ABNORMAL: u16 = 1006;
+const CLOSE_GOING_AWAY: u16 = 1001;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INTERNAL_ERROR: u16 = 1011;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketOptions {
+    // How long a socket can stay open
+    pub max_duration: Duration,
+    // How long a socket can stay open without any message in either direction
+    pub idle_timeout: Duration,
+}
+
+impl Default for WebSocketOptions {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_secs(60 * 60),
+            idle_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl WebSocketOptions {
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+}
+
+// The pending upgrade of a request's connection. It has to be taken
+// from the request before the request is sent to the isolate
+pub struct WebSocketUpgrade {
+    key: String,
+    on_upgrade: OnUpgrade,
+}
+
+impl WebSocketUpgrade {
+    pub fn from_request(request: &mut Request<Body>) -> Option<Self> {
+        let is_websocket = request
+            .headers()
+            .get(UPGRADE)
+            .and_then(|upgrade| upgrade.to_str().ok())
+            .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+
+        if !is_websocket {
+            return None;
+        }
+
+        let key = request
+            .headers()
+            .get(SEC_WEBSOCKET_KEY)?
+            .to_str()
+            .ok()?
+            .to_string();
+
+        Some(Self {
+            key,
+            on_upgrade: hyper::upgrade::on(request),
+        })
+    }
+}
+
+// Complete the handshake of a WebSocket accepted by a Function, and forward the
+// messages between the client and the isolate until either side closes it.
+// `on_message` sends the client's messages to the isolate, and is dropped
+// when the socket is closed
+pub fn handle_websocket<F>(
+    upgrade: Option<WebSocketUpgrade>,
+    response_builder: Builder,
+    channel: WebSocketChannel,
+    options: WebSocketOptions,
+    on_message: F,
+) -> Result<Response<Body>>
+where
+    F: Fn(WebSocketMessage) + Send + 'static,
+{
+    let upgrade = match upgrade {
+        Some(upgrade) => upgrade,
+        None => {
+            on_message(WebSocketMessage::Close(
+                CLOSE_PROTOCOL_ERROR,
+                String::from("Expected a WebSocket upgrade request"),
+            ));
+
+            return Ok(Response::builder()
+                .status(StatusCode::UPGRADE_REQUIRED)
+                .body(Body::from("Expected a WebSocket upgrade request"))?);
+        }
+    };
+
+    let response = response_builder
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(
+            SEC_WEBSOCKET_ACCEPT,
+            derive_accept_key(upgrade.key.as_bytes()),
+        )
+        .body(Body::empty())?;
+
+    tokio::spawn(async move {
+        match upgrade.on_upgrade.await {
+            Ok(upgraded) => {
+                let stream = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+
+                run_websocket(stream, channel, options, on_message).await;
+            }
+            Err(_) => on_message(WebSocketMessage::Close(CLOSE_ABNORMAL, String::new())),
+        }
+    });
+
+    Ok(response)
+}
+
+async fn close(stream: &mut WebSocketStream<Upgraded>, code: u16, reason: String) {
+    stream
+        .close(Some(CloseFrame {
+            code: code.into(),
+            reason: reason.into(),
+        }))
+        .await
+        .unwrap_or(());
+}
+
+async fn run_websocket<F>(
+    mut stream: WebSocketStream<Upgraded>,
+    channel: WebSocketChannel,
+    options: WebSocketOptions,
+    on_message: F,
+) where
+    F: Fn(WebSocketMessage),
+{
+    let max_duration = sleep(options.max_duration);
+    tokio::pin!(max_duration);
+
+    let mut last_message = Instant::now();
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                last_message = Instant::now();
+
+                match message {
+                    Some(Ok(Message::Text(text))) => on_message(WebSocketMessage::Text(text)),
+                    Some(Ok(Message::Binary(bytes))) => on_message(WebSocketMessage::Binary(bytes)),
+                    Some(Ok(Message::Close(frame))) => {
+                        let (code, reason) = frame.map_or((CLOSE_NO_STATUS, String::new()), |frame| {
+                            (frame.code.into(), frame.reason.into_owned())
+                        });
+
+                        on_message(WebSocketMessage::Close(code, reason));
+
+                        // Acknowledge the close frame
+                        stream.close(None).await.unwrap_or(());
+                        break;
+                    }
+                    // Pings are answered automatically
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => {
+                        on_message(WebSocketMessage::Close(CLOSE_ABNORMAL, String::new()));
+                        break;
+                    }
+                }
+            }
+            message = channel.receiver.recv_async() => {
+                last_message = Instant::now();
+
+                let message = match message {
+                    Ok(WebSocketMessage::Text(text)) => Message::Text(text),
+                    Ok(WebSocketMessage::Binary(bytes)) => Message::Binary(bytes),
+                    // The socket was already removed from the isolate
+                    Ok(WebSocketMessage::Close(code, reason)) => {
+                        close(&mut stream, code, reason).await;
+                        break;
+                    }
+                    // The isolate was terminated, e.g because it reached its limits
+                    Err(_) => {
+                        close(&mut stream, CLOSE_INTERNAL_ERROR, String::from("Function terminated")).await;
+                        break;
+                    }
+                };
+
+                if stream.send(message).await.is_err() {
+                    on_message(WebSocketMessage::Close(CLOSE_ABNORMAL, String::new()));
+                    break;
+                }
+            }
+            _ = &mut max_duration => {
+                let reason = String::from("WebSocket duration limit reached");
+
+                on_message(WebSocketMessage::Close(CLOSE_GOING_AWAY, reason.clone()));
+                close(&mut stream, CLOSE_GOING_AWAY, reason).await;
+                break;
+            }
+            _ = sleep_until(last_message + options.idle_timeout) => {
+                let reason = String::from("WebSocket idle timeout");
+
+                on_message(WebSocketMessage::Close(CLOSE_GOING_AWAY, reason.clone()));
+                close(&mut stream, CLOSE_GOING_AWAY, reason).await;
+                break;
+            }
+        }
+    }
+}
@@ -47,17 +47,42 @@ pub fn extract_v8_headers_object(
                 .get_index(scope, 0)
                 .map_or_else(String::new, |key| key.to_rust_string_lossy(scope));
 
-            let value = entry
-                .get_index(scope, 1)
-                .map_or_else(String::new, |value| value.to_rust_string_lossy(scope));
-
-            header_map.append(HeaderName::from_bytes(key.as_bytes())?, value.parse()?);
+            let value = match entry.get_index(scope, 1) {
+                Some(value) => extract_v8_byte_string(value, scope)?,
+                None => Vec::new(),
+            };
+
+            header_map.append(
+                HeaderName::from_bytes(key.as_bytes())?,
+                HeaderValue::from_bytes(&value)?,
+            );
         }
     }
 
     Ok(())
 }
 
+// Header values are ByteStrings: each character is a single byte. Strings
+// containing characters outside of latin1 are encoded as UTF-8 instead.
+fn extract_v8_byte_string(
+    value: v8::Local<v8::Value>,
+    scope: &mut v8::HandleScope,
+) -> Result<Vec<u8>> {
+    let value = match value.to_string(scope) {
+        Some(value) => value,
+        None => return Err(anyhow!("Value is not a string")),
+    };
+
+    if !value.contains_only_onebyte() {
+        return Ok(value.to_rust_string_lossy(scope).into_bytes());
+    }
+
+    let mut buf = vec![0; value.length()];
+    value.write_one_byte(scope, &mut buf, 0, v8::WriteOptions::NO_NULL_TERMINATION);
+
+    Ok(buf)
+}
+
 pub fn extract_v8_uint8array(value: v8::Local<v8::Value>) -> Result<Vec<u8>> {
     if !value.is_uint8_array() {
         return Err(anyhow!("Value is not of type 'Uint8Array'"));
@@ -77,6 +102,10 @@ pub fn v8_string<'a>(
     v8::String::new(scope, value).unwrap()
 }
 
+fn v8_byte_string<'a>(scope: &mut v8::HandleScope<'a>, value: &[u8]) -> v8::Local<'a, v8::String> {
+    v8::String::new_from_one_byte(scope, value, v8::NewStringType::Normal).unwrap()
+}
+
 pub fn v8_integer<'a>(scope: &mut v8::HandleScope<'a>, value: i32) -> v8::Local<'a, v8::Integer> {
     v8::Integer::new(scope, value)
 }
@@ -100,10 +129,12 @@ pub fn v8_headers_object<'a>(
 ) -> v8::Local<'a, v8::Array> {
     let mut elements = Vec::with_capacity(value.len());
 
-    for key in value.keys() {
+    // Iterate over every value, since a header can
+    // be present multiple times (e.g Set-Cookie)
+    for (key, value) in value.iter() {
         let keypair = [
             v8_string(scope, key.as_str()).into(),
-            v8_string(scope, value.get(key).unwrap().to_str().unwrap()).into(),
+            v8_byte_string(scope, value.as_bytes()).into(),
         ];
 
         let keypair = v8::Array::new_with_elements(scope, &keypair);
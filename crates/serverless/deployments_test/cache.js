@@ -0,0 +1,11 @@
+export async function handler(request) {
+  const cached = await caches.default.match(request.url);
+
+  if (cached) {
+    return new Response(`HIT ${await cached.text()}`);
+  }
+
+  await caches.default.put(request.url, new Response('Hello', { headers: { 'cache-control': 'max-age=60' } }));
+
+  return new Response('MISS');
+}
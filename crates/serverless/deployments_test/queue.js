@@ -0,0 +1,20 @@
+export async function handler(request, { env }) {
+  const value = new URL(request.url).searchParams.get('value');
+
+  if (value) {
+    await env.QUEUE.send({ value });
+    return new Response('Sent');
+  }
+
+  return new Response(`${await env.KV.get('consumed')}`);
+}
+
+export async function queue(batch, { env }) {
+  for (const message of batch.messages) {
+    if (message.body.value === 'fail') {
+      throw new Error('Failed');
+    }
+
+    await env.KV.put('consumed', message.body.value);
+  }
+}
@@ -0,0 +1,11 @@
+export async function handler(request) {
+  if (request.url.endsWith('/loop')) {
+    await new Promise(resolve => setTimeout(resolve, 100));
+    while (true) {}
+  }
+
+  const body = await request.text();
+
+  await new Promise(resolve => setTimeout(resolve, 500));
+  return new Response(body);
+}
@@ -0,0 +1,5 @@
+export async function handler(request, { env }) {
+  const response = await env.AUTH.fetch('http://auth/login');
+
+  return new Response(await response.text());
+}
@@ -21,6 +21,25 @@ pub struct RequestRow {
     pub bytes_in: u32,
     pub bytes_out: u32,
     pub cpu_time_micros: Option<u128>,
+    // Empty unless the request was sent by another Function's service binding
+    pub caller_deployment_id: String,
+    pub timestamp: u32,
+}
+
+#[derive(Row, Serialize, Deserialize)]
+pub struct CronRunRow {
+    pub function_id: String,
+    pub deployment_id: String,
+    pub region: String,
+    // Name of the deployment's cron schedule
+    pub schedule: String,
+    // See `CronOutcome::as_str`
+    pub outcome: String,
+    pub message: String,
+    // Starts at 1, 0 for skipped executions
+    pub attempt: u32,
+    pub duration_micros: u64,
+    // When the attempt started
     pub timestamp: u32,
 }
 
@@ -74,6 +93,7 @@ TTL timestamp + INTERVAL 1 WEEK",
     bytes_in UInt32,
     bytes_out UInt32,
     cpu_time_micros Nullable(UInt128),
+    caller_deployment_id String,
     timestamp DateTime,
 )
 ENGINE = MergeTree()
@@ -82,5 +102,34 @@ PRIMARY KEY (function_id, timestamp)",
         .execute()
         .await?;
 
+    client
+        .query(
+            "CREATE TABLE IF NOT EXISTS serverless.cron_runs
+(
+    function_id String,
+    deployment_id String,
+    region String,
+    schedule String,
+    outcome String,
+    message String,
+    attempt UInt32,
+    duration_micros UInt64,
+    timestamp DateTime,
+)
+ENGINE = MergeTree()
+PRIMARY KEY (function_id, timestamp)",
+        )
+        .execute()
+        .await?;
+
+    // Tables created before service bindings don't have this column
+    client
+        .query(
+            "ALTER TABLE serverless.requests
+ADD COLUMN IF NOT EXISTS caller_deployment_id String AFTER cpu_time_micros",
+        )
+        .execute()
+        .await?;
+
     Ok(())
 }
@@ -1,269 +1,459 @@
-use anyhow::Result;
-use bytes::Bytes;
-use clickhouse::inserter::Inserter;
+use anyhow::{anyhow, Result};
+use chrono_tz::Tz;
+use clickhouse::Client;
 use futures::lock::Mutex;
-use hyper::{body, Request};
 use lagon_runtime_http::RunResult;
-use lagon_runtime_isolate::{
-    options::{IsolateOptions, Metadata},
-    Isolate, IsolateEvent, IsolateRequest,
-};
-use lagon_runtime_utils::Deployment;
+use lagon_runtime_isolate::{IsolateEvent, IsolateScheduled};
+use lagon_runtime_utils::{CronOverlap, Deployment};
 use log::{error, info, warn};
-use metrics::{decrement_gauge, histogram, increment_gauge};
 use std::{
     collections::HashMap,
     sync::Arc,
-    time::{Duration, UNIX_EPOCH},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
-use tokio::runtime::Handle;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use uuid::Uuid;
 
 use crate::{
-    clickhouse::{LogRow, RequestRow},
-    get_region, SNAPSHOT_BLOB,
+    clickhouse::{CronRunRow, RequestRow},
+    get_region,
+    serverless::{dispatch_with_retry, ServerlessContext},
 };
 
+// Delay before the first retry of a failed execution,
+// doubled for each following retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+// The outcome of a cron execution attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CronOutcome {
+    Success,
+    // Scheduled events never resolve with a response
+    Response,
+    Timeout,
+    MemoryLimit,
+    Error,
+    Interrupted,
+    Skipped,
+}
+
+impl CronOutcome {
+    // The value stored in the `outcome` column of `serverless.cron_runs`
+    fn as_str(&self) -> &'static str {
+        match self {
+            CronOutcome::Success => "success",
+            CronOutcome::Response => "response",
+            CronOutcome::Timeout => "timeout",
+            CronOutcome::MemoryLimit => "memory_limit",
+            CronOutcome::Error => "error",
+            CronOutcome::Interrupted => "interrupted",
+            CronOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+// A named schedule of a deployment, registered in the scheduler
+#[derive(Clone)]
+struct CronSchedule {
+    deployment: Arc<Deployment>,
+    name: String,
+    expression: String,
+    // Held while an execution (including its retries) is running
+    running: Arc<Mutex<()>>,
+}
+
 pub struct Cronjob {
-    jobs: HashMap<String, Uuid>,
+    // Deployment id to its schedules and their job, by name
+    jobs: HashMap<String, HashMap<String, (Uuid, CronSchedule)>>,
     scheduler: JobScheduler,
-    log_sender: flume::Sender<(String, String, Metadata)>,
-    inserters: Arc<Mutex<(Inserter<RequestRow>, Inserter<LogRow>)>>,
+    context: ServerlessContext,
+    client: Client,
 }
 
 impl Cronjob {
-    pub async fn new(
-        log_sender: flume::Sender<(String, String, Metadata)>,
-        inserters: Arc<Mutex<(Inserter<RequestRow>, Inserter<LogRow>)>>,
-    ) -> Self {
+    pub(crate) async fn new(context: ServerlessContext, client: Client) -> Self {
         let scheduler = JobScheduler::new().await.unwrap();
         scheduler.start().await.unwrap();
 
         Self {
             jobs: HashMap::new(),
             scheduler,
-            log_sender,
-            inserters,
+            context,
+            client,
         }
     }
 
     pub async fn add(&mut self, deployment: Arc<Deployment>) -> Result<()> {
-        if let Some(cron) = &deployment.cron {
-            // Adding a 0 at the beginning because tokio-cron-scheduler's
-            // cron format include seconds at the start
-            let cron = format!("0 {cron}");
+        if !deployment.is_cron() {
+            return Ok(());
+        }
 
-            info!("Registering cron {} for deployment {}", cron, deployment.id);
+        let timezone = match &deployment.cron_options.timezone {
+            Some(timezone) => timezone.parse::<Tz>().unwrap_or_else(|error| {
+                warn!(deployment = deployment.id; "Invalid cron timezone {}, using UTC: {}", timezone, error);
 
-            let id = deployment.id.clone();
-            let inserters = self.inserters.clone();
-            let log_sender = self.log_sender.clone();
+                Tz::UTC
+            }),
+            None => Tz::UTC,
+        };
+
+        let mut schedules = HashMap::new();
+
+        for (name, expression) in &deployment.crons {
+            info!(
+                "Registering cron {} ({}) for deployment {}",
+                name, expression, deployment.id
+            );
+
+            // Adding a 0 at the beginning because tokio-cron-scheduler's
+            // cron format include seconds at the start
+            let cron = format!("0 {expression}");
+
+            let schedule = CronSchedule {
+                deployment: Arc::clone(&deployment),
+                name: name.clone(),
+                expression: expression.clone(),
+                running: Arc::new(Mutex::new(())),
+            };
+            let context = self.context.clone();
+            let client = self.client.clone();
+            let run_schedule = schedule.clone();
 
             let uuid = self
                 .scheduler
-                .add(Job::new_async(cron.as_str(), move |_, _| {
-                    let labels = [
-                        ("deployment", deployment.id.clone()),
-                        ("function", deployment.function_id.clone()),
-                    ];
-
-                    let deployment = Arc::clone(&deployment);
-                    let inserters = Arc::clone(&inserters);
-                    let log_sender = log_sender.clone();
-                    let code = deployment.get_code().unwrap_or_else(|error| {
-                        error!(deployment = deployment.id; "Error while getting deployment code: {}", error);
-
-                        "".into()
-                    });
-
-                    Box::pin(async move {
-                        let handle = Handle::current();
-                        let (isolate_sender, isolate_receiver) = flume::unbounded();
-                        let log_sender_handle = log_sender.clone();
-                        let deployment_handle = Arc::clone(&deployment);
-
-                        std::thread::Builder::new().name(String::from("cron-") + deployment.id.as_str()).spawn(move || {
-                            handle.block_on(async move {
-                                let deployment  = deployment_handle;
-
-                                increment_gauge!("lagon_isolates", 1.0, &labels);
-                                info!(deployment = deployment.id.clone(), function = deployment.function_id.clone(); "Creating new cron isolate");
-
-                                let options = IsolateOptions::new(code)
-                                    .environment_variables(deployment.environment_variables.clone())
-                                    .memory(deployment.memory)
-                                    .tick_timeout(Duration::from_millis(deployment.tick_timeout as u64))
-                                    .total_timeout(Duration::from_millis(
-                                        deployment.total_timeout as u64,
-                                    ))
-                                    .metadata(Some((
-                                        deployment.id.clone(),
-                                        deployment.function_id.clone(),
-                                    )))
-                                    .on_drop_callback(Box::new(|metadata| {
-                                        if let Some(metadata) = metadata.as_ref().as_ref() {
-                                            let labels = [
-                                                ("deployment", metadata.0.clone()),
-                                                ("function", metadata.1.clone()),
-                                            ];
-
-                                            decrement_gauge!("lagon_isolates", 1.0, &labels);
-                                            info!(deployment = metadata.0, function = metadata.1; "Dropping cron isolate");
-                                        }
-                                    }))
-                                    .on_statistics_callback(Box::new(|metadata, statistics| {
-                                        if let Some(metadata) = metadata.as_ref().as_ref() {
-                                            let labels = [
-                                                ("deployment", metadata.0.clone()),
-                                                ("function", metadata.1.clone()),
-                                            ];
-
-                                            histogram!(
-                                                "lagon_isolate_memory_usage",
-                                                statistics as f64,
-                                                &labels
-                                            );
-                                        }
-                                    }))
-                                    .log_sender(log_sender_handle)
-                                    .snapshot_blob(SNAPSHOT_BLOB);
-
-                                let mut isolate = Isolate::new(options, isolate_receiver);
-                                isolate.evaluate();
-                                isolate.run_event_loop().await;
-                            });
-                        }).unwrap();
-
-                        let (sender, receiver) = flume::unbounded();
-                        let request = Request::new(Bytes::new()).into_parts();
-
-                        isolate_sender.send_async(IsolateEvent::Request(IsolateRequest {
-                            sender,
-                            request,
-                        })).await.unwrap_or(());
-
-                        let run_result = receiver.recv_async().await.expect("Isolate didn't send a response");
-
-                        isolate_sender.send_async(IsolateEvent::Terminate(String::from("Cron completed"))).await.unwrap_or(());
-
-                        let (level, message) = match run_result {
-                            RunResult::Stream(_) => {
-                                warn!(
-                                    deployment = deployment.id,
-                                    function = deployment.function_id;
-                                    "Cron Functions can't return a stream",
-                                );
-
-                                (String::from("warn"), String::from("Cron Functions can't return a stream"))
-                            }
-                            RunResult::Response(response_builder, body, elapsed) => {
-                                let response = response_builder.body(body).unwrap();
-                                let status = response.status();
-                                let body = body::to_bytes(response.into_body()).await.unwrap_or_else(|error| {
-                                    error!(
-                                        deployment = deployment.id,
-                                        function = deployment.function_id;
-                                        "Error while reading response body: {}", error,
-                                    );
-
-                                    Bytes::new()
-                                });
-
-                                let timestamp = UNIX_EPOCH.elapsed().unwrap().as_secs() as u32;
-
-                                inserters
-                                    .lock()
-                                    .await
-                                    .0
-                                    .write(&RequestRow {
-                                        function_id: deployment.function_id.clone(),
-                                        deployment_id: deployment.id.clone(),
-                                        region: get_region().clone(),
-                                        bytes_in: 0,
-                                        bytes_out: 0,
-                                        cpu_time_micros: elapsed.map(|duration| duration.as_micros()),
-                                        timestamp,
-                                    })
-                                    .await
-                                    .unwrap_or(());
-
-                                let body = String::from_utf8_lossy(&body);
-                                let maybe_body = if body == "" { String::from("") } else { format!(": {body}") };
-
-                                if status == 200 {
-                                    info!(
-                                        deployment = deployment.id,
-                                        function = deployment.function_id;
-                                        "Cron execution successful{}",
-                                        maybe_body,
-                                    );
-
-                                    (String::from("info"), format!("Cron execution successful{}", maybe_body))
-                                } else {
-
-                                    error!(
-                                        deployment = deployment.id,
-                                        function = deployment.function_id;
-                                        "Cron execution failed with status {}{}",
-                                        status,
-                                        maybe_body,
-                                    );
-
-                                    (String::from("error"), format!("Cron execution failed with status {}{}", status, maybe_body))
-                                }
-                            }
-                            RunResult::Timeout => {
-                                warn!(
-                                    deployment = deployment.id,
-                                    function = deployment.function_id;
-                                    "Cron execution timed out",
-                                );
-
-                                (String::from("warn"), String::from("Cron execution timed out"))
-                            }
-                            RunResult::MemoryLimit => {
-                                warn!(
-                                    deployment = deployment.id,
-                                    function = deployment.function_id;
-                                    "Cron execution memory limit reached",
-                                );
-
-                                (String::from("warn"), String::from("Cron execution memory limit reached"))
-                            }
-                            RunResult::Error(error) => {
-                                error!(
-                                    deployment = deployment.id,
-                                    function = deployment.function_id;
-                                    "Cron execution error: {}",
-                                    error,
-                                );
-
-                                (String::from("error"), format!("Cron execution error: {}", error))
-                            }
-                        };
-
-                        log_sender.send_async((level, message, Some((
-                            deployment.id.clone(),
-                            deployment.function_id.clone(),
-                        )))).await.unwrap_or(());
-                    })
+                .add(Job::new_async_tz(cron.as_str(), timezone, move |_, _| {
+                    Box::pin(run(
+                        context.clone(),
+                        client.clone(),
+                        run_schedule.clone(),
+                        scheduled_tick(),
+                    ))
                 })?)
                 .await?;
 
-            self.jobs.insert(id, uuid);
+            schedules.insert(name.clone(), (uuid, schedule));
         }
 
+        self.jobs.insert(deployment.id.clone(), schedules);
+
         Ok(())
     }
 
     pub async fn remove(&mut self, deployment_id: &String) -> Result<()> {
-        if let Some(uuid) = self.jobs.remove(deployment_id) {
+        if let Some(schedules) = self.jobs.remove(deployment_id) {
             info!("Unregistering cron for deployment {}", deployment_id);
 
-            self.scheduler.remove(&uuid).await?;
+            for (uuid, _) in schedules.values() {
+                self.scheduler.remove(uuid).await?;
+            }
         }
 
         Ok(())
     }
+
+    // Run a schedule of a deployment now, outside of its cron expression.
+    // The execution follows the same overlap policy and retries
+    pub fn trigger(&self, deployment_id: &str, name: &str) -> Result<()> {
+        let (_, schedule) = self
+            .jobs
+            .get(deployment_id)
+            .and_then(|schedules| schedules.get(name))
+            .ok_or_else(|| anyhow!("Schedule not found"))?;
+
+        info!("Triggering cron {} for deployment {}", name, deployment_id);
+
+        tokio::spawn(run(
+            self.context.clone(),
+            self.client.clone(),
+            schedule.clone(),
+            UNIX_EPOCH.elapsed().unwrap().as_millis() as u64,
+        ));
+
+        Ok(())
+    }
+}
+
+// Cron expressions have a minute granularity, and the seconds are always
+// set to 0 when registering them. Jobs are started right when their tick
+// is reached, so the tick is the start of the current minute
+fn scheduled_tick() -> u64 {
+    let now = UNIX_EPOCH.elapsed().unwrap().as_millis() as u64;
+
+    now - now % 60_000
+}
+
+// Delay before the given retry, capped to `RETRY_MAX_DELAY`
+fn retry_delay(attempt: u32) -> Duration {
+    2u32.checked_pow(attempt.saturating_sub(1))
+        .and_then(|factor| RETRY_BASE_DELAY.checked_mul(factor))
+        .map_or(RETRY_MAX_DELAY, |delay| delay.min(RETRY_MAX_DELAY))
+}
+
+// Retries and queued executions keep the original scheduled time
+async fn run(
+    context: ServerlessContext,
+    client: Client,
+    schedule: CronSchedule,
+    scheduled_time: u64,
+) {
+    let CronSchedule {
+        deployment,
+        name,
+        expression,
+        running,
+    } = schedule;
+
+    let _guard = match deployment.cron_options.overlap {
+        CronOverlap::Skip => match running.try_lock() {
+            Some(guard) => Some(guard),
+            None => {
+                warn!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution skipped, the previous one is still running",
+                );
+
+                let message =
+                    String::from("Cron execution skipped, the previous one is still running");
+
+                insert_cron_run(
+                    &client,
+                    &deployment,
+                    &name,
+                    CronOutcome::Skipped,
+                    message.clone(),
+                    0,
+                    Duration::ZERO,
+                )
+                .await;
+
+                context
+                    .log_sender
+                    .send_async((
+                        String::from("warn"),
+                        message,
+                        Some((deployment.id.clone(), deployment.function_id.clone())),
+                    ))
+                    .await
+                    .unwrap_or(());
+
+                return;
+            }
+        },
+        CronOverlap::Queue => Some(running.lock().await),
+        CronOverlap::Allow => None,
+    };
+
+    let mut attempt = 1;
+
+    loop {
+        let start = Instant::now();
+        let run_result = execute(
+            &context,
+            Arc::clone(&deployment),
+            name.clone(),
+            expression.clone(),
+            scheduled_time,
+        )
+        .await;
+        let duration = start.elapsed();
+
+        let (level, message, outcome) = match run_result {
+            RunResult::Scheduled(elapsed) => {
+                let timestamp = UNIX_EPOCH.elapsed().unwrap().as_secs() as u32;
+
+                context
+                    .inserters
+                    .lock()
+                    .await
+                    .0
+                    .write(&RequestRow {
+                        function_id: deployment.function_id.clone(),
+                        deployment_id: deployment.id.clone(),
+                        region: get_region().clone(),
+                        bytes_in: 0,
+                        bytes_out: 0,
+                        cpu_time_micros: Some(elapsed.as_micros()),
+                        caller_deployment_id: String::new(),
+                        timestamp,
+                    })
+                    .await
+                    .unwrap_or(());
+
+                info!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution successful",
+                );
+
+                (
+                    String::from("info"),
+                    String::from("Cron execution successful"),
+                    CronOutcome::Success,
+                )
+            }
+            // Scheduled events never resolve with a response
+            RunResult::Response(..) | RunResult::Stream(_) | RunResult::WebSocket(..) => {
+                warn!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution returned a response",
+                );
+
+                (
+                    String::from("warn"),
+                    String::from("Cron execution returned a response"),
+                    CronOutcome::Response,
+                )
+            }
+            RunResult::Timeout => {
+                warn!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution timed out",
+                );
+
+                (
+                    String::from("warn"),
+                    String::from("Cron execution timed out"),
+                    CronOutcome::Timeout,
+                )
+            }
+            RunResult::MemoryLimit => {
+                warn!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution memory limit reached",
+                );
+
+                (
+                    String::from("warn"),
+                    String::from("Cron execution memory limit reached"),
+                    CronOutcome::MemoryLimit,
+                )
+            }
+            RunResult::Error(error) => {
+                error!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution error: {}",
+                    error,
+                );
+
+                (
+                    String::from("error"),
+                    format!("Cron execution error: {}", error),
+                    CronOutcome::Error,
+                )
+            }
+            RunResult::Retry => {
+                warn!(
+                    deployment = deployment.id,
+                    function = deployment.function_id;
+                    "Cron execution interrupted",
+                );
+
+                (
+                    String::from("warn"),
+                    String::from("Cron execution interrupted"),
+                    CronOutcome::Interrupted,
+                )
+            }
+        };
+
+        insert_cron_run(
+            &client,
+            &deployment,
+            &name,
+            outcome,
+            message.clone(),
+            attempt,
+            duration,
+        )
+        .await;
+
+        context
+            .log_sender
+            .send_async((
+                level,
+                message,
+                Some((deployment.id.clone(), deployment.function_id.clone())),
+            ))
+            .await
+            .unwrap_or(());
+
+        // Only failed executions are retried
+        if matches!(outcome, CronOutcome::Success | CronOutcome::Response)
+            || attempt > deployment.cron_options.max_retries
+        {
+            break;
+        }
+
+        let delay = retry_delay(attempt);
+
+        info!(
+            deployment = deployment.id,
+            function = deployment.function_id;
+            "Retrying cron execution in {}s",
+            delay.as_secs(),
+        );
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// Run a single execution of the cron on one of the deployment's cached
+// isolates, and wait for the promises passed to `ctx.waitUntil()`. Scheduled
+// events don't have a body, so they can always be sent again to a new isolate
+async fn execute(
+    context: &ServerlessContext,
+    deployment: Arc<Deployment>,
+    name: String,
+    expression: String,
+    scheduled_time: u64,
+) -> RunResult {
+    dispatch_with_retry(context, deployment, "cron execution", |sender| {
+        IsolateEvent::Scheduled(IsolateScheduled {
+            scheduled_time,
+            name: name.clone(),
+            cron: expression.clone(),
+            sender,
+        })
+    })
+    .await
+}
+
+async fn insert_cron_run(
+    client: &Client,
+    deployment: &Deployment,
+    schedule: &str,
+    outcome: CronOutcome,
+    message: String,
+    attempt: u32,
+    duration: Duration,
+) {
+    let row = CronRunRow {
+        function_id: deployment.function_id.clone(),
+        deployment_id: deployment.id.clone(),
+        region: get_region().clone(),
+        schedule: schedule.to_string(),
+        outcome: outcome.as_str().to_string(),
+        message,
+        attempt,
+        duration_micros: duration.as_micros() as u64,
+        timestamp: (UNIX_EPOCH.elapsed().unwrap() - duration).as_secs() as u32,
+    };
+
+    let result = async {
+        let mut insert = client.insert::<CronRunRow>("serverless.cron_runs")?;
+        insert.write(&row).await?;
+        insert.end().await
+    }
+    .await;
+
+    if let Err(error) = result {
+        error!(deployment = deployment.id; "Error while writing cron run: {}", error);
+    }
 }
@@ -1,6 +1,7 @@
 use super::pubsub::clear_deployment_cache;
 use crate::serverless::Workers;
 use dashmap::DashMap;
+use lagon_runtime_isolate::IsolateEvent;
 use std::{
     env,
     sync::Arc,
@@ -25,10 +26,30 @@ pub fn run_cache_clear_task(last_requests: Arc<DashMap<String, Instant>>, worker
 
             let now = Instant::now();
 
+            // Terminate the extra isolates of each pool that are idle,
+            // which were created to handle a burst of requests
+            let idle_senders = workers
+                .iter()
+                .flat_map(|pool| pool.scale_down(isolates_cache_seconds))
+                .collect::<Vec<_>>();
+
+            for tx in idle_senders {
+                tx.send_async(IsolateEvent::Terminate(String::from("scale down")))
+                    .await
+                    .unwrap_or(());
+            }
+
             for last_request in last_requests.iter() {
                 let (deployment_id, last_request) = last_request.pair();
 
-                if now.duration_since(*last_request) > isolates_cache_seconds {
+                // Deployments with open WebSockets are kept until they are closed
+                let has_requests_in_flight = workers
+                    .get(deployment_id)
+                    .map_or(false, |pool| pool.has_requests_in_flight());
+
+                if now.duration_since(*last_request) > isolates_cache_seconds
+                    && !has_requests_in_flight
+                {
                     deployments_to_clear.push(deployment_id.clone());
                 }
             }
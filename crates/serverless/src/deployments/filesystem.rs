@@ -19,6 +19,10 @@ pub fn rm_deployment(deployment_id: &str) -> Result<()> {
     #[cfg(not(feature = "test"))]
     {
         fs::remove_file(Path::new(DEPLOYMENTS_DIR).join(deployment_id.to_owned() + ".js"))?;
+        fs::remove_file(Path::new(DEPLOYMENTS_DIR).join(deployment_id.to_owned() + ".js.map"))
+            .unwrap_or(());
+        fs::remove_file(Path::new(DEPLOYMENTS_DIR).join(deployment_id.to_owned() + ".js.cache"))
+            .unwrap_or(());
         // It's possible that the folder doesn't exists if the deployment has no assets
         fs::remove_dir_all(Path::new(DEPLOYMENTS_DIR).join(deployment_id)).unwrap_or(());
     }
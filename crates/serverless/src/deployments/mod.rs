@@ -2,13 +2,16 @@ use crate::get_region;
 use anyhow::{anyhow, Result};
 use dashmap::DashMap;
 use futures::{stream::FuturesUnordered, StreamExt};
-use lagon_runtime_utils::{Deployment, DEPLOYMENTS_DIR};
+use lagon_runtime_utils::{
+    CronOptions, Deployment, CRON_MAX_RETRIES, DEFAULT_CRON_NAME, DEPLOYMENTS_DIR,
+};
 use lagon_serverless_downloader::Downloader;
 use log::{error, info, warn};
 use mysql::{prelude::Queryable, PooledConn};
 use serde::Deserialize;
+use serde_json::Value;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     path::Path,
     sync::Arc,
@@ -22,6 +25,43 @@ pub mod pubsub;
 
 pub type Deployments = Arc<DashMap<String, Arc<Deployment>>>;
 
+// The Function's main cron expression is the default schedule, and
+// named schedules are stored as a JSON object of names to expressions
+pub fn parse_crons(cron: Option<String>, schedules: &Value) -> BTreeMap<String, String> {
+    let mut crons = schedules
+        .as_object()
+        .map(|schedules| {
+            schedules
+                .iter()
+                .filter_map(|(name, cron)| Some((name.to_owned(), cron.as_str()?.to_string())))
+                .collect::<BTreeMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    if let Some(cron) = cron {
+        crons.insert(DEFAULT_CRON_NAME.into(), cron);
+    }
+
+    crons
+}
+
+// Missing or invalid options fall back to their default value
+pub fn parse_cron_options(value: &Value) -> CronOptions {
+    CronOptions {
+        max_retries: value["maxRetries"]
+            .as_u64()
+            .unwrap_or_default()
+            .min(CRON_MAX_RETRIES as u64) as u32,
+        overlap: value["overlap"]
+            .as_str()
+            .and_then(|overlap| overlap.parse().ok())
+            .unwrap_or_default(),
+        timezone: value["timezone"]
+            .as_str()
+            .map(|timezone| timezone.to_string()),
+    }
+}
+
 pub async fn download_deployment<D>(deployment: &Deployment, downloader: Arc<D>) -> Result<()>
 where
     D: Downloader,
@@ -33,6 +73,14 @@ where
             deployment.write_code(&object)?;
             info!(deployment = deployment.id; "Wrote deployment");
 
+            // Deployments created before source maps were
+            // supported, or without one, don't have it
+            let path = format!("{}.js.map", deployment.id);
+
+            if let Ok(source_map) = downloader.download(&path).await {
+                deployment.write_source_map(&source_map)?;
+            }
+
             if !deployment.assets.is_empty() {
                 let mut futures = FuturesUnordered::new();
 
@@ -113,7 +161,7 @@ LEFT JOIN Domain
 LEFT JOIN EnvVariable 
     ON Function.id = EnvVariable.functionId
 WHERE
-    Function.cron IS NULL
+    (Function.cron IS NULL AND Function.cronSchedules IS NULL)
 OR
     Function.cronRegion = '{}'
 ",
@@ -175,11 +223,106 @@ OR
                     tick_timeout,
                     total_timeout,
                     is_production,
-                    cron,
+                    crons: parse_crons(cron, &Value::Null),
+                    cron_options: CronOptions::default(),
+                    service_bindings: HashMap::new(),
                 });
         },
     )?;
 
+    // Service bindings are stored as a JSON object of binding
+    // names to Function names, which we query separately
+    let service_bindings = conn.query_map(
+        "
+SELECT
+    id,
+    serviceBindings
+FROM
+    Function
+WHERE
+    serviceBindings IS NOT NULL
+",
+        |(function_id, service_bindings): (String, String)| {
+            (
+                function_id,
+                serde_json::from_str::<HashMap<String, String>>(&service_bindings)
+                    .unwrap_or_default(),
+            )
+        },
+    )?;
+
+    for (function_id, service_bindings) in service_bindings {
+        for deployment in deployments_list.values_mut() {
+            if deployment.function_id == function_id {
+                deployment.service_bindings = service_bindings.clone();
+            }
+        }
+    }
+
+    let cron_schedules = conn.query_map(
+        "
+SELECT
+    id,
+    cronSchedules
+FROM
+    Function
+WHERE
+    cronSchedules IS NOT NULL
+",
+        |(function_id, cron_schedules): (String, String)| {
+            (
+                function_id,
+                serde_json::from_str::<Value>(&cron_schedules)
+                    .map(|cron_schedules| parse_crons(None, &cron_schedules))
+                    .unwrap_or_default(),
+            )
+        },
+    )?;
+
+    for (function_id, cron_schedules) in cron_schedules {
+        for deployment in deployments_list.values_mut() {
+            if deployment.function_id == function_id {
+                deployment.crons.extend(cron_schedules.clone());
+            }
+        }
+    }
+
+    let cron_options = conn.query_map(
+        "
+SELECT
+    id,
+    cronOptions
+FROM
+    Function
+WHERE
+    cronOptions IS NOT NULL
+",
+        |(function_id, cron_options): (String, String)| {
+            (
+                function_id,
+                serde_json::from_str::<Value>(&cron_options)
+                    .map(|cron_options| parse_cron_options(&cron_options))
+                    .unwrap_or_default(),
+            )
+        },
+    )?;
+
+    for (function_id, cron_options) in cron_options {
+        for deployment in deployments_list.values_mut() {
+            if deployment.function_id == function_id {
+                deployment.cron_options = cron_options.clone();
+            }
+        }
+    }
+
+    deployments_list.retain(|_, deployment| match deployment.check_service_bindings() {
+        Ok(()) => true,
+        Err(error) => {
+            error!(deployment = deployment.id; "Failed to load deployment: {}", error);
+            false
+        }
+    });
+
     let deployments_list: Vec<Deployment> = deployments_list.values().cloned().collect();
 
     info!("Found {} deployment(s) to deploy", deployments_list.len());
@@ -1,8 +1,13 @@
-use super::{download_deployment, filesystem::rm_deployment, Deployment, Deployments};
+use super::{
+    download_deployment, filesystem::rm_deployment, parse_cron_options, parse_crons, Deployment,
+    Deployments,
+};
 use crate::{cronjob::Cronjob, get_region, serverless::Workers};
 use anyhow::Result;
 use futures::StreamExt;
+use lagon_runtime_cache::CacheStore;
 use lagon_runtime_isolate::IsolateEvent;
+use lagon_runtime_utils::DEFAULT_CRON_NAME;
 use lagon_serverless_downloader::Downloader;
 use lagon_serverless_pubsub::{PubSubListener, PubSubMessage, PubSubMessageKind};
 use log::{error, warn};
@@ -12,10 +17,12 @@ use std::{collections::HashMap, sync::Arc};
 use tokio::{runtime::Handle, sync::Mutex};
 
 pub async fn clear_deployment_cache(deployment_id: String, workers: Workers, reason: String) {
-    if let Some((_, tx)) = workers.remove(&deployment_id) {
-        tx.send_async(IsolateEvent::Terminate(reason))
-            .await
-            .unwrap_or(());
+    if let Some((_, pool)) = workers.remove(&deployment_id) {
+        for tx in pool.drain() {
+            tx.send_async(IsolateEvent::Terminate(reason.clone()))
+                .await
+                .unwrap_or(());
+        }
     }
 }
 
@@ -24,6 +31,7 @@ async fn run<D, P>(
     deployments: Deployments,
     workers: Workers,
     cronjob: Arc<Mutex<Cronjob>>,
+    cache_store: Arc<CacheStore>,
     pubsub: Arc<Mutex<P>>,
 ) -> Result<()>
 where
@@ -36,18 +44,35 @@ where
     while let Some(Ok(PubSubMessage { kind, payload })) = stream.next().await {
         let value: Value = serde_json::from_str(&payload)?;
 
-        let cron = value["cron"].as_str();
+        let crons = parse_crons(
+            value["cron"].as_str().map(|cron| cron.to_string()),
+            &value["cronSchedules"],
+        );
         let cron_region = value["cronRegion"].as_str().unwrap().to_string();
 
+        // Manual triggers only run on the node that runs the
+        // crons of this Function, which already has the deployment
+        if kind == PubSubMessageKind::Trigger {
+            if &cron_region == get_region() {
+                let deployment_id = value["deploymentId"].as_str().unwrap();
+                let schedule = value["schedule"].as_str().unwrap_or(DEFAULT_CRON_NAME);
+
+                if let Err(error) = cronjob.lock().await.trigger(deployment_id, schedule) {
+                    error!(deployment = deployment_id; "Failed to trigger cron {}: {}", schedule, error);
+                }
+            }
+
+            continue;
+        }
+
         // Ignore deployments that have a cron set but where
         // the region isn't this node' region, except for undeploys
         // because we might remove the cron from the old region
-        if cron.is_some() && &cron_region != get_region() && kind != PubSubMessageKind::Undeploy {
+        if !crons.is_empty() && &cron_region != get_region() && kind != PubSubMessageKind::Undeploy
+        {
             continue;
         }
 
-        let cron = cron.map(|cron| cron.to_string());
-
         let deployment = Deployment {
             id: value["deploymentId"].as_str().unwrap().to_string(),
             function_id: value["functionId"].as_str().unwrap().to_string(),
@@ -74,14 +99,30 @@ where
             tick_timeout: value["tickTimeout"].as_u64().unwrap() as usize,
             total_timeout: value["totalTimeout"].as_u64().unwrap() as usize,
             is_production: value["isProduction"].as_bool().unwrap(),
-            cron,
+            crons,
+            cron_options: parse_cron_options(&value["cronOptions"]),
+            // Messages published before service bindings existed don't have them
+            service_bindings: value["serviceBindings"]
+                .as_object()
+                .map(|service_bindings| {
+                    service_bindings
+                        .iter()
+                        .filter_map(|(k, v)| Some((k.to_owned(), v.as_str()?.to_string())))
+                        .collect::<HashMap<_, _>>()
+                })
+                .unwrap_or_default(),
         };
 
         let workers = Arc::clone(&workers);
 
         match kind {
             PubSubMessageKind::Deploy => {
-                match download_deployment(&deployment, Arc::clone(&downloader)).await {
+                let result = match deployment.check_service_bindings() {
+                    Ok(()) => download_deployment(&deployment, Arc::clone(&downloader)).await,
+                    Err(error) => Err(error),
+                };
+
+                match result {
                     Ok(_) => {
                         increment_counter!(
                             "lagon_deployments",
@@ -115,7 +156,7 @@ where
                         );
                         error!(
                             deployment = deployment.id;
-                            "Failed to download deployment: {}", error
+                            "Failed to load deployment: {}", error
                         );
                     }
                 };
@@ -143,6 +184,10 @@ where
                         )
                         .await;
 
+                        if let Err(error) = cache_store.purge(&deployment.id).await {
+                            error!(deployment = deployment.id; "Failed to purge cache: {}", error);
+                        }
+
                         let mut cronjob = cronjob.lock().await;
 
                         cronjob.remove(&deployment.id).await.unwrap_or(());
@@ -218,6 +263,7 @@ pub fn listen_pub_sub<D, P>(
     deployments: Deployments,
     workers: Workers,
     cronjob: Arc<Mutex<Cronjob>>,
+    cache_store: Arc<CacheStore>,
     pubsub: Arc<Mutex<P>>,
 ) where
     D: Downloader + Send + Sync + 'static,
@@ -232,6 +278,7 @@ pub fn listen_pub_sub<D, P>(
                     Arc::clone(&deployments),
                     Arc::clone(&workers),
                     Arc::clone(&cronjob),
+                    Arc::clone(&cache_store),
                     Arc::clone(&pubsub),
                 )
                 .await
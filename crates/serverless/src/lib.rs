@@ -1,14 +1,178 @@
-use std::{env, sync::OnceLock};
+use lagon_runtime_cache::CacheStoreOptions;
+use lagon_runtime_db::Database;
+use lagon_runtime_isolate::fetch_policy::{FetchPolicy, HostRule};
+use lagon_runtime_kv::{KvNamespace, KvStore};
+use lagon_runtime_queue::{Queue, QueueStore};
+use lagon_runtime_utils::{websocket::WebSocketOptions, Deployment, DEPLOYMENTS_DIR};
+use log::error;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 pub mod clickhouse;
 pub mod cronjob;
 pub mod deployments;
+pub mod pool;
+pub mod queue;
 pub mod serverless;
+pub mod service_bindings;
 
 static REGION: OnceLock<String> = OnceLock::new();
+static ROOT_DOMAIN: OnceLock<String> = OnceLock::new();
+static FETCH_POLICY: OnceLock<Arc<FetchPolicy>> = OnceLock::new();
 
 pub fn get_region() -> &'static String {
     REGION.get_or_init(|| env::var("LAGON_REGION").expect("LAGON_REGION must be set"))
 }
 
+pub fn get_root_domain() -> &'static String {
+    ROOT_DOMAIN
+        .get_or_init(|| env::var("LAGON_ROOT_DOMAIN").expect("LAGON_ROOT_DOMAIN must be set"))
+}
+
+fn parse_host_rules(name: &str) -> Vec<HostRule> {
+    env::var(name).map_or_else(
+        |_| Vec::new(),
+        |rules| {
+            rules
+                .split(',')
+                .filter(|rule| !rule.trim().is_empty())
+                .map(|rule| {
+                    rule.parse()
+                        .unwrap_or_else(|_| panic!("{name} contains an invalid host: {rule}"))
+                })
+                .collect()
+        },
+    )
+}
+
+// Shared by all the isolates, so they also share the same HTTP client.
+// Unlike `lagon dev`, private addresses are blocked unless disabled
+pub fn get_fetch_policy() -> Arc<FetchPolicy> {
+    Arc::clone(FETCH_POLICY.get_or_init(|| {
+        let default = FetchPolicy::default();
+
+        let max_calls = env::var("LAGON_FETCH_MAX_CALLS").map_or(default.max_calls, |max| {
+            max.parse()
+                .expect("LAGON_FETCH_MAX_CALLS is not a valid number")
+        });
+        let block_private_addresses =
+            env::var("LAGON_FETCH_BLOCK_PRIVATE_ADDRESSES").map_or(true, |block| {
+                block
+                    .parse()
+                    .expect("LAGON_FETCH_BLOCK_PRIVATE_ADDRESSES is not a valid boolean")
+            });
+        let max_response_size = env::var("LAGON_FETCH_MAX_RESPONSE_SIZE").ok().map(|size| {
+            size.parse()
+                .expect("LAGON_FETCH_MAX_RESPONSE_SIZE is not a valid number")
+        });
+
+        Arc::new(
+            default
+                .max_calls(max_calls)
+                .allowed_hosts(parse_host_rules("LAGON_FETCH_ALLOWED_HOSTS"))
+                .denied_hosts(parse_host_rules("LAGON_FETCH_DENIED_HOSTS"))
+                .block_private_addresses(block_private_addresses)
+                .max_response_size(max_response_size),
+        )
+    }))
+}
+
+// Each Function gets its own namespace, shared by all its deployments
+pub fn get_kv_namespace(
+    kv_store: &Arc<dyn KvStore>,
+    deployment: &Deployment,
+) -> Option<KvNamespace> {
+    KvNamespace::new(Arc::clone(kv_store), deployment.function_id.clone())
+        .map_err(|error| {
+            error!(deployment = deployment.id; "Error while creating KV namespace: {}", error);
+        })
+        .ok()
+}
+
+// Each Function gets its own queue, consumed by its production deployment
+pub fn get_queue(queue_store: &Arc<dyn QueueStore>, deployment: &Deployment) -> Option<Queue> {
+    Queue::new(Arc::clone(queue_store), deployment.function_id.clone())
+        .map_err(|error| {
+            error!(deployment = deployment.id; "Error while creating queue: {}", error);
+        })
+        .ok()
+}
+
+// Without a directory, entries evicted from memory are dropped instead of moved to disk
+pub fn get_cache_store_options() -> CacheStoreOptions {
+    let default = CacheStoreOptions::default();
+
+    let root = env::var("LAGON_CACHE_DIR")
+        .ok()
+        .filter(|root| !root.is_empty())
+        .map(PathBuf::from);
+    let max_memory_size =
+        env::var("LAGON_CACHE_MAX_MEMORY_SIZE").map_or(default.max_memory_size, |size| {
+            size.parse()
+                .expect("LAGON_CACHE_MAX_MEMORY_SIZE is not a valid number")
+        });
+    let max_disk_size =
+        env::var("LAGON_CACHE_MAX_DISK_SIZE").map_or(default.max_disk_size, |size| {
+            size.parse()
+                .expect("LAGON_CACHE_MAX_DISK_SIZE is not a valid number")
+        });
+
+    default
+        .root(root)
+        .max_memory_size(max_memory_size)
+        .max_disk_size(max_disk_size)
+}
+
+// Both limits are in seconds
+pub fn get_websocket_options() -> WebSocketOptions {
+    let default = WebSocketOptions::default();
+
+    let max_duration =
+        env::var("LAGON_WEBSOCKET_MAX_DURATION").map_or(default.max_duration, |seconds| {
+            Duration::from_secs(
+                seconds
+                    .parse()
+                    .expect("LAGON_WEBSOCKET_MAX_DURATION is not a valid number"),
+            )
+        });
+    let idle_timeout =
+        env::var("LAGON_WEBSOCKET_IDLE_TIMEOUT").map_or(default.idle_timeout, |seconds| {
+            Duration::from_secs(
+                seconds
+                    .parse()
+                    .expect("LAGON_WEBSOCKET_IDLE_TIMEOUT is not a valid number"),
+            )
+        });
+
+    default
+        .max_duration(max_duration)
+        .idle_timeout(idle_timeout)
+}
+
+// In seconds
+pub fn get_wait_until_timeout() -> Duration {
+    env::var("LAGON_WAIT_UNTIL_TIMEOUT").map_or(Duration::from_secs(30), |seconds| {
+        Duration::from_secs(
+            seconds
+                .parse()
+                .expect("LAGON_WAIT_UNTIL_TIMEOUT is not a valid number"),
+        )
+    })
+}
+
+// Each Function gets its own database, shared by all its deployments. Since
+// transactions are tied to the isolate that started them, each isolate
+// opens its own connection to the same file
+pub fn get_database(deployment: &Deployment) -> Database {
+    Database::new(
+        Path::new(DEPLOYMENTS_DIR)
+            .join("databases")
+            .join(format!("{}.sqlite", deployment.function_id)),
+    )
+}
+
 pub const SNAPSHOT_BLOB: &[u8] = include_bytes!("../snapshot.bin");
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use lagon_runtime::{options::RuntimeOptions, Runtime};
+use lagon_runtime_kv::RedisKvStore;
+use lagon_runtime_queue::RedisQueueStore;
 use lagon_serverless::clickhouse::{create_client, run_migrations};
 use lagon_serverless::deployments::get_deployments;
-use lagon_serverless::get_region;
 use lagon_serverless::serverless::start;
+use lagon_serverless::{get_region, get_root_domain};
 use lagon_serverless_downloader::{get_bucket, S3BucketDownloader};
 use lagon_serverless_logger::init_logger;
 use lagon_serverless_pubsub::RedisPubSub;
@@ -32,6 +34,8 @@ async fn main() -> Result<()> {
     dotenv::dotenv().expect("Failed to load .env file");
 
     let _flush_guard = init_logger(get_region().clone()).expect("Failed to init logger");
+    // Used by service bindings, read here to fail early when it's missing
+    get_root_domain();
 
     let runtime = Runtime::new(RuntimeOptions::default());
     let addr: SocketAddr = env::var("LAGON_LISTEN_ADDR")
@@ -69,13 +73,24 @@ async fn main() -> Result<()> {
     let downloader = Arc::new(S3BucketDownloader::new(bucket));
 
     let url = env::var("REDIS_URL").expect("REDIS_URL must be set");
+    let kv_store = Arc::new(RedisKvStore::new(url.clone()).await?);
+    let queue_store = Arc::new(RedisQueueStore::new(url.clone(), get_region().clone()).await?);
     let pubsub = RedisPubSub::new(url);
 
     let client = create_client();
     run_migrations(&client).await?;
 
     let deployments = get_deployments(conn, Arc::clone(&downloader)).await?;
-    let serverless = start(deployments, addr, downloader, pubsub, client).await?;
+    let serverless = start(
+        deployments,
+        addr,
+        downloader,
+        pubsub,
+        client,
+        kv_store,
+        queue_store,
+    )
+    .await?;
     tokio::spawn(serverless).await?;
 
     runtime.dispose();
@@ -0,0 +1,206 @@
+use lagon_runtime_isolate::IsolateEvent;
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolOptions {
+    pub min_isolates: usize,
+    pub max_isolates: usize,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            min_isolates: 1,
+            max_isolates: 1,
+        }
+    }
+}
+
+impl PoolOptions {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+
+        let min_isolates =
+            env::var("LAGON_POOL_MIN_ISOLATES").map_or(default.min_isolates, |min| {
+                min.parse()
+                    .expect("LAGON_POOL_MIN_ISOLATES is not a valid number")
+            });
+        let max_isolates =
+            env::var("LAGON_POOL_MAX_ISOLATES").map_or(default.max_isolates, |max| {
+                max.parse()
+                    .expect("LAGON_POOL_MAX_ISOLATES is not a valid number")
+            });
+
+        // A pool always contains at least one isolate
+        let min_isolates = min_isolates.max(1);
+
+        Self {
+            min_isolates,
+            max_isolates: max_isolates.max(min_isolates),
+        }
+    }
+}
+
+struct PooledIsolate {
+    sender: flume::Sender<IsolateEvent>,
+    in_flight: Arc<AtomicUsize>,
+    last_request: Mutex<Instant>,
+}
+
+impl PooledIsolate {
+    fn new(sender: flume::Sender<IsolateEvent>) -> Self {
+        Self {
+            sender,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            last_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+}
+
+// Keeps track of a request dispatched to an isolate: the
+// request is considered in flight until the guard is dropped
+pub struct PoolGuard {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for PoolGuard {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A pool of isolates for a single deployment. Isolates are spread across
+// the scheduler's worker threads, allowing a deployment to use more than one core.
+pub struct IsolatePool {
+    isolates: Mutex<Vec<Arc<PooledIsolate>>>,
+    options: PoolOptions,
+}
+
+impl IsolatePool {
+    pub fn new(options: PoolOptions) -> Self {
+        Self {
+            isolates: Mutex::new(Vec::with_capacity(options.max_isolates)),
+            options,
+        }
+    }
+
+    // Dispatch a request to the least-loaded isolate. A new isolate is created
+    // using `spawn` when the pool is under its minimum size, or when every
+    // isolate already has requests in flight and the pool can still grow.
+    pub fn dispatch(
+        &self,
+        spawn: impl Fn() -> flume::Sender<IsolateEvent>,
+    ) -> (flume::Sender<IsolateEvent>, PoolGuard) {
+        let mut isolates = self.isolates.lock().unwrap();
+
+        // Isolates that were terminated (e.g because they reached
+        // their limits) have dropped their receiver
+        isolates.retain(|isolate| !isolate.sender.is_disconnected());
+
+        while isolates.len() < self.options.min_isolates {
+            isolates.push(Arc::new(PooledIsolate::new(spawn())));
+        }
+
+        let least_loaded = isolates
+            .iter()
+            .min_by_key(|isolate| isolate.in_flight())
+            .map(Arc::clone);
+
+        let isolate = match least_loaded {
+            Some(isolate)
+                if isolate.in_flight() == 0 || isolates.len() >= self.options.max_isolates =>
+            {
+                isolate
+            }
+            _ => {
+                let isolate = Arc::new(PooledIsolate::new(spawn()));
+                isolates.push(Arc::clone(&isolate));
+                isolate
+            }
+        };
+
+        isolate.in_flight.fetch_add(1, Ordering::Relaxed);
+        *isolate.last_request.lock().unwrap() = Instant::now();
+
+        (
+            isolate.sender.clone(),
+            PoolGuard {
+                in_flight: Arc::clone(&isolate.in_flight),
+            },
+        )
+    }
+
+    // Remove an isolate from the pool, e.g when we know it has been
+    // terminated but it didn't drop its receiver yet
+    pub fn evict(&self, sender: &flume::Sender<IsolateEvent>) {
+        self.isolates
+            .lock()
+            .unwrap()
+            .retain(|isolate| !isolate.sender.same_channel(sender));
+    }
+
+    pub fn len(&self) -> usize {
+        self.isolates.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Requests stay in flight until their response is sent,
+    // or until their WebSocket is closed
+    pub fn has_requests_in_flight(&self) -> bool {
+        self.isolates
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|isolate| isolate.in_flight() > 0)
+    }
+
+    // Remove the isolates that didn't receive any request for the given duration,
+    // while keeping the minimum number of isolates. Returns the removed isolates'
+    // senders so the caller can terminate them.
+    pub fn scale_down(&self, idle_timeout: Duration) -> Vec<flume::Sender<IsolateEvent>> {
+        let mut isolates = self.isolates.lock().unwrap();
+        let mut removed = Vec::new();
+
+        let now = Instant::now();
+        let mut index = 0;
+
+        while index < isolates.len() && isolates.len() > self.options.min_isolates {
+            let isolate = &isolates[index];
+            let is_idle = isolate.in_flight() == 0
+                && now.duration_since(*isolate.last_request.lock().unwrap()) > idle_timeout;
+
+            if is_idle {
+                removed.push(isolates.remove(index).sender.clone());
+            } else {
+                index += 1;
+            }
+        }
+
+        removed
+    }
+
+    // Remove all the isolates from the pool, returning their
+    // senders so the caller can terminate them.
+    pub fn drain(&self) -> Vec<flume::Sender<IsolateEvent>> {
+        self.isolates
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|isolate| isolate.sender.clone())
+            .collect()
+    }
+}
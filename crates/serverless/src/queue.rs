@@ -0,0 +1,161 @@
+use lagon_runtime_http::RunResult;
+use lagon_runtime_isolate::{IsolateEvent, IsolateQueue};
+use lagon_runtime_queue::{Queue, QueueMessage, QueueOutcome};
+use lagon_runtime_utils::Deployment;
+use log::{error, warn};
+use metrics::counter;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    get_queue,
+    serverless::{dispatch_with_retry, ServerlessContext},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const BATCH_SIZE: usize = 10;
+
+// Pull batches from the queues of the production deployments known by this
+// node, and consume them on the deployments' cached isolates. A queue is only
+// consumed by one batch at a time, until its messages are settled
+pub(crate) fn run_queue_consumer(context: ServerlessContext) {
+    let consuming = Arc::new(Mutex::new(HashSet::new()));
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let queues = match context.queue_store.queues().await {
+                Ok(queues) => queues,
+                Err(error) => {
+                    error!("Error while listing queues: {}", error);
+                    continue;
+                }
+            };
+
+            for name in queues {
+                let deployment = context
+                    .deployments
+                    .iter()
+                    .find(|deployment| deployment.function_id == name && deployment.is_production)
+                    .map(|deployment| Arc::clone(deployment.value()));
+
+                let deployment = match deployment {
+                    Some(deployment) => deployment,
+                    None => continue,
+                };
+
+                if !consuming.lock().unwrap().insert(name.clone()) {
+                    continue;
+                }
+
+                let context = context.clone();
+                let consuming = Arc::clone(&consuming);
+
+                tokio::spawn(async move {
+                    consume(&context, deployment).await;
+
+                    consuming.lock().unwrap().remove(&name);
+                });
+            }
+        }
+    });
+}
+
+async fn consume(context: &ServerlessContext, deployment: Arc<Deployment>) {
+    let queue = match get_queue(&context.queue_store, &deployment) {
+        Some(queue) => queue,
+        None => return,
+    };
+
+    loop {
+        let messages = match queue.receive(BATCH_SIZE).await {
+            Ok(messages) => messages,
+            Err(error) => {
+                error!(deployment = deployment.id, function = deployment.function_id; "Error while receiving queue messages: {}", error);
+                return;
+            }
+        };
+
+        if messages.is_empty() {
+            return;
+        }
+
+        let (run_result, outcomes) =
+            execute(context, Arc::clone(&deployment), &queue, &messages).await;
+        let success = matches!(run_result, RunResult::Scheduled(_));
+
+        match &run_result {
+            RunResult::Scheduled(_) => {}
+            RunResult::Timeout => {
+                warn!(deployment = deployment.id, function = deployment.function_id; "Queue batch timed out");
+            }
+            RunResult::MemoryLimit => {
+                warn!(deployment = deployment.id, function = deployment.function_id; "Queue batch reached memory limit");
+            }
+            RunResult::Error(error) => {
+                error!(deployment = deployment.id, function = deployment.function_id; "Queue batch failed: {}", error);
+            }
+            // Queue events never resolve with a response
+            _ => {
+                error!(deployment = deployment.id, function = deployment.function_id; "Queue batch returned an unexpected result");
+            }
+        }
+
+        match queue.settle(messages, &outcomes, success).await {
+            Ok(result) => {
+                for (outcome, count) in [
+                    ("acked", result.acked),
+                    ("retried", result.retried),
+                    ("dead_lettered", result.dead_lettered),
+                ] {
+                    counter!("lagon_queue_messages", count as u64, "deployment" => deployment.id.clone(), "function" => deployment.function_id.clone(), "outcome" => outcome);
+                }
+
+                if result.dead_lettered > 0 {
+                    warn!(deployment = deployment.id, function = deployment.function_id; "{} queue message(s) moved to the dead-letter queue", result.dead_lettered);
+                }
+            }
+            Err(error) => {
+                error!(deployment = deployment.id, function = deployment.function_id; "Error while settling queue messages: {}", error);
+                return;
+            }
+        }
+    }
+}
+
+// Consume a batch on one of the deployment's cached isolates, and wait
+// for the promises passed to `ctx.waitUntil()`. Messages are kept until
+// settled, so the whole batch can always be sent again to a new isolate.
+// Returns the messages that were explicitly acknowledged or retried
+async fn execute(
+    context: &ServerlessContext,
+    deployment: Arc<Deployment>,
+    queue: &Queue,
+    messages: &[QueueMessage],
+) -> (RunResult, HashMap<String, QueueOutcome>) {
+    let mut outcomes_receiver = None;
+
+    let run_result = dispatch_with_retry(context, deployment, "queue batch", |sender| {
+        let (outcomes_sender, receiver) = flume::unbounded();
+        outcomes_receiver = Some(receiver);
+
+        IsolateEvent::Queue(IsolateQueue {
+            queue: queue.name().to_string(),
+            messages: messages.to_vec(),
+            outcomes: outcomes_sender,
+            sender,
+        })
+    })
+    .await;
+
+    // The last outcome of a message wins
+    let outcomes = outcomes_receiver
+        .map(|receiver| receiver.drain().collect())
+        .unwrap_or_default();
+
+    (run_result, outcomes)
+}
@@ -2,7 +2,12 @@ use crate::{
     clickhouse::{LogRow, RequestRow},
     cronjob::Cronjob,
     deployments::{cache::run_cache_clear_task, pubsub::listen_pub_sub, Deployments},
-    get_region, SNAPSHOT_BLOB,
+    get_cache_store_options, get_database, get_fetch_policy, get_kv_namespace, get_queue,
+    get_region, get_wait_until_timeout, get_websocket_options,
+    pool::{IsolatePool, PoolOptions},
+    queue::run_queue_consumer,
+    service_bindings::{ServiceBinding, ServiceBindingCaller},
+    SNAPSHOT_BLOB,
 };
 use anyhow::Result;
 use clickhouse::{inserter::Inserter, Client};
@@ -10,37 +15,265 @@ use dashmap::DashMap;
 use futures::lock::Mutex;
 use hyper::{
     header::HOST,
-    http::response::Builder,
+    http::{request::Parts, response::Builder},
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server,
 };
+use lagon_runtime_cache::{CacheStorage, CacheStore};
 use lagon_runtime_http::{RunResult, X_LAGON_ID};
 use lagon_runtime_isolate::{
+    fetch_handler::{FetchHandler, ServiceBindingDepth},
     options::{IsolateOptions, Metadata},
+    scheduler::Scheduler,
     Isolate, IsolateEvent, IsolateRequest,
 };
+use lagon_runtime_kv::KvStore;
+use lagon_runtime_queue::QueueStore;
 use lagon_runtime_utils::{
     assets::{find_asset, handle_asset},
-    response::{handle_response, ResponseEvent, FAVICON_URL, PAGE_403, PAGE_404},
-    DEPLOYMENTS_DIR,
+    request::{
+        is_body_limit_reached, is_body_too_large, replayable_body, DEFAULT_MAX_REQUEST_BODY_SIZE,
+    },
+    response::{handle_run_result, ResponseEvent, FAVICON_URL, PAGE_403, PAGE_404, PAGE_413},
+    websocket::{handle_websocket, WebSocketOptions, WebSocketUpgrade},
+    Deployment, DEPLOYMENTS_DIR,
 };
 use lagon_serverless_downloader::Downloader;
 use lagon_serverless_pubsub::PubSubListener;
 use log::{as_debug, error, info, warn};
 use metrics::{decrement_gauge, histogram, increment_counter, increment_gauge};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     convert::Infallible,
     env,
     future::Future,
     net::SocketAddr,
     path::Path,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant, UNIX_EPOCH},
 };
-use tokio::{runtime::Handle, sync::Mutex as TokioMutex};
+use tokio::sync::Mutex as TokioMutex;
+
+pub type Workers = Arc<DashMap<String, Arc<IsolatePool>>>;
+
+// Everything needed to handle a request, shared by the HTTP server
+// and by the service bindings calling another Function in-process
+#[derive(Clone)]
+pub(crate) struct ServerlessContext {
+    pub deployments: Deployments,
+    pub last_requests: Arc<DashMap<String, Instant>>,
+    pub workers: Workers,
+    pub inserters: Arc<Mutex<(Inserter<RequestRow>, Inserter<LogRow>)>>,
+    pub log_sender: flume::Sender<(String, String, Metadata)>,
+    pub max_request_body_size: usize,
+    pub pool_options: PoolOptions,
+    pub scheduler: Arc<Scheduler>,
+    pub kv_store: Arc<dyn KvStore>,
+    pub queue_store: Arc<dyn QueueStore>,
+    pub cache_store: Arc<CacheStore>,
+    pub websocket_options: WebSocketOptions,
+}
 
-pub type Workers = Arc<DashMap<String, flume::Sender<IsolateEvent>>>;
+pub(crate) fn spawn_isolate(
+    context: &ServerlessContext,
+    deployment: Arc<Deployment>,
+    request_id: String,
+) -> flume::Sender<IsolateEvent> {
+    let (sender, receiver) = flume::unbounded();
+    let log_sender = context.log_sender.clone();
+    let service_bindings = deployment
+        .service_bindings
+        .iter()
+        .map(|(name, function_name)| {
+            let service_binding: Arc<dyn FetchHandler> = Arc::new(ServiceBinding::new(
+                context.clone(),
+                deployment.id.clone(),
+                function_name,
+            ));
+
+            (name.clone(), service_binding)
+        })
+        .collect::<HashMap<_, _>>();
+    let kv = get_kv_namespace(&context.kv_store, &deployment);
+    let queue = get_queue(&context.queue_store, &deployment);
+    let cache = CacheStorage::new(Arc::clone(&context.cache_store), deployment.id.clone());
+    let db = get_database(&deployment);
+
+    // When the event loop is completed, that means a) the isolate was terminate due to limits
+    // or b) the isolate was dropped because of cache expiration. In the first case, the isolate
+    // is removed from its pool on the next request, since its receiver is now dropped
+    context.scheduler.spawn(move || {
+        increment_gauge!("lagon_isolates", 1.0, "deployment" => deployment.id.clone(), "function" => deployment.function_id.clone());
+        info!(deployment = deployment.id, function = deployment.function_id, request = request_id; "Creating new isolate");
+
+        let code = deployment.get_code().unwrap_or_else(|error| {
+            error!(deployment = deployment.id, request = request_id; "Error while getting deployment code: {}", error);
+
+            "".into()
+        });
+        let deployment_handle = Arc::clone(&deployment);
+        let options = IsolateOptions::new(code)
+            .source_map(deployment.get_source_map())
+            .code_cache(deployment.get_code_cache())
+            .environment_variables(deployment.environment_variables.clone())
+            .fetch_policy(get_fetch_policy())
+            .service_bindings(service_bindings)
+            .kv(kv)
+            .queue(queue)
+            .cache(Some(cache))
+            .db(Some(db))
+            .memory(deployment.memory)
+            .tick_timeout(Duration::from_millis(deployment.tick_timeout as u64))
+            .total_timeout(Duration::from_millis(
+                deployment.total_timeout as u64,
+            ))
+            .wait_until_timeout(get_wait_until_timeout())
+            .metadata(Some((
+                deployment.id.clone(),
+                deployment.function_id.clone(),
+            )))
+            .on_drop_callback(Box::new(|metadata| {
+                if let Some(metadata) = metadata.as_ref().as_ref() {
+                    let labels = [
+                        ("deployment", metadata.0.clone()),
+                        ("function", metadata.1.clone()),
+                    ];
+
+                    decrement_gauge!("lagon_isolates", 1.0, &labels);
+                    info!(deployment = metadata.0, function = metadata.1; "Dropping isolate");
+                }
+            }))
+            .on_statistics_callback(Box::new(|metadata, statistics| {
+                if let Some(metadata) = metadata.as_ref().as_ref() {
+                    let labels = [
+                        ("deployment", metadata.0.clone()),
+                        ("function", metadata.1.clone()),
+                    ];
+
+                    histogram!(
+                        "lagon_isolate_memory_usage",
+                        statistics as f64,
+                        &labels
+                    );
+                }
+            }))
+            .on_cold_start_callback(Box::new(move |metadata, cold_start| {
+                if let Some(metadata) = metadata.as_ref().as_ref() {
+                    let labels = [
+                        ("deployment", metadata.0.clone()),
+                        ("function", metadata.1.clone()),
+                        ("cache", String::from(match cold_start.cache_hit {
+                            true => "hit",
+                            false => "miss",
+                        })),
+                    ];
+
+                    histogram!(
+                        "lagon_isolate_cold_start_seconds",
+                        cold_start.duration.as_secs_f64(),
+                        &labels
+                    );
+                }
+
+                if let Some(code_cache) = cold_start.code_cache {
+                    if let Err(error) = deployment_handle.write_code_cache(&code_cache) {
+                        error!(deployment = deployment_handle.id; "Error while writing code cache: {}", error);
+                    }
+                }
+            }))
+            .log_sender(log_sender)
+            .snapshot_blob(SNAPSHOT_BLOB);
+
+        Isolate::new(options, receiver)
+    });
+
+    sender
+}
+
+// Run an event on one of the deployment's cached isolates, and send it again to
+// a new isolate if the first one is terminated because of another request, so
+// the events created by `make_event` must be safe to send twice. Waits for the
+// promises passed to `ctx.waitUntil()` before returning the result
+pub(crate) async fn dispatch_with_retry<F>(
+    context: &ServerlessContext,
+    deployment: Arc<Deployment>,
+    event_name: &str,
+    mut make_event: F,
+) -> RunResult
+where
+    F: FnMut(flume::Sender<RunResult>) -> IsolateEvent,
+{
+    context
+        .last_requests
+        .insert(deployment.id.clone(), Instant::now());
+
+    let pool = Arc::clone(
+        &context
+            .workers
+            .entry(deployment.id.clone())
+            .or_insert_with(|| Arc::new(IsolatePool::new(context.pool_options))),
+    );
+
+    let spawn = || spawn_isolate(context, Arc::clone(&deployment), String::new());
+    let mut retried = false;
+
+    loop {
+        let (isolate_sender, guard) = pool.dispatch(&spawn);
+        let (sender, receiver) = flume::unbounded();
+
+        isolate_sender
+            .send_async(make_event(sender))
+            .await
+            .unwrap_or(());
+
+        let run_result = receiver
+            .recv_async()
+            .await
+            .unwrap_or(RunResult::Error("Isolate didn't send a response".into()));
+
+        if matches!(run_result, RunResult::Retry) && !retried {
+            increment_counter!("lagon_isolate_retries", "deployment" => deployment.id.clone(), "function" => deployment.function_id.clone());
+            info!(deployment = deployment.id, function = deployment.function_id; "Retrying {} on a new isolate", event_name);
+
+            pool.evict(&isolate_sender);
+            drop(guard);
+            retried = true;
+
+            continue;
+        }
+
+        // The isolate drops the sender once the promises passed
+        // to `ctx.waitUntil()` are settled, or timed out. Until
+        // then, the event stays in flight so the isolate
+        // isn't evicted from the cache
+        while receiver.recv_async().await.is_ok() {}
+
+        drop(guard);
+
+        return run_result;
+    }
+}
+
+// Parts aren't cloneable because of their extensions, where
+// we only need the service binding depth of the request
+fn clone_parts(parts: &Parts) -> Parts {
+    let mut request = Request::builder()
+        .method(parts.method.clone())
+        .uri(parts.uri.clone())
+        .version(parts.version)
+        .body(())
+        .unwrap();
+    *request.headers_mut() = parts.headers.clone();
+
+    if let Some(depth) = parts.extensions.get::<ServiceBindingDepth>() {
+        request.extensions_mut().insert(*depth);
+    }
+
+    request.into_parts().0
+}
 
 async fn handle_error(
     result: RunResult,
@@ -66,6 +299,12 @@ async fn handle_error(
 
             ("warn", message.into())
         }
+        RunResult::Retry => {
+            let message = "Function execution interrupted by another request";
+            warn!(deployment = deployment_id, function = function_id, request = request_id; "{}", message);
+
+            ("warn", message.into())
+        }
         RunResult::Error(error) => {
             increment_counter!("lagon_isolate_errors", "deployment" => deployment_id.clone(), "function" => function_id.clone());
 
@@ -95,14 +334,27 @@ async fn handle_error(
     }
 }
 
-async fn handle_request(
-    req: Request<Body>,
-    deployments: Deployments,
-    last_requests: Arc<DashMap<String, Instant>>,
-    workers: Workers,
-    inserters: Arc<Mutex<(Inserter<RequestRow>, Inserter<LogRow>)>>,
-    log_sender: flume::Sender<(String, String, Metadata)>,
+pub(crate) async fn handle_request(
+    mut req: Request<Body>,
+    context: ServerlessContext,
 ) -> Result<Response<Body>> {
+    let ServerlessContext {
+        deployments,
+        last_requests,
+        workers,
+        inserters,
+        max_request_body_size,
+        pool_options,
+        ..
+    } = context.clone();
+
+    // Requests from a service binding are recorded
+    // separately, with the deployment that called them
+    let caller_deployment_id = req
+        .extensions()
+        .get::<ServiceBindingCaller>()
+        .map_or_else(String::new, |caller| caller.0.clone());
+
     let request_id = match req.headers().get(X_LAGON_ID) {
         Some(x_lagon_id) => x_lagon_id.to_str().unwrap_or("").to_string(),
         None => String::new(),
@@ -135,7 +387,7 @@ async fn handle_request(
         }
     };
 
-    if deployment.cron.is_some() {
+    if deployment.is_cron() {
         increment_counter!(
             "lagon_ignored_requests",
             "reason" => "Cron",
@@ -146,8 +398,14 @@ async fn handle_request(
         return Ok(Response::builder().status(403).body(PAGE_403.into())?);
     }
 
-    let (sender, receiver) = flume::unbounded();
-    let mut bytes_in = 0;
+    let (sender, mut receiver) = flume::unbounded();
+    let bytes_in = Arc::new(AtomicUsize::new(0));
+    let mut pool_guard = None;
+    let mut first_result = None;
+    // The isolate that handled the request, which receives
+    // the client's messages if it accepts a WebSocket
+    let mut handler_sender = None;
+    let websocket_upgrade = WebSocketUpgrade::from_request(&mut req);
 
     let url = req.uri().path();
 
@@ -179,92 +437,136 @@ async fn handle_request(
         last_requests.insert(deployment.id.clone(), Instant::now());
 
         let (parts, body) = req.into_parts();
-        let body = hyper::body::to_bytes(body).await?;
 
-        bytes_in = body.len() as u32;
+        if is_body_too_large(&parts, max_request_body_size) {
+            increment_counter!(
+                "lagon_ignored_requests",
+                "reason" => "Body too large",
+                "hostname" => hostname.clone(),
+            );
+            warn!(hostname = hostname, request = request_id; "Request body is too large");
+
+            return Ok(Response::builder().status(413).body(PAGE_413.into())?);
+        }
+
+        // The request can safely be sent again to another isolate, when
+        // its isolate is terminated because of another request
+        let replay_parts = clone_parts(&parts);
+
+        // The body is streamed to the isolate, so we can only
+        // know its size once it has been read
+        let (body, body_replay) =
+            replayable_body(body, max_request_body_size, Arc::clone(&bytes_in));
         let request = (parts, body);
 
-        let deployment = Arc::clone(&deployment);
-        let isolate_workers = Arc::clone(&workers);
-        let request_id_handle = request_id.clone();
-
-        let isolate_sender = workers.entry(deployment.id.clone()).or_insert_with(|| {
-            let handle = Handle::current();
-            let (sender, receiver) = flume::unbounded();
-
-            std::thread::Builder::new().name(String::from("isolate-") + deployment.id.as_str()).spawn(move || {
-                handle.block_on(async move {
-                    increment_gauge!("lagon_isolates", 1.0, "deployment" => deployment.id.clone(), "function" => deployment.function_id.clone());
-                    info!(deployment = deployment.id, function = deployment.function_id, request = request_id_handle; "Creating new isolate");
-
-                    let code = deployment.get_code().unwrap_or_else(|error| {
-                        error!(deployment = deployment.id, request = request_id_handle; "Error while getting deployment code: {}", error);
-
-                        "".into()
-                    });
-                    let options = IsolateOptions::new(code)
-                        .environment_variables(deployment.environment_variables.clone())
-                        .memory(deployment.memory)
-                        .tick_timeout(Duration::from_millis(deployment.tick_timeout as u64))
-                        .total_timeout(Duration::from_millis(
-                            deployment.total_timeout as u64,
-                        ))
-                        .metadata(Some((
-                            deployment.id.clone(),
-                            deployment.function_id.clone(),
-                        )))
-                        .on_drop_callback(Box::new(|metadata| {
-                            if let Some(metadata) = metadata.as_ref().as_ref() {
-                                let labels = [
-                                    ("deployment", metadata.0.clone()),
-                                    ("function", metadata.1.clone()),
-                                ];
-
-                                decrement_gauge!("lagon_isolates", 1.0, &labels);
-                                info!(deployment = metadata.0, function = metadata.1; "Dropping isolate");
-                            }
-                        }))
-                        .on_statistics_callback(Box::new(|metadata, statistics| {
-                            if let Some(metadata) = metadata.as_ref().as_ref() {
-                                let labels = [
-                                    ("deployment", metadata.0.clone()),
-                                    ("function", metadata.1.clone()),
-                                ];
-
-                                histogram!(
-                                    "lagon_isolate_memory_usage",
-                                    statistics as f64,
-                                    &labels
-                                );
-                            }
-                        }))
-                        .log_sender(log_sender)
-                        .snapshot_blob(SNAPSHOT_BLOB);
-
-                    let mut isolate = Isolate::new(options, receiver);
-                    isolate.evaluate();
-                    isolate.run_event_loop().await;
-
-                    // When the event loop is completed, that means a) the isolate was terminate due to limits
-                    // or b) the isolate was dropped because of cache expiration. In the first case, the isolate
-                    // isn't removed from the workers map
-                    isolate_workers.remove(&deployment.id);
-                });
-            }).unwrap();
-
-            sender
-        });
+        // Clone the pool so we don't hold a lock on the
+        // workers map while dispatching the request
+        let pool = Arc::clone(
+            &workers
+                .entry(deployment.id.clone())
+                .or_insert_with(|| Arc::new(IsolatePool::new(pool_options))),
+        );
+
+        let spawn = || spawn_isolate(&context, Arc::clone(&deployment), request_id.clone());
+
+        let (isolate_sender, guard) = pool.dispatch(&spawn);
 
         isolate_sender
             .send_async(IsolateEvent::Request(IsolateRequest { request, sender }))
             .await
             .unwrap_or(());
+
+        match receiver.recv_async().await? {
+            RunResult::Retry => {
+                // The isolate has been terminated, make sure
+                // we don't dispatch the request to it again
+                pool.evict(&isolate_sender);
+                drop(guard);
+
+                // Bodies that were partially read and are too large to be kept can't
+                // be sent again, the client gets a 503 response and can retry by itself
+                match body_replay.body() {
+                    Some(body) => {
+                        increment_counter!("lagon_isolate_retries", "deployment" => deployment.id.clone(), "function" => deployment.function_id.clone());
+                        info!(deployment = deployment.id, function = deployment.function_id, request = request_id; "Retrying request on a new isolate");
+
+                        let (sender, retry_receiver) = flume::unbounded();
+                        let (isolate_sender, guard) = pool.dispatch(&spawn);
+                        pool_guard = Some(guard);
+                        handler_sender = Some(isolate_sender.clone());
+                        receiver = retry_receiver;
+
+                        isolate_sender
+                            .send_async(IsolateEvent::Request(IsolateRequest {
+                                request: (replay_parts, body),
+                                sender,
+                            }))
+                            .await
+                            .unwrap_or(());
+                    }
+                    None => first_result = Some(RunResult::Retry),
+                }
+            }
+            run_result => {
+                pool_guard = Some(guard);
+                handler_sender = Some(isolate_sender);
+                first_result = Some(run_result);
+            }
+        }
     }
 
+    let run_result = match first_result {
+        Some(run_result) => run_result,
+        None => receiver.recv_async().await?,
+    };
+
+    if matches!(run_result, RunResult::Error(_))
+        && is_body_limit_reached(&bytes_in, max_request_body_size)
+    {
+        increment_counter!(
+            "lagon_ignored_requests",
+            "reason" => "Body too large",
+            "hostname" => hostname.clone(),
+        );
+        warn!(hostname = hostname, request = request_id; "Request body is too large");
+
+        return Ok(Response::builder().status(413).body(PAGE_413.into())?);
+    }
+
+    let run_result = match (run_result, handler_sender) {
+        (RunResult::WebSocket(response_builder, channel), Some(isolate_sender)) => {
+            let id = channel.id;
+
+            info!(deployment = deployment.id, function = deployment.function_id, request = request_id; "Accepting WebSocket");
+
+            return handle_websocket(
+                websocket_upgrade,
+                response_builder,
+                channel,
+                context.websocket_options,
+                move |message| {
+                    // The request stays in flight until the socket is closed,
+                    // so the isolate isn't terminated while the socket is open
+                    let _pool_guard = &pool_guard;
+
+                    isolate_sender
+                        .send(IsolateEvent::WebSocket(id, message))
+                        .unwrap_or(());
+                },
+            );
+        }
+        (run_result, _) => run_result,
+    };
+
     let deployment_handle = Arc::clone(&deployment);
 
-    handle_response(receiver, deployment, move |event| {
+    handle_run_result(run_result, receiver, deployment, move |event| {
+        // The request is now completed, which allows
+        // the pool to dispatch new requests to this isolate
+        drop(pool_guard);
+
         let inserters = Arc::clone(&inserters);
+        let bytes_in = Arc::clone(&bytes_in);
 
         async move {
             match event {
@@ -279,9 +581,10 @@ async fn handle_request(
                             function_id: deployment_handle.function_id.clone(),
                             deployment_id: deployment_handle.id.clone(),
                             region: get_region().clone(),
-                            bytes_in,
+                            bytes_in: bytes_in.load(Ordering::Relaxed) as u32,
                             bytes_out: bytes as u32,
                             cpu_time_micros,
+                            caller_deployment_id,
                             timestamp,
                         })
                         .await
@@ -321,6 +624,8 @@ pub async fn start<D, P>(
     downloader: Arc<D>,
     pubsub: P,
     client: Client,
+    kv_store: Arc<dyn KvStore>,
+    queue_store: Arc<dyn QueueStore>,
 ) -> Result<impl Future<Output = ()> + Send>
 where
     D: Downloader + Send + Sync + 'static,
@@ -328,7 +633,28 @@ where
 {
     let last_requests = Arc::new(DashMap::new());
     let workers = Arc::new(DashMap::new());
+    let max_request_body_size = env::var("LAGON_MAX_REQUEST_BODY_SIZE")
+        .map(|size| {
+            size.parse()
+                .expect("LAGON_MAX_REQUEST_BODY_SIZE is not a valid number")
+        })
+        .unwrap_or(DEFAULT_MAX_REQUEST_BODY_SIZE);
+    let pool_options = PoolOptions::from_env();
+    // All the isolates run on the same fixed number of threads,
+    // no matter how many deployments and isolates there are
+    let scheduler = Arc::new(env::var("LAGON_WORKER_THREADS").map_or_else(
+        |_| Scheduler::with_available_parallelism(),
+        |threads| {
+            Scheduler::new(
+                threads
+                    .parse()
+                    .expect("LAGON_WORKER_THREADS is not a valid number"),
+            )
+        },
+    ));
     let pubsub = Arc::new(TokioMutex::new(pubsub));
+    // Shared by all the deployments, but each one gets its own partition
+    let cache_store = Arc::new(CacheStore::new(get_cache_store_options()));
 
     let insertion_interval = Duration::from_secs(1);
     let inserters = Arc::new(Mutex::new((
@@ -341,9 +667,24 @@ where
     )));
 
     let (log_sender, log_receiver) = flume::unbounded::<(String, String, Metadata)>();
-    let cronjob = Arc::new(TokioMutex::new(
-        Cronjob::new(log_sender.clone(), Arc::clone(&inserters)).await,
-    ));
+
+    let context = ServerlessContext {
+        deployments: Arc::clone(&deployments),
+        last_requests: Arc::clone(&last_requests),
+        workers: Arc::clone(&workers),
+        inserters: Arc::clone(&inserters),
+        log_sender,
+        max_request_body_size,
+        pool_options,
+        scheduler,
+        kv_store,
+        queue_store,
+        cache_store: Arc::clone(&cache_store),
+        websocket_options: get_websocket_options(),
+    };
+
+    // Cron executions go through the same isolate pools as HTTP requests
+    let cronjob = Arc::new(TokioMutex::new(Cronjob::new(context.clone(), client).await));
 
     let mut cron_deployments = HashSet::new();
 
@@ -374,9 +715,11 @@ where
         Arc::clone(&deployments),
         Arc::clone(&workers),
         Arc::clone(&cronjob),
+        Arc::clone(&cache_store),
         pubsub,
     );
     run_cache_clear_task(Arc::clone(&last_requests), Arc::clone(&workers));
+    run_queue_consumer(context.clone());
 
     let inserters_handle = Arc::clone(&inserters);
     tokio::spawn(async move {
@@ -422,26 +765,14 @@ where
         }
     });
 
-    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
-        let deployments = Arc::clone(&deployments);
-        let last_requests = Arc::clone(&last_requests);
-        let workers = Arc::clone(&workers);
-        let inserters = Arc::clone(&inserters);
-        let log_sender = log_sender.clone();
+    let server =
+        Server::bind(&addr).serve(make_service_fn(move |_| {
+            let context = context.clone();
 
-        async move {
-            Ok::<_, Infallible>(service_fn(move |req| {
-                handle_request(
-                    req,
-                    Arc::clone(&deployments),
-                    Arc::clone(&last_requests),
-                    Arc::clone(&workers),
-                    Arc::clone(&inserters),
-                    log_sender.clone(),
-                )
-            }))
-        }
-    }));
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| handle_request(req, context.clone())))
+            }
+        }));
 
     Ok(async move {
         if let Err(error) = server.await {
@@ -0,0 +1,72 @@
+use crate::{
+    get_root_domain,
+    serverless::{handle_request, ServerlessContext},
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hyper::{header::HOST, Body, Request, Response};
+use lagon_runtime_isolate::fetch_handler::{FetchHandler, ServiceBindingDepth};
+
+// How many service bindings a request can go through, to
+// prevent Functions from calling each other in a loop
+const MAX_SERVICE_BINDING_DEPTH: u32 = 8;
+
+// Request extension containing the deployment that called
+// a Function through one of its service bindings
+#[derive(Debug, Clone)]
+pub struct ServiceBindingCaller(pub String);
+
+// Sends requests to another Function's production deployment, by routing them
+// directly to its isolates instead of going through the network
+pub struct ServiceBinding {
+    context: ServerlessContext,
+    caller_deployment_id: String,
+    hostname: String,
+}
+
+impl ServiceBinding {
+    pub(crate) fn new(
+        context: ServerlessContext,
+        caller_deployment_id: String,
+        function_name: &str,
+    ) -> Self {
+        Self {
+            context,
+            caller_deployment_id,
+            hostname: format!("{}.{}", function_name, get_root_domain()),
+        }
+    }
+}
+
+#[async_trait]
+impl FetchHandler for ServiceBinding {
+    async fn fetch(&self, request: Request<Body>) -> Result<Response<Body>> {
+        let (mut parts, body) = request.into_parts();
+
+        let depth = parts
+            .extensions
+            .get::<ServiceBindingDepth>()
+            .map_or(0, |depth| depth.0);
+
+        if depth > MAX_SERVICE_BINDING_DEPTH {
+            return Err(anyhow!(
+                "Service bindings can only be nested {} times",
+                MAX_SERVICE_BINDING_DEPTH
+            ));
+        }
+
+        // Requests received by the HTTP server only contain the path and query,
+        // and the hostname is used to find the deployment to call
+        parts.uri = parts
+            .uri
+            .path_and_query()
+            .map_or("/", |path_and_query| path_and_query.as_str())
+            .parse()?;
+        parts.headers.insert(HOST, self.hostname.parse()?);
+        parts
+            .extensions
+            .insert(ServiceBindingCaller(self.caller_deployment_id.clone()));
+
+        handle_request(Request::from_parts(parts, body), self.context.clone()).await
+    }
+}
@@ -1,12 +1,14 @@
 use anyhow::Result;
 use dashmap::DashMap;
-use lagon_runtime_utils::Deployment;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::MemoryQueueStore;
+use lagon_runtime_utils::{CronOptions, Deployment};
 use lagon_serverless::serverless::start;
 use lagon_serverless_downloader::FakeDownloader;
 use lagon_serverless_pubsub::FakePubSub;
 use serial_test::serial;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -30,7 +32,9 @@ async fn html_assets() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -39,6 +43,8 @@ async fn html_assets() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -85,7 +91,9 @@ async fn assets_nested() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -94,6 +102,8 @@ async fn assets_nested() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -137,7 +147,9 @@ async fn set_content_type() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -146,6 +158,8 @@ async fn set_content_type() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -1,12 +1,15 @@
 use anyhow::Result;
 use dashmap::DashMap;
-use lagon_runtime_utils::Deployment;
-use lagon_serverless::serverless::start;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::MemoryQueueStore;
+use lagon_runtime_utils::{CronOptions, Deployment, CRON_MAX_RETRIES};
+use lagon_serverless::{deployments::parse_cron_options, serverless::start};
 use lagon_serverless_downloader::FakeDownloader;
 use lagon_serverless_pubsub::FakePubSub;
+use serde_json::json;
 use serial_test::serial;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -30,7 +33,9 @@ async fn simple() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -39,6 +44,8 @@ async fn simple() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -66,7 +73,9 @@ async fn custom_domains() -> Result<()> {
         tick_timeout: 1000,
         total_timeout: 1000,
         is_production: true,
-        cron: None,
+        crons: BTreeMap::new(),
+        cron_options: CronOptions::default(),
+        service_bindings: HashMap::new(),
     });
     deployments.insert("127.0.0.1:4000".into(), Arc::clone(&deployment));
     deployments.insert("custom.domain".into(), Arc::clone(&deployment));
@@ -76,6 +85,8 @@ async fn custom_domains() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -114,7 +125,9 @@ async fn reuse_isolate() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -123,6 +136,8 @@ async fn reuse_isolate() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -154,7 +169,9 @@ async fn reuse_isolate_across_domains() -> Result<()> {
         tick_timeout: 1000,
         total_timeout: 1000,
         is_production: true,
-        cron: None,
+        crons: BTreeMap::new(),
+        cron_options: CronOptions::default(),
+        service_bindings: HashMap::new(),
     });
     deployments.insert("127.0.0.1:4000".into(), Arc::clone(&deployment));
     deployments.insert("another.domain".into(), deployment);
@@ -164,6 +181,8 @@ async fn reuse_isolate_across_domains() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -183,3 +202,12 @@ async fn reuse_isolate_across_domains() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn cron_options_max_retries_limited() {
+    let options = parse_cron_options(&json!({ "maxRetries": 3 }));
+    assert_eq!(options.max_retries, 3);
+
+    let options = parse_cron_options(&json!({ "maxRetries": u64::MAX }));
+    assert_eq!(options.max_retries, CRON_MAX_RETRIES);
+}
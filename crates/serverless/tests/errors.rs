@@ -1,15 +1,17 @@
 use anyhow::Result;
 use dashmap::DashMap;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::MemoryQueueStore;
 use lagon_runtime_utils::{
     response::{PAGE_403, PAGE_404, PAGE_500, PAGE_502},
-    Deployment,
+    CronOptions, Deployment, DEFAULT_CRON_NAME,
 };
 use lagon_serverless::serverless::start;
 use lagon_serverless_downloader::FakeDownloader;
 use lagon_serverless_pubsub::FakePubSub;
 use serial_test::serial;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -25,6 +27,8 @@ async fn return_404_no_deployment_found() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -54,7 +58,9 @@ async fn return_403_cron_deployment() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: Some("".into()),
+            crons: BTreeMap::from([(DEFAULT_CRON_NAME.into(), "".into())]),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -63,6 +69,8 @@ async fn return_403_cron_deployment() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -92,7 +100,9 @@ async fn return_500_unknown_code() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -101,6 +111,8 @@ async fn return_500_unknown_code() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -130,7 +142,9 @@ async fn return_502_timeout_execution() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -139,6 +153,8 @@ async fn return_502_timeout_execution() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -168,7 +184,9 @@ async fn return_502_timeout_init() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -177,6 +195,8 @@ async fn return_502_timeout_init() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -206,7 +226,9 @@ async fn return_500_code_invalid() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -215,6 +237,8 @@ async fn return_500_code_invalid() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -244,7 +268,9 @@ async fn return_500_throw_error() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -253,6 +279,8 @@ async fn return_500_throw_error() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
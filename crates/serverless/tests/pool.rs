@@ -0,0 +1,126 @@
+use lagon_runtime_isolate::IsolateEvent;
+use lagon_serverless::pool::{IsolatePool, PoolOptions};
+use std::{cell::RefCell, time::Duration};
+
+// Create a fake isolate, keeping its receiver
+// so the sender isn't disconnected
+fn spawn(
+    receivers: &RefCell<Vec<flume::Receiver<IsolateEvent>>>,
+) -> impl Fn() -> flume::Sender<IsolateEvent> + '_ {
+    move || {
+        let (sender, receiver) = flume::unbounded();
+        receivers.borrow_mut().push(receiver);
+        sender
+    }
+}
+
+#[test]
+fn reuses_idle_isolate() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions {
+        min_isolates: 1,
+        max_isolates: 4,
+    });
+
+    let (first, guard) = pool.dispatch(spawn(&receivers));
+    drop(guard);
+    let (second, _guard) = pool.dispatch(spawn(&receivers));
+
+    assert!(first.same_channel(&second));
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn scales_up_to_max_when_busy() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions {
+        min_isolates: 1,
+        max_isolates: 2,
+    });
+
+    let (first, _first_guard) = pool.dispatch(spawn(&receivers));
+    let (second, _second_guard) = pool.dispatch(spawn(&receivers));
+    let (third, _third_guard) = pool.dispatch(spawn(&receivers));
+
+    assert!(!first.same_channel(&second));
+    assert!(third.same_channel(&first) || third.same_channel(&second));
+    assert_eq!(pool.len(), 2);
+}
+
+#[test]
+fn dispatches_to_least_loaded() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions {
+        min_isolates: 2,
+        max_isolates: 2,
+    });
+
+    let (first, first_guard) = pool.dispatch(spawn(&receivers));
+    let (second, _second_guard) = pool.dispatch(spawn(&receivers));
+    assert!(!first.same_channel(&second));
+
+    // The first isolate doesn't have any request in
+    // flight anymore, while the second one still has one
+    drop(first_guard);
+    let (third, _third_guard) = pool.dispatch(spawn(&receivers));
+
+    assert!(third.same_channel(&first));
+}
+
+#[test]
+fn removes_terminated_isolates() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions::default());
+
+    let (first, guard) = pool.dispatch(spawn(&receivers));
+    drop(guard);
+
+    // Simulate an isolate that was terminated
+    receivers.borrow_mut().clear();
+
+    let (second, _guard) = pool.dispatch(spawn(&receivers));
+
+    assert!(!first.same_channel(&second));
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn scales_down_to_min() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions {
+        min_isolates: 1,
+        max_isolates: 3,
+    });
+
+    let guards = (0..3)
+        .map(|_| pool.dispatch(spawn(&receivers)).1)
+        .collect::<Vec<_>>();
+    assert_eq!(pool.len(), 3);
+
+    // Isolates with requests in flight are never removed
+    assert!(pool.scale_down(Duration::ZERO).is_empty());
+
+    drop(guards);
+    std::thread::sleep(Duration::from_millis(10));
+
+    assert_eq!(pool.scale_down(Duration::ZERO).len(), 2);
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn evicts_isolate() {
+    let receivers = RefCell::new(Vec::new());
+    let pool = IsolatePool::new(PoolOptions::default());
+
+    let (first, guard) = pool.dispatch(spawn(&receivers));
+    drop(guard);
+
+    // The isolate was terminated, but its receiver isn't dropped yet
+    pool.evict(&first);
+    assert!(pool.is_empty());
+
+    let (second, _guard) = pool.dispatch(spawn(&receivers));
+
+    assert!(!first.same_channel(&second));
+    assert_eq!(pool.len(), 1);
+}
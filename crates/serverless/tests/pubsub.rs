@@ -1,5 +1,7 @@
 use anyhow::Result;
 use dashmap::DashMap;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::MemoryQueueStore;
 use lagon_runtime_utils::response::{PAGE_403, PAGE_404};
 use lagon_serverless::serverless::start;
 use lagon_serverless_downloader::FakeDownloader;
@@ -21,6 +23,8 @@ async fn deploy_undeploy() -> Result<()> {
         Arc::new(FakeDownloader),
         pubsub,
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -94,6 +98,8 @@ async fn assign_correct_domains_prod() -> Result<()> {
         Arc::new(FakeDownloader),
         pubsub,
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -155,6 +161,8 @@ async fn assign_correct_domains_dev() -> Result<()> {
         Arc::new(FakeDownloader),
         pubsub,
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -216,6 +224,8 @@ async fn skip_cron_not_same_region() -> Result<()> {
         Arc::new(FakeDownloader),
         pubsub,
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -248,6 +258,112 @@ async fn skip_cron_not_same_region() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[serial]
+async fn skip_cron_schedules_not_same_region() -> Result<()> {
+    let client = utils::setup();
+    let pubsub = FakePubSub::default();
+    let tx = pubsub.get_tx();
+    let serverless = start(
+        Arc::new(DashMap::new()),
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        pubsub,
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    tx.send_async(PubSubMessage::new(
+        PubSubMessageKind::Deploy,
+        r#"{
+    "functionId": "function_id",
+    "functionName": "function_name",
+    "deploymentId": "simple",
+    "domains": ["127.0.0.1:4000"],
+    "memory": 128,
+    "tickTimeout": 1000,
+    "totalTimeout": 1000,
+    "cron": null,
+    "cronSchedules": { "nightly": "0 0 * * *" },
+    "cronRegion": "unknown",
+    "env": {},
+    "isProduction": true,
+    "assets": []
+}"#
+        .into(),
+    ))
+    .await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.text().await?, PAGE_404);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn ignore_trigger_unknown_deployment() -> Result<()> {
+    let client = utils::setup();
+    let pubsub = FakePubSub::default();
+    let tx = pubsub.get_tx();
+    let serverless = start(
+        Arc::new(DashMap::new()),
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        pubsub,
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    tx.send_async(PubSubMessage::new(
+        PubSubMessageKind::Trigger,
+        r#"{
+    "functionId": "function_id",
+    "deploymentId": "unknown",
+    "cronRegion": "local",
+    "schedule": "default"
+}"#
+        .into(),
+    ))
+    .await?;
+
+    // The listener keeps handling messages after a failed trigger
+    tx.send_async(PubSubMessage::new(
+        PubSubMessageKind::Deploy,
+        r#"{
+    "functionId": "function_id",
+    "functionName": "function_name",
+    "deploymentId": "simple",
+    "domains": ["127.0.0.1:4000"],
+    "memory": 128,
+    "tickTimeout": 1000,
+    "totalTimeout": 1000,
+    "cron": null,
+    "cronRegion": "local",
+    "env": {},
+    "isProduction": true,
+    "assets": []
+}"#
+        .into(),
+    ))
+    .await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await?, "Hello world");
+
+    Ok(())
+}
+
 #[tokio::test]
 #[serial]
 async fn warn_cron_direct_access() -> Result<()> {
@@ -260,6 +376,8 @@ async fn warn_cron_direct_access() -> Result<()> {
         Arc::new(FakeDownloader),
         pubsub,
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
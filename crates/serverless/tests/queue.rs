@@ -0,0 +1,91 @@
+use anyhow::Result;
+use dashmap::DashMap;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::{MemoryQueueStore, MAX_ATTEMPTS};
+use lagon_runtime_utils::{CronOptions, Deployment};
+use lagon_serverless::serverless::start;
+use lagon_serverless_downloader::FakeDownloader;
+use lagon_serverless_pubsub::FakePubSub;
+use serial_test::serial;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+mod utils;
+
+async fn start_queue(queue_store: Arc<MemoryQueueStore>) -> Result<()> {
+    let client = utils::setup();
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "queue".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        queue_store,
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn consume_queue_messages() -> Result<()> {
+    start_queue(Arc::new(MemoryQueueStore::default())).await?;
+
+    let response = reqwest::get("http://127.0.0.1:4000?value=hello").await?;
+    assert_eq!(response.text().await?, "Sent");
+
+    // Queues are polled by the consumer every second
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.text().await?, "hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn dead_letter_failed_messages() -> Result<()> {
+    let queue_store = Arc::new(MemoryQueueStore::default());
+    start_queue(Arc::clone(&queue_store)).await?;
+
+    let response = reqwest::get("http://127.0.0.1:4000?value=fail").await?;
+    assert_eq!(response.text().await?, "Sent");
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let dead_letters = queue_store.dead_letters("function_id");
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].body, r#"{"value":"fail"}"#);
+    assert_eq!(dead_letters[0].attempts, MAX_ATTEMPTS);
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.text().await?, "null");
+
+    Ok(())
+}
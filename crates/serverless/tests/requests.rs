@@ -2,13 +2,18 @@ use anyhow::Result;
 use dashmap::DashMap;
 use futures::StreamExt;
 use hyper::body::Bytes;
-use lagon_runtime_utils::Deployment;
+use lagon_runtime_kv::MemoryKvStore;
+use lagon_runtime_queue::MemoryQueueStore;
+use lagon_runtime_utils::{request::MAX_REPLAY_BODY_SIZE, CronOptions, Deployment};
 use lagon_serverless::serverless::start;
 use lagon_serverless_downloader::FakeDownloader;
 use lagon_serverless_pubsub::FakePubSub;
 use serial_test::serial;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    io::{Read, Write},
+    net::TcpStream,
     sync::Arc,
 };
 
@@ -32,7 +37,9 @@ async fn returns_correct_http() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -41,6 +48,8 @@ async fn returns_correct_http() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -73,7 +82,9 @@ async fn returns_correct_path() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -82,6 +93,8 @@ async fn returns_correct_path() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -122,7 +135,9 @@ async fn forwards_headers() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -131,6 +146,8 @@ async fn forwards_headers() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -163,7 +180,9 @@ async fn stream_sequentially() -> Result<()> {
             tick_timeout: 1000,
             total_timeout: 1000,
             is_production: true,
-            cron: None,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
         }),
     );
     let serverless = start(
@@ -172,6 +191,8 @@ async fn stream_sequentially() -> Result<()> {
         Arc::new(FakeDownloader),
         FakePubSub::default(),
         client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
     )
     .await?;
     tokio::spawn(serverless);
@@ -187,3 +208,280 @@ async fn stream_sequentially() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[serial]
+async fn service_binding() -> Result<()> {
+    let client = utils::setup();
+    let root_domain = env::var("LAGON_ROOT_DOMAIN")?;
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "service-binding".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::from([("AUTH".into(), "auth".into())]),
+        }),
+    );
+    deployments.insert(
+        format!("auth.{root_domain}"),
+        Arc::new(Deployment {
+            id: "path-query".into(),
+            function_id: "auth_function_id".into(),
+            function_name: "auth".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await?,
+        format!("https://auth.{root_domain}/login")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn cache() -> Result<()> {
+    let client = utils::setup();
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "cache".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.text().await?, "MISS");
+
+    let response = reqwest::get("http://127.0.0.1:4000").await?;
+    assert_eq!(response.text().await?, "HIT Hello");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn retry_request_with_body() -> Result<()> {
+    let client = utils::setup();
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "retry-body".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 200,
+            total_timeout: 5000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    let request = tokio::spawn(
+        reqwest::Client::new()
+            .post("http://127.0.0.1:4000/echo")
+            .body("Hello world")
+            .send(),
+    );
+
+    // Make sure the first request is in flight on the
+    // isolate before the other one terminates it
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let response = reqwest::get("http://127.0.0.1:4000/loop").await?;
+    assert_eq!(response.status(), 502);
+
+    // The first request is sent again with its body to a new isolate
+    let response = request.await??;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await?, "Hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn retry_request_with_large_body() -> Result<()> {
+    let client = utils::setup();
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "retry-body".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 200,
+            total_timeout: 5000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    tokio::spawn(serverless);
+
+    let request = tokio::spawn(
+        reqwest::Client::new()
+            .post("http://127.0.0.1:4000/echo")
+            .body(vec![b'a'; MAX_REPLAY_BODY_SIZE + 1])
+            .send(),
+    );
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let response = reqwest::get("http://127.0.0.1:4000/loop").await?;
+    assert_eq!(response.status(), 502);
+
+    // The body has been read by the first isolate and is too large to be kept
+    let response = request.await??;
+    assert_eq!(response.status(), 503);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "1");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn return_413_chunked_body_too_large() -> Result<()> {
+    let client = utils::setup();
+    let deployments = Arc::new(DashMap::new());
+    deployments.insert(
+        "127.0.0.1:4000".into(),
+        Arc::new(Deployment {
+            id: "body".into(),
+            function_id: "function_id".into(),
+            function_name: "function_name".into(),
+            domains: HashSet::new(),
+            assets: HashSet::new(),
+            environment_variables: HashMap::new(),
+            memory: 128,
+            tick_timeout: 1000,
+            total_timeout: 1000,
+            is_production: true,
+            crons: BTreeMap::new(),
+            cron_options: CronOptions::default(),
+            service_bindings: HashMap::new(),
+        }),
+    );
+    env::set_var("LAGON_MAX_REQUEST_BODY_SIZE", "10");
+    let serverless = start(
+        deployments,
+        "127.0.0.1:4000".parse().unwrap(),
+        Arc::new(FakeDownloader),
+        FakePubSub::default(),
+        client,
+        Arc::new(MemoryKvStore::default()),
+        Arc::new(MemoryQueueStore::default()),
+    )
+    .await?;
+    env::remove_var("LAGON_MAX_REQUEST_BODY_SIZE");
+    tokio::spawn(serverless);
+
+    // Chunked requests don't have a Content-Length header,
+    // so their size is only known once their body is read
+    let response = tokio::task::spawn_blocking(|| -> Result<String> {
+        let mut stream = TcpStream::connect("127.0.0.1:4000")?;
+        stream.write_all(
+            b"POST / HTTP/1.1\r\nHost: 127.0.0.1:4000\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n8\r\nHello wo\r\n8\r\nrld, Hel\r\n0\r\n\r\n",
+        )?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        Ok(response)
+    })
+    .await??;
+
+    assert!(response.starts_with("HTTP/1.1 413"));
+
+    Ok(())
+}
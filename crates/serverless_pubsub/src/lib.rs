@@ -13,6 +13,8 @@ pub enum PubSubMessageKind {
     Deploy,
     Undeploy,
     Promote,
+    // Run a cron schedule of a deployment now
+    Trigger,
     Unknown,
 }
 
@@ -33,6 +35,7 @@ impl From<&str> for PubSubMessageKind {
             "deploy" => Self::Deploy,
             "undeploy" => Self::Undeploy,
             "promote" => Self::Promote,
+            "trigger" => Self::Trigger,
             _ => Self::Unknown,
         }
     }
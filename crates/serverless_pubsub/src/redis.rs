@@ -30,6 +30,7 @@ impl PubSubListener for RedisPubSub {
             pubsub.subscribe("deploy")?;
             pubsub.subscribe("undeploy")?;
             pubsub.subscribe("promote")?;
+            pubsub.subscribe("trigger")?;
 
             loop {
                 let msg = pubsub.get_message()?;
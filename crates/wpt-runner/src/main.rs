@@ -155,9 +155,7 @@ export function handler() {{
     });
 
     let (request_tx, request_rx) = flume::unbounded();
-    let (parts, body) = Request::new(Body::empty()).into_parts();
-    let body = hyper::body::to_bytes(body).await.unwrap();
-    let request = (parts, body);
+    let request = Request::new(Body::empty()).into_parts();
 
     tx.send_async(IsolateEvent::Request(IsolateRequest {
         request,
@@ -7,6 +7,7 @@ import { TRPCError } from '@trpc/server';
 import { envStringToObject } from 'lib/utils';
 import { checkCanQueryFunction } from './functions';
 import { Plan } from 'lib/plans';
+import { Prisma } from '@prisma/client';
 
 export async function createDeployment(
   func: {
@@ -73,6 +74,13 @@ export async function removeDeployment(
         Key: `${deployment.id}.js`,
       }),
     ),
+    // Deleting an object that doesn't exist doesn't fail
+    s3.send(
+      new DeleteObjectCommand({
+        Bucket: process.env.S3_BUCKET,
+        Key: `${deployment.id}.js.map`,
+      }),
+    ),
   ];
 
   if (Array.isArray(deployment.assets) && deployment.assets.length > 0) {
@@ -158,6 +166,9 @@ export async function promoteProductionDeployment(functionId: string, newDeploym
       totalTimeout: true,
       cron: true,
       cronRegion: true,
+      serviceBindings: true,
+      cronOptions: true,
+      cronSchedules: true,
       env: {
         select: {
           key: true,
@@ -204,6 +215,9 @@ export async function promoteProductionDeployment(functionId: string, newDeploym
       totalTimeout: func.totalTimeout,
       cron: func.cron,
       cronRegion: func.cronRegion,
+      serviceBindings: func.serviceBindings,
+      cronOptions: func.cronOptions,
+      cronSchedules: func.cronSchedules,
       env: envStringToObject(func.env),
       isProduction: true,
       assets: deployment.assets,
@@ -221,6 +235,9 @@ export async function redeploy(
     totalTimeout: number;
     cron: string | null;
     cronRegion: string;
+    serviceBindings: Prisma.JsonValue;
+    cronOptions: Prisma.JsonValue;
+    cronSchedules: Prisma.JsonValue;
     env: { key: string; value: string }[];
   },
   deployment: { id: string; isProduction: boolean; assets: string[] },
@@ -256,6 +273,9 @@ export async function redeploy(
       totalTimeout: func.totalTimeout,
       cron: func.cron,
       cronRegion: func.cronRegion,
+      serviceBindings: func.serviceBindings,
+      cronOptions: func.cronOptions,
+      cronSchedules: func.cronSchedules,
       env: envStringToObject(func.env),
       isProduction: deployment.isProduction,
       assets: deployment.assets,
@@ -263,6 +283,18 @@ export async function redeploy(
   );
 }
 
+export async function triggerCron(func: { id: string; cronRegion: string }, deploymentId: string, schedule: string) {
+  await redis.publish(
+    'trigger',
+    JSON.stringify({
+      functionId: func.id,
+      deploymentId,
+      cronRegion: func.cronRegion,
+      schedule,
+    }),
+  );
+}
+
 export async function removeFunction(func: {
   id: string;
   createdAt: Date;
@@ -345,6 +377,9 @@ export async function upgradeFunctions({ plan, organizationId }: { plan: Plan; o
       totalTimeout: true,
       cron: true,
       cronRegion: true,
+      serviceBindings: true,
+      cronOptions: true,
+      cronSchedules: true,
       env: {
         select: {
           key: true,
@@ -18,6 +18,12 @@ export const ENVIRONMENT_VARIABLES_PER_FUNCTION = 100;
 
 export const CUSTOM_DOMAINS_PER_FUNCTION = 10;
 
+export const CRON_MAX_RETRIES = 5;
+export const CRON_SCHEDULES_PER_FUNCTION = 10;
+// The name of the schedule using the Function's main Cron expression
+export const DEFAULT_CRON_NAME = 'default';
+export const CRON_SCHEDULE_NAME_REGEX = /^[a-z0-9-]+$/;
+
 export const PRESIGNED_URL_EXPIRES_SECONDS = 60 * 60; // 1 hour
 
 export const FUNCTION_NAME_REGEX = /^[a-z0-9-]+$/;
@@ -1,5 +1,5 @@
 import { FieldValidator } from 'final-form';
-import { FUNCTION_NAME_REGEX } from 'lib/constants';
+import { CRON_SCHEDULE_NAME_REGEX, DEFAULT_CRON_NAME, FUNCTION_NAME_REGEX } from 'lib/constants';
 import cronstrue from 'cronstrue';
 
 export const requiredValidator: FieldValidator<string | number> = value => {
@@ -32,6 +32,20 @@ export const functionNameValidator: FieldValidator<string | number> = value => {
   return 'Field must be a string';
 };
 
+export const cronScheduleNameValidator: FieldValidator<string | number> = value => {
+  if (typeof value === 'string' && value) {
+    if (value === DEFAULT_CRON_NAME) {
+      return `Schedule name can't be ${DEFAULT_CRON_NAME}`;
+    }
+
+    return CRON_SCHEDULE_NAME_REGEX.test(value)
+      ? undefined
+      : 'Schedule name must only contain lowercase alphanumeric characters and dashes';
+  }
+
+  return undefined;
+};
+
 export const domainNameValidator: FieldValidator<string | number> = value => {
   if (typeof value === 'string') {
     return /^[a-z0-9-]+(\.[a-z0-9-]{1,63})+$/.test(value) ? undefined : 'Field must be a domain or subdomain';
@@ -52,6 +66,18 @@ export const cronValidator: FieldValidator<string | number> = value => {
   return undefined;
 };
 
+export const timezoneValidator: FieldValidator<string | number> = value => {
+  if (typeof value === 'string' && value) {
+    try {
+      new Intl.DateTimeFormat(undefined, { timeZone: value });
+    } catch (e) {
+      return 'Field must be an IANA timezone';
+    }
+  }
+
+  return undefined;
+};
+
 export const emailValidator: FieldValidator<string | number> = value => {
   if (typeof value === 'string') {
     return /^[^\s@]+@[^\s@]+\.[^\s@]+$/.test(value) ? undefined : 'Field must be an email address';
@@ -5,14 +5,18 @@ import { getCurrentDomain, getFullDomain } from 'lib/utils';
 import {
   alphaNumUnderscoreValidator,
   composeValidators,
+  cronScheduleNameValidator,
   cronValidator,
   domainNameValidator,
   functionNameValidator,
   maxLengthValidator,
   minLengthValidator,
   requiredValidator,
+  timezoneValidator,
 } from 'lib/form/validators';
 import {
+  CRON_MAX_RETRIES,
+  CRON_SCHEDULES_PER_FUNCTION,
   ENVIRONMENT_VARIABLE_KEY_MAX_LENGTH,
   ENVIRONMENT_VARIABLE_VALUE_MAX_SIZE,
   FUNCTION_NAME_MAX_LENGTH,
@@ -28,6 +32,7 @@ import { Copiable, Link } from '@lagon/ui';
 import { ComponentProps, ReactNode, useEffect, useState } from 'react';
 import { getHumanFriendlyCron } from 'lib/utils';
 import { isInSubnet } from 'is-in-subnet';
+import { CronOptions, CronOverlap, CRON_OVERLAPS } from 'lib/types';
 
 // https://www.cloudflare.com/ips-v4
 const CLOUDFLARE_SUBNETS = [
@@ -317,8 +322,12 @@ const FunctionSettings = ({ func, refetch }: FunctionSettingsProps) => {
         initialValues={{
           cron: func?.cron,
           cronRegion: func?.cronRegion,
+          maxRetries: (func?.cronOptions as CronOptions | null)?.maxRetries ?? 0,
+          overlap: (func?.cronOptions as CronOptions | null)?.overlap ?? 'skip',
+          timezone: (func?.cronOptions as CronOptions | null)?.timezone,
+          schedules: (func?.cronSchedules as Record<string, string> | null) ?? {},
         }}
-        onSubmit={async ({ cron, cronRegion }) => {
+        onSubmit={async ({ cron, cronRegion, maxRetries, overlap, timezone, schedules }) => {
           if (!func) {
             return;
           }
@@ -327,6 +336,12 @@ const FunctionSettings = ({ func, refetch }: FunctionSettingsProps) => {
             functionId: func.id,
             cron: cron || null,
             cronRegion: cronRegion,
+            cronOptions: {
+              maxRetries,
+              overlap,
+              timezone: timezone || undefined,
+            },
+            cronSchedules: Object.keys(schedules).length > 0 ? schedules : null,
           });
         }}
         onSubmitSuccess={async () => {
@@ -378,6 +393,102 @@ const FunctionSettings = ({ func, refetch }: FunctionSettingsProps) => {
                 </Menu>
               </div>
             </div>
+            <div className="flex flex-col items-start justify-between gap-6 md:flex-row md:gap-0">
+              <div className="flex flex-1 flex-col items-start gap-1">
+                <Text size="lg">{t('cron.retries')}</Text>
+                <Menu>
+                  <Menu.Button>
+                    <Button>{values.maxRetries}</Button>
+                  </Menu.Button>
+                  <Menu.Items>
+                    {Array.from({ length: CRON_MAX_RETRIES + 1 }, (_, retries) => (
+                      <Menu.Item key={retries} onClick={() => form.change('maxRetries', retries)}>
+                        {retries}
+                      </Menu.Item>
+                    ))}
+                  </Menu.Items>
+                </Menu>
+              </div>
+              <div className="flex flex-1 flex-col items-start gap-1">
+                <Text size="lg">{t('cron.overlap')}</Text>
+                <Menu>
+                  <Menu.Button>
+                    <Button>{t(`cron.overlap.${values.overlap as CronOverlap}`)}</Button>
+                  </Menu.Button>
+                  <Menu.Items>
+                    {CRON_OVERLAPS.map(overlap => (
+                      <Menu.Item key={overlap} onClick={() => form.change('overlap', overlap)}>
+                        {t(`cron.overlap.${overlap}`)}
+                      </Menu.Item>
+                    ))}
+                  </Menu.Items>
+                </Menu>
+              </div>
+              <div className="flex flex-1 flex-col items-start gap-1">
+                <Text size="lg">{t('cron.timezone')}</Text>
+                <Input
+                  name="timezone"
+                  placeholder={t('cron.timezone.placeholder')}
+                  disabled={updateFunction.isLoading}
+                  validator={timezoneValidator}
+                />
+              </div>
+            </div>
+            <div className="flex flex-col items-start gap-4">
+              <div className="flex flex-col items-start gap-1">
+                <Text size="lg">{t('cron.schedules')}</Text>
+                <Text>{t('cron.schedules.description')}</Text>
+              </div>
+              <div className="flex flex-col items-start gap-2 md:flex-row md:items-center">
+                <Input
+                  name="scheduleName"
+                  placeholder={t('cron.schedules.placeholder.name')}
+                  disabled={updateFunction.isLoading}
+                  validator={cronScheduleNameValidator}
+                />
+                <Input
+                  name="scheduleExpression"
+                  placeholder={t('cron.expression.placeholder')}
+                  disabled={updateFunction.isLoading}
+                  validator={cronValidator}
+                />
+                <Button
+                  disabled={
+                    updateFunction.isLoading || Object.keys(values.schedules).length >= CRON_SCHEDULES_PER_FUNCTION
+                  }
+                  onClick={() => {
+                    const { scheduleName, scheduleExpression } = values;
+
+                    if (scheduleName && scheduleExpression) {
+                      form.change('schedules', { ...values.schedules, [scheduleName]: scheduleExpression });
+
+                      form.change('scheduleName', '');
+                      form.change('scheduleExpression', '');
+                    }
+                  }}
+                >
+                  {t('cron.schedules.add')}
+                </Button>
+              </div>
+              {Object.entries<string>(values.schedules).map(([name, expression]) => (
+                <div key={name} className="flex flex-col items-start gap-2 md:flex-row md:items-center">
+                  <Input name={`${name}-name`} placeholder={name} disabled />
+                  <Input name={`${name}-expression`} placeholder={expression} disabled />
+                  <Button
+                    disabled={updateFunction.isLoading}
+                    variant="danger"
+                    onClick={() => {
+                      const newSchedules = { ...values.schedules };
+                      delete newSchedules[name];
+
+                      form.change('schedules', newSchedules);
+                    }}
+                  >
+                    {t('cron.schedules.remove')}
+                  </Button>
+                </div>
+              ))}
+            </div>
             <div>
               <Button variant="primary" disabled={updateFunction.isLoading} submit>
                 {t('cron.submit')}
@@ -25,6 +25,7 @@ export const deploymentsRouter = (t: T) =>
         z.object({
           functionId: z.string(),
           functionSize: z.number(),
+          sourceMapSize: z.number().optional(),
           assets: z
             .object({
               name: z.string(),
@@ -67,6 +68,10 @@ export const deploymentsRouter = (t: T) =>
         };
 
         const codeUrl = await getPresignedUrl(`${deployment.id}.js`, input.functionSize);
+        const sourceMapUrl =
+          input.sourceMapSize !== undefined
+            ? await getPresignedUrl(`${deployment.id}.js.map`, input.sourceMapSize)
+            : undefined;
         const assetsUrls: Record<string, string> = {};
 
         await Promise.all(
@@ -79,6 +84,7 @@ export const deploymentsRouter = (t: T) =>
         return {
           deploymentId: deployment.id,
           codeUrl,
+          sourceMapUrl,
           assetsUrls,
         };
       }),
@@ -121,6 +127,9 @@ export const deploymentsRouter = (t: T) =>
               totalTimeout: true,
               cron: true,
               cronRegion: true,
+              serviceBindings: true,
+              cronOptions: true,
+              cronSchedules: true,
               env: true,
             },
           }),
@@ -157,6 +166,9 @@ export const deploymentsRouter = (t: T) =>
             totalTimeout: func.totalTimeout,
             cron: func.cron,
             cronRegion: func.cronRegion,
+            serviceBindings: func.serviceBindings,
+            cronOptions: func.cronOptions,
+            cronSchedules: func.cronSchedules,
             env: envStringToObject(func.env),
             isProduction: deployment.isProduction,
             assets: deployment.assets,
@@ -1,8 +1,12 @@
 import { z } from 'zod';
 import prisma from 'lib/prisma';
-import { LOGS_TIMEFRAMES } from 'lib/types';
-import { getDeploymentCode, removeFunction, redeploy } from 'lib/api/deployments';
+import { CRON_OVERLAPS, LOGS_TIMEFRAMES } from 'lib/types';
+import { getDeploymentCode, removeFunction, redeploy, triggerCron } from 'lib/api/deployments';
 import {
+  CRON_MAX_RETRIES,
+  CRON_SCHEDULES_PER_FUNCTION,
+  CRON_SCHEDULE_NAME_REGEX,
+  DEFAULT_CRON_NAME,
   CUSTOM_DOMAINS_PER_FUNCTION,
   ENVIRONMENT_VARIABLES_PER_FUNCTION,
   ENVIRONMENT_VARIABLE_KEY_MAX_LENGTH,
@@ -13,6 +17,7 @@ import {
 } from 'lib/constants';
 import { LOGS_LEVELS } from '@lagon/ui';
 import { TRPCError } from '@trpc/server';
+import { Prisma } from '@prisma/client';
 import { T } from 'pages/api/trpc/[trpc]';
 import {
   checkCanCreateFunction,
@@ -79,6 +84,8 @@ export const functionsRouter = (t: T) =>
             totalTimeout: true,
             cron: true,
             cronRegion: true,
+            cronOptions: true,
+            cronSchedules: true,
             env: {
               select: {
                 key: true,
@@ -272,6 +279,26 @@ LIMIT 100`,
           domains: z.string().array().max(CUSTOM_DOMAINS_PER_FUNCTION).optional(),
           cron: z.string().nullable().optional(),
           cronRegion: z.string().optional(),
+          serviceBindings: z.record(z.string()).nullable().optional(),
+          cronOptions: z
+            .object({
+              maxRetries: z.number().int().min(0).max(CRON_MAX_RETRIES).optional(),
+              overlap: z.enum(CRON_OVERLAPS).optional(),
+              timezone: z.string().optional(),
+            })
+            .nullable()
+            .optional(),
+          cronSchedules: z
+            .record(
+              z
+                .string()
+                .regex(CRON_SCHEDULE_NAME_REGEX)
+                .refine(name => name !== DEFAULT_CRON_NAME),
+              z.string(),
+            )
+            .refine(schedules => Object.keys(schedules).length <= CRON_SCHEDULES_PER_FUNCTION)
+            .nullable()
+            .optional(),
           env: z
             .object({
               key: z.string().max(ENVIRONMENT_VARIABLE_KEY_MAX_LENGTH),
@@ -307,6 +334,9 @@ LIMIT 100`,
             totalTimeout: true,
             cron: true,
             cronRegion: true,
+            serviceBindings: true,
+            cronOptions: true,
+            cronSchedules: true,
             env: {
               select: {
                 key: true,
@@ -422,6 +452,39 @@ LIMIT 100`,
           });
         }
 
+        if (input.serviceBindings !== undefined) {
+          await prisma.function.update({
+            where: {
+              id: input.functionId,
+            },
+            data: {
+              serviceBindings: input.serviceBindings ?? Prisma.DbNull,
+            },
+          });
+        }
+
+        if (input.cronSchedules !== undefined) {
+          await prisma.function.update({
+            where: {
+              id: input.functionId,
+            },
+            data: {
+              cronSchedules: input.cronSchedules ?? Prisma.DbNull,
+            },
+          });
+        }
+
+        if (input.cronOptions !== undefined) {
+          await prisma.function.update({
+            where: {
+              id: input.functionId,
+            },
+            data: {
+              cronOptions: input.cronOptions ?? Prisma.DbNull,
+            },
+          });
+        }
+
         const oldDomains = func.domains.map(({ domain }) => domain);
         const deployment = func.deployments.find(deployment => deployment.isProduction);
 
@@ -433,6 +496,9 @@ LIMIT 100`,
               env: input.env || func.env,
               cron: input.cron !== undefined ? input.cron : func.cron,
               cronRegion: input.cronRegion || func.cronRegion,
+              serviceBindings: input.serviceBindings !== undefined ? input.serviceBindings : func.serviceBindings,
+              cronOptions: input.cronOptions !== undefined ? input.cronOptions : func.cronOptions,
+              cronSchedules: input.cronSchedules !== undefined ? input.cronSchedules : func.cronSchedules,
               domains: input.domains || oldDomains,
             },
             {
@@ -503,6 +569,71 @@ LIMIT 100`,
 
         await removeFunction(func);
 
+        return { ok: true };
+      }),
+    functionCronTrigger: t.procedure
+      .input(
+        z.object({
+          functionId: z.string(),
+          schedule: z.string().default(DEFAULT_CRON_NAME),
+        }),
+      )
+      .mutation(async ({ input, ctx }) => {
+        await checkCanQueryFunction({
+          functionId: input.functionId,
+          userId: ctx.session.user.id,
+        });
+
+        const func = await prisma.function.findFirst({
+          where: {
+            id: input.functionId,
+          },
+          select: {
+            id: true,
+            cron: true,
+            cronRegion: true,
+            cronSchedules: true,
+            deployments: {
+              select: {
+                id: true,
+              },
+              where: {
+                isProduction: true,
+              },
+            },
+          },
+        });
+
+        if (!func) {
+          throw new TRPCError({
+            code: 'NOT_FOUND',
+          });
+        }
+
+        const schedules = {
+          ...((func.cronSchedules as Record<string, string> | null) ?? {}),
+          ...(func.cron ? { [DEFAULT_CRON_NAME]: func.cron } : {}),
+        };
+
+        if (!(input.schedule in schedules)) {
+          throw new TRPCError({
+            code: 'BAD_REQUEST',
+            message: `Function doesn't have a Cron schedule named ${input.schedule}`,
+          });
+        }
+
+        // Only production deployments run their Cron schedules
+        const deployment = func.deployments[0];
+
+        if (!deployment) {
+          throw new TRPCError({
+            code: 'BAD_REQUEST',
+            message: "Function doesn't have a production Deployment",
+          });
+        }
+
+        await triggerCron(func, deployment.id, input.schedule);
+
         return { ok: true };
       }),
   });
@@ -4,6 +4,14 @@ export type AnalyticsTimeframe = (typeof ANALYTICS_TIMEFRAMES)[number];
 export const LOGS_TIMEFRAMES = ['Last hour', 'Last 24 hours', 'Last week'] as const;
 export type LogsTimeframe = (typeof LOGS_TIMEFRAMES)[number];
 
+export const CRON_OVERLAPS = ['skip', 'queue', 'allow'] as const;
+export type CronOverlap = (typeof CRON_OVERLAPS)[number];
+export type CronOptions = {
+  maxRetries?: number;
+  overlap?: CronOverlap;
+  timezone?: string;
+};
+
 export const THEMES = ['Light', 'Dark', 'System'] as const;
 export type ThemeOption = (typeof THEMES)[number];
 export type Theme = 'Light' | 'Dark';
@@ -240,6 +240,19 @@ export default {
   'functions.settings.cron.expression': 'Expression',
   'functions.settings.cron.expression.placeholder': '* */12 * * *',
   'functions.settings.cron.region': 'Region',
+  'functions.settings.cron.retries': 'Max retries',
+  'functions.settings.cron.overlap': 'When still running',
+  'functions.settings.cron.overlap.skip': 'Skip the execution',
+  'functions.settings.cron.overlap.queue': 'Queue the execution',
+  'functions.settings.cron.overlap.allow': 'Run concurrently',
+  'functions.settings.cron.timezone': 'Timezone',
+  'functions.settings.cron.timezone.placeholder': 'UTC',
+  'functions.settings.cron.schedules': 'Named schedules',
+  'functions.settings.cron.schedules.description':
+    'Run this Function at additional rates. The expression above is the schedule named "default".',
+  'functions.settings.cron.schedules.placeholder.name': 'e.g nightly',
+  'functions.settings.cron.schedules.add': 'Add',
+  'functions.settings.cron.schedules.remove': 'Remove',
   'functions.settings.cron.submit': 'Update',
   'functions.settings.cron.success': 'Function Cron updated successfully.',
   'functions.settings.env.title': 'Environment variables',
@@ -248,6 +248,19 @@ export default defineLocale({
   'functions.settings.cron.expression': 'Expression',
   'functions.settings.cron.expression.placeholder': '* */12 * * *',
   'functions.settings.cron.region': 'Région',
+  'functions.settings.cron.retries': 'Nombre de tentatives',
+  'functions.settings.cron.overlap': "En cours d'exécution",
+  'functions.settings.cron.overlap.skip': "Ignorer l'exécution",
+  'functions.settings.cron.overlap.queue': "Mettre l'exécution en attente",
+  'functions.settings.cron.overlap.allow': 'Exécuter en parallèle',
+  'functions.settings.cron.timezone': 'Fuseau horaire',
+  'functions.settings.cron.timezone.placeholder': 'UTC',
+  'functions.settings.cron.schedules': 'Planifications nommées',
+  'functions.settings.cron.schedules.description':
+    "Exécuter cette Fonction à d'autres fréquences. L'expression ci-dessus est la planification nommée « default ».",
+  'functions.settings.cron.schedules.placeholder.name': 'ex. nightly',
+  'functions.settings.cron.schedules.add': 'Ajouter',
+  'functions.settings.cron.schedules.remove': 'Supprimer',
   'functions.settings.cron.submit': 'Mettre à jour',
   'functions.settings.cron.success': 'Le Cron de la Fonction a été mis à jour.',
   'functions.settings.env.title': "Variables d'environement",
@@ -147,6 +147,28 @@ describe('Headers', () => {
     });
     expect(Array.from(headers.values())).toEqual(['b', 'd']);
   });
+
+  it('should combine duplicate headers', () => {
+    const headers = new Headers([
+      ['Accept', 'text/html'],
+      ['Accept', 'application/json'],
+    ]);
+    expect(headers.get('accept')).toEqual('text/html, application/json');
+    expect(Array.from(headers.keys())).toEqual(['accept']);
+    expect(Array.from(headers.values())).toEqual(['text/html, application/json']);
+  });
+
+  it('should not combine set-cookie headers', () => {
+    const headers = new Headers([
+      ['Set-Cookie', 'greeting=hello'],
+      ['Set-Cookie', 'name=world'],
+    ]);
+    expect(headers.getSetCookie()).toEqual(['greeting=hello', 'name=world']);
+    expect(Array.from(headers.entries())).toEqual([
+      ['set-cookie', 'greeting=hello'],
+      ['set-cookie', 'name=world'],
+    ]);
+  });
 });
 
 describe('fetch', () => {
@@ -154,6 +176,7 @@ describe('fetch', () => {
     globalThis.LagonAsync = {
       ...globalThis.LagonAsync,
       fetch: vi.fn(),
+      pullBody: vi.fn(),
     };
   });
 
@@ -164,24 +187,27 @@ describe('fetch', () => {
   it('should call LagonAsync.fetch', async () => {
     // @ts-expect-error LagonAsync is not defined
     globalThis.LagonAsync.fetch.mockReturnValueOnce({
-      b: 'Hello',
+      b: 1,
       s: 200,
     });
 
     await fetch('https://google.com');
 
-    expect(globalThis.LagonAsync.fetch).toHaveBeenCalledWith({
-      b: undefined,
-      h: [],
-      m: 'GET',
-      u: 'https://google.com',
-    });
+    expect(globalThis.LagonAsync.fetch).toHaveBeenCalledWith(
+      {
+        b: undefined,
+        h: [],
+        m: 'GET',
+        u: 'https://google.com',
+      },
+      undefined,
+    );
   });
 
   it('should call LagonAsync.fetch with options', async () => {
     // @ts-expect-error LagonAsync is not defined
     globalThis.LagonAsync.fetch.mockReturnValueOnce({
-      b: 'Hello',
+      b: 1,
       s: 200,
     });
 
@@ -190,12 +216,64 @@ describe('fetch', () => {
       body: 'A body',
     });
 
-    expect(globalThis.LagonAsync.fetch).toHaveBeenCalledWith({
-      m: 'POST',
-      h: [],
-      u: 'https://google.com',
-      b: 'A body',
+    expect(globalThis.LagonAsync.fetch).toHaveBeenCalledWith(
+      {
+        m: 'POST',
+        h: [],
+        u: 'https://google.com',
+        b: new TextEncoder().encode('A body'),
+      },
+      undefined,
+    );
+  });
+
+  it('should call LagonAsync.fetch with a service binding', async () => {
+    // @ts-expect-error LagonAsync is not defined
+    globalThis.LagonAsync.fetch.mockReturnValueOnce({
+      s: 200,
     });
+
+    await globalThis.__lagon__.createServiceBinding('AUTH').fetch('https://auth/login');
+
+    expect(globalThis.LagonAsync.fetch).toHaveBeenCalledWith(
+      {
+        b: undefined,
+        h: [],
+        m: 'GET',
+        u: 'https://auth/login',
+      },
+      'AUTH',
+    );
+  });
+
+  it('should stream the response body', async () => {
+    // @ts-expect-error LagonAsync is not defined
+    globalThis.LagonAsync.fetch.mockReturnValueOnce({
+      b: 1,
+      s: 200,
+    });
+    // @ts-expect-error LagonAsync is not defined
+    globalThis.LagonAsync.pullBody
+      .mockResolvedValueOnce(new TextEncoder().encode('Hello '))
+      .mockResolvedValueOnce(new TextEncoder().encode('World'))
+      .mockResolvedValueOnce(undefined);
+
+    const response = await fetch('https://google.com');
+
+    expect(await response.text()).toEqual('Hello World');
+    expect(globalThis.LagonAsync.pullBody).toHaveBeenCalledWith(1);
+  });
+
+  it('should not have a body when the response is empty', async () => {
+    // @ts-expect-error LagonAsync is not defined
+    globalThis.LagonAsync.fetch.mockReturnValueOnce({
+      s: 204,
+    });
+
+    const response = await fetch('https://google.com');
+
+    expect(response.body).toBeNull();
+    expect(globalThis.LagonAsync.pullBody).not.toHaveBeenCalled();
   });
 });
 
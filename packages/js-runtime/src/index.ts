@@ -23,6 +23,12 @@ import './runtime/http/FormData';
 import './runtime/http/Response';
 import './runtime/http/Request';
 import './runtime/http/fetch';
+import './runtime/kv';
+import './runtime/queue';
+import './runtime/cache';
+import './runtime/db';
+import './runtime/websocket';
+import './runtime/socket';
 
 // Declare the global functions and variables available
 // on the runtime, that are injected from the Rust code.
@@ -64,11 +70,19 @@ declare global {
     compressionCreate: (format: CompressionFormat, isDecoder: boolean) => string;
     compressionWrite: (id: string, buf: Uint8Array) => Uint8Array;
     compressionFinish: (id: string) => Uint8Array;
+    websocketCreate: () => number;
+    websocketSend: (id: number, data: string | Uint8Array) => void;
+    websocketClose: (id: number, code: number, reason: string) => void;
+    waitUntil: (promise: Promise<unknown>) => void;
+    queueSettle: (id: string, retry: boolean) => void;
   };
 
   var LagonAsync: {
-    fetch: ({ h, m, b, u }: { h?: Map<string, string>; m: string; b?: string; u: string }) => Promise<{
-      b: Uint8Array;
+    fetch: (
+      { h, m, b, u }: { h?: Map<string, string>; m: string; b?: Uint8Array; u: string },
+      serviceBinding?: string,
+    ) => Promise<{
+      b?: number;
       s: number;
       h?: Record<string, string>;
     }>;
@@ -110,31 +124,249 @@ declare global {
       algorithm: RsaHashedKeyGenParams | EcKeyGenParams | HmacKeyGenParams | AesKeyGenParams,
     ): Promise<ArrayBuffer>;
     sleep: (ms: number) => Promise<void>;
+    pullBody: (id: number) => Promise<Uint8Array | undefined>;
+    kvGet: (key: string) => Promise<{ v: Uint8Array; m?: string } | null>;
+    kvPut: (
+      key: string,
+      value: Uint8Array,
+      metadata?: string,
+      expiration?: number,
+      expirationTtl?: number,
+    ) => Promise<void>;
+    kvDelete: (key: string) => Promise<void>;
+    kvList: (
+      prefix?: string,
+      limit?: number,
+      cursor?: string,
+    ) => Promise<{
+      keys: { name: string; metadata: string | null; expiration: number | null }[];
+      cursor: string | null;
+    }>;
+    queueSend: (body: string) => Promise<void>;
+    cacheMatch: (
+      cacheName: string,
+      request: { m: string; u: string; h?: Map<string, string> },
+    ) => Promise<{ b?: number; s: number; h?: Record<string, string> } | undefined>;
+    cachePut: (
+      cacheName: string,
+      request: { m: string; u: string; h?: Map<string, string> },
+      response: { b: Uint8Array; h: Headers; s: number },
+    ) => Promise<void>;
+    cacheDelete: (cacheName: string, request: { m: string; u: string; h?: Map<string, string> }) => Promise<boolean>;
+    dbQuery: (
+      transaction: number | undefined,
+      statement: { q: string; p: DatabaseValue[] },
+    ) => Promise<DatabaseQueryResult>;
+    dbBatch: (
+      transaction: number | undefined,
+      statements: { q: string; p: DatabaseValue[] }[],
+    ) => Promise<DatabaseQueryResult[]>;
+    dbExec: (transaction: number | undefined, sql: string) => Promise<{ count: number; duration: number }>;
+    dbBegin: () => Promise<number>;
+    dbCommit: (transaction: number) => Promise<void>;
+    dbRollback: (transaction: number) => Promise<void>;
+    socketConnect: (hostname: string, port: number, secure: boolean) => Promise<number>;
+    socketRead: (id: number) => Promise<Uint8Array | undefined>;
+    socketWrite: (id: number, data: Uint8Array) => Promise<void>;
+    socketClose: (id: number) => Promise<void>;
+    // Protocols are comma-separated
+    websocketConnect: (url: string, protocols: string) => Promise<{ i: number; p: string }>;
+    // Resolves with the close code and reason when the server closes the socket
+    websocketReceive: (id: number) => Promise<string | Uint8Array | { c: number; r: string } | undefined>;
   };
   var __lagon__: {
-    isIterable: (value: unknown) => value is ArrayBuffer;
     parseMultipart: (headers: Headers, body?: string) => FormData;
+    createBodyStream: (id: number) => ReadableStream<Uint8Array>;
+    createServiceBinding: (name: string) => ServiceBinding;
+    createKvNamespace: () => KVNamespace;
+    createQueue: () => Queue;
+    createMessageBatch: (queue: string, messages: QueueMessagePayload[]) => MessageBatch;
+    createDatabase: () => Database;
+    getWebSocketId: (webSocket: WebSocket) => number;
     TEXT_ENCODER: TextEncoder;
     TEXT_DECODER: TextDecoder;
   };
   var __storage__: Map<AsyncContext, unknown>;
+  interface ServiceBinding {
+    fetch: (input: RequestInfo | URL, init?: RequestInit) => Promise<Response>;
+  }
+
+  type KVValue = string | ArrayBuffer | ArrayBufferView | ReadableStream;
+  type KVValueType = 'text' | 'json' | 'arrayBuffer' | 'stream';
+
+  interface KVNamespace {
+    get: (key: string, options?: KVValueType | { type?: KVValueType }) => Promise<unknown>;
+    getWithMetadata: (
+      key: string,
+      options?: KVValueType | { type?: KVValueType },
+    ) => Promise<{ value: unknown; metadata: unknown }>;
+    put: (
+      key: string,
+      value: KVValue,
+      options?: { expiration?: number; expirationTtl?: number; metadata?: unknown },
+    ) => Promise<void>;
+    delete: (key: string) => Promise<void>;
+    list: (options?: { prefix?: string; limit?: number; cursor?: string }) => Promise<{
+      keys: { name: string; expiration?: number; metadata?: unknown }[];
+      list_complete: boolean;
+      cursor?: string;
+    }>;
+  }
+
+  interface Queue {
+    send: (body: unknown) => Promise<void>;
+  }
+
+  // The body is serialized as JSON, and the timestamp is in milliseconds since the epoch
+  interface QueueMessagePayload {
+    id: string;
+    body: string;
+    timestamp: number;
+    attempts: number;
+  }
+
+  interface Message {
+    readonly id: string;
+    readonly body: unknown;
+    readonly timestamp: Date;
+    readonly attempts: number;
+    ack: () => void;
+    retry: () => void;
+  }
+
+  interface MessageBatch {
+    readonly queue: string;
+    readonly messages: readonly Message[];
+    ackAll: () => void;
+    retryAll: () => void;
+  }
+
+  type DatabaseValue = null | number | bigint | string | boolean | Uint8Array;
+
+  // Rows are returned as arrays of values, in the same order as `columns`
+  interface DatabaseQueryResult {
+    columns: string[];
+    rows: DatabaseValue[][];
+    changes: number;
+    lastRowId: number;
+    duration: number;
+  }
+
+  interface DatabaseResult {
+    results: Record<string, DatabaseValue>[];
+    meta: { changes: number; last_row_id: number; duration: number };
+  }
+
+  interface DatabasePreparedStatement {
+    readonly sql: string;
+    readonly params: DatabaseValue[];
+    bind: (...values: unknown[]) => DatabasePreparedStatement;
+    all: () => Promise<DatabaseResult>;
+    first: (column?: string) => Promise<unknown>;
+    run: () => Promise<{ success: boolean; meta: DatabaseResult['meta'] }>;
+    raw: () => Promise<DatabaseValue[][]>;
+  }
+
+  interface Database {
+    prepare: (sql: string) => DatabasePreparedStatement;
+    batch: (statements: DatabasePreparedStatement[]) => Promise<DatabaseResult[]>;
+    exec: (sql: string) => Promise<{ count: number; duration: number }>;
+    transaction: <T>(callback: (tx: Database) => Promise<T>) => Promise<T>;
+  }
+
+  type HandlerBindingKind = 'service' | 'kv' | 'queue' | 'db';
+
+  interface HandlerContext {
+    env: Record<string, ServiceBinding | KVNamespace | Queue | Database>;
+    waitUntil: (promise: Promise<unknown>) => void;
+  }
+
   var masterHandler: (
     id: number,
-    handler: (request: Request) => Promise<Response>,
+    handler: (request: Request, ctx: HandlerContext) => Promise<Response>,
     request: {
       i: string;
       m: RequestInit['method'];
       h: RequestInit['headers'];
-      b: RequestInit['body'];
+      b?: number;
     },
+    // Maps the name of each binding to its kind
+    bindings: Record<string, HandlerBindingKind>,
   ) => Promise<{
-    b?: string;
+    b?: Uint8Array;
     h: ResponseInit['headers'];
     s: ResponseInit['status'];
+    // The id of the accepted WebSocket
+    w?: number;
   }>;
 
+  interface ScheduledEvent {
+    readonly scheduledTime: number;
+    readonly cron: string;
+    readonly name: string;
+  }
+
+  var masterScheduledHandler: (
+    scheduled: ((event: ScheduledEvent, ctx: HandlerContext) => Promise<void>) | undefined,
+    handler: ((request: Request, ctx: HandlerContext) => Promise<Response>) | undefined,
+    // The scheduled time in milliseconds since the epoch, the cron expression and the schedule name
+    event: { t: number; c: string; n: string },
+    bindings: Record<string, HandlerBindingKind>,
+  ) => Promise<void>;
+
+  var masterQueueHandler: (
+    queue: ((batch: MessageBatch, ctx: HandlerContext) => Promise<void>) | undefined,
+    // The queue name and its messages
+    batch: { q: string; m: QueueMessagePayload[] },
+    bindings: Record<string, HandlerBindingKind>,
+  ) => Promise<void>;
+
+  var masterWebSocketHandler: (
+    id: number,
+    type: 'message' | 'close',
+    // The message, or the close code
+    data: string | Uint8Array | number,
+    reason?: string,
+  ) => void;
+
+  var WebSocketPair: {
+    new (): { 0: WebSocket; 1: WebSocket };
+  };
+
+  interface WebSocket {
+    accept(): void;
+  }
+
+  interface ResponseInit {
+    webSocket?: WebSocket | null;
+  }
+
+  interface SocketAddress {
+    hostname: string;
+    port: number;
+  }
+
+  interface SocketOptions {
+    secureTransport?: 'on' | 'off';
+  }
+
+  interface Socket {
+    readonly readable: ReadableStream<Uint8Array>;
+    readonly writable: WritableStream<Uint8Array>;
+    readonly opened: Promise<void>;
+    readonly closed: Promise<void>;
+    close(): Promise<void>;
+  }
+
+  var connect: (address: string | SocketAddress, options?: SocketOptions) => Socket;
+
   interface Response {
     readonly isStream: boolean;
+    readonly webSocket: WebSocket | null;
+  }
+
+  interface CacheStorage {
+    readonly default: Cache;
   }
 
   interface Blob {
@@ -146,7 +378,39 @@ declare global {
   }
 }
 
-globalThis.masterHandler = async (id, handler, request) => {
+const createHandlerContext = (bindings: Record<string, HandlerBindingKind>): HandlerContext => {
+  const env: HandlerContext['env'] = {};
+
+  for (const [name, kind] of Object.entries(bindings)) {
+    switch (kind) {
+      case 'kv':
+        env[name] = globalThis.__lagon__.createKvNamespace();
+        break;
+      case 'queue':
+        env[name] = globalThis.__lagon__.createQueue();
+        break;
+      case 'db':
+        env[name] = globalThis.__lagon__.createDatabase();
+        break;
+      default:
+        env[name] = globalThis.__lagon__.createServiceBinding(name);
+    }
+  }
+
+  const waitUntil = (promise: Promise<unknown>) => {
+    const waitedPromise = Promise.resolve(promise);
+
+    // Rejections are logged once the response has been sent,
+    // instead of failing the request as unhandled rejections
+    waitedPromise.catch(() => undefined);
+
+    LagonSync.waitUntil(waitedPromise);
+  };
+
+  return { env: Object.freeze(env), waitUntil };
+};
+
+globalThis.masterHandler = async (id, handler, request, bindings) => {
   if (typeof handler !== 'function') {
     throw new Error('Handler function is not defined or is not a function');
   }
@@ -154,15 +418,23 @@ globalThis.masterHandler = async (id, handler, request) => {
   const handlerRequest = new Request(request.i, {
     method: request.m,
     headers: request.h,
-    body: request.b,
+    body: request.b !== undefined ? globalThis.__lagon__.createBodyStream(request.b) : undefined,
   });
 
-  const response = await handler(handlerRequest);
+  const response = await handler(handlerRequest, createHandlerContext(bindings));
 
   if (!(response instanceof Response)) {
     throw new Error('Handler function should return a Response object');
   }
 
+  if (response.webSocket) {
+    return {
+      h: response.headers,
+      s: response.status,
+      w: globalThis.__lagon__.getWebSocketId(response.webSocket),
+    };
+  }
+
   if (response.isStream) {
     const responseBody = response.body;
 
@@ -195,9 +467,44 @@ globalThis.masterHandler = async (id, handler, request) => {
     };
   } else {
     return {
-      b: await response.text(),
+      b: new Uint8Array(await response.arrayBuffer()),
       h: response.headers,
       s: response.status,
     };
   }
 };
+
+globalThis.masterScheduledHandler = async (scheduled, handler, event, bindings) => {
+  const ctx = createHandlerContext(bindings);
+
+  if (typeof scheduled === 'function') {
+    await scheduled(Object.freeze({ scheduledTime: event.t, cron: event.c, name: event.n }), ctx);
+    return;
+  }
+
+  if (typeof handler !== 'function') {
+    throw new Error('Scheduled function is not defined or is not a function');
+  }
+
+  // Cron Functions exporting only a handler receive an empty request,
+  // and fail when they don't respond with a successful status
+  const response = await handler(new Request('/'), ctx);
+
+  if (!(response instanceof Response)) {
+    throw new Error('Handler function should return a Response object');
+  }
+
+  if (!response.ok) {
+    throw new Error(`Handler function responded with status ${response.status}`);
+  }
+};
+
+globalThis.masterQueueHandler = async (queue, batch, bindings) => {
+  if (typeof queue !== 'function') {
+    throw new Error('Queue function is not defined or is not a function');
+  }
+
+  // Messages that aren't explicitly acknowledged or retried are acknowledged
+  // when this promise resolves, and retried when it rejects
+  await queue(globalThis.__lagon__.createMessageBatch(batch.q, batch.m), createHandlerContext(bindings));
+};
@@ -0,0 +1,105 @@
+(globalThis => {
+  const toRequest = (input: RequestInfo | URL) => (input instanceof Request ? input : new Request(input));
+
+  // Only the URL and headers of the request are used to
+  // match entries, the headers being needed for `Vary`
+  const serializeRequest = (request: Request) => ({
+    m: 'GET',
+    u: request.url,
+    // @ts-expect-error private property
+    h: request.headers.h,
+  });
+
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  class LagonCache {
+    private readonly name: string;
+
+    constructor(name: string) {
+      this.name = name;
+    }
+
+    async match(input: RequestInfo | URL, options?: CacheQueryOptions): Promise<Response | undefined> {
+      const request = toRequest(input);
+
+      if (request.method !== 'GET' && !options?.ignoreMethod) {
+        return undefined;
+      }
+
+      try {
+        const response = await LagonAsync.cacheMatch(this.name, serializeRequest(request));
+
+        if (response === undefined) {
+          return undefined;
+        }
+
+        const responseBody = response.b !== undefined ? globalThis.__lagon__.createBodyStream(response.b) : null;
+
+        return new Response(responseBody, {
+          headers: response.h,
+          status: response.s,
+        });
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+
+    async put(input: RequestInfo | URL, response: Response): Promise<void> {
+      const request = toRequest(input);
+
+      if (request.method !== 'GET') {
+        throw new TypeError('Cache only supports GET requests');
+      }
+
+      if (response.status === 206) {
+        throw new TypeError('Cache does not support partial responses');
+      }
+
+      if (
+        response.headers
+          .get('vary')
+          ?.split(',')
+          .some(name => name.trim() === '*')
+      ) {
+        throw new TypeError('Cache does not support responses with "Vary: *"');
+      }
+
+      if (response.bodyUsed) {
+        throw new TypeError('Response body has already been used');
+      }
+
+      const body = new Uint8Array(await response.arrayBuffer());
+
+      try {
+        await LagonAsync.cachePut(this.name, serializeRequest(request), {
+          b: body,
+          h: response.headers,
+          s: response.status,
+        });
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+
+    async delete(input: RequestInfo | URL, options?: CacheQueryOptions): Promise<boolean> {
+      const request = toRequest(input);
+
+      if (request.method !== 'GET' && !options?.ignoreMethod) {
+        return false;
+      }
+
+      try {
+        return await LagonAsync.cacheDelete(this.name, serializeRequest(request));
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+  }
+
+  globalThis.caches = {
+    default: new LagonCache('default'),
+    open: async (name: string) => new LagonCache(String(name)),
+  } as unknown as CacheStorage;
+})(globalThis);
@@ -3,9 +3,6 @@
   const CONTENT_DISPOSITION = 'Content-Disposition';
   const APPLICATION_X_WWW_FORM_URLENCODED = 'application/x-www-form-urlencoded';
 
-  const isIterable = (value: unknown): value is ArrayBuffer =>
-    typeof value !== 'string' && Symbol.iterator in Object(value);
-
   const parseMultipart = (headers: Headers, body?: string): FormData => {
     const formData = new FormData();
 
@@ -63,14 +60,35 @@
   const TEXT_ENCODER = new TextEncoder();
   const TEXT_DECODER = new TextDecoder();
 
+  // Request bodies are streamed from Rust: each chunk is pulled
+  // on demand, until the binding resolves with `undefined`
+  const createBodyStream = (id: number): ReadableStream<Uint8Array> =>
+    new ReadableStream({
+      async pull(controller) {
+        try {
+          const chunk = await LagonAsync.pullBody(id);
+
+          if (chunk === undefined) {
+            controller.close();
+          } else {
+            controller.enqueue(chunk);
+          }
+        } catch (error) {
+          controller.error(new TypeError(error instanceof Error ? error.message : String(error)));
+        }
+      },
+    });
+
   // https://developer.mozilla.org/en-US/docs/Web/API/WorkerGlobalScope/self
   // @ts-expect-error Workers have a global `self` property, which we assign
   // to `globalThis` because we don't implement all the Workers APIs
   globalThis.self = globalThis;
+  // `createServiceBinding` is added by fetch.ts, since it
+  // needs to build requests the same way fetch() does
   globalThis.__lagon__ = {
-    isIterable,
     parseMultipart,
+    createBodyStream,
     TEXT_ENCODER,
     TEXT_DECODER,
-  };
+  } as typeof globalThis.__lagon__;
 })(globalThis);
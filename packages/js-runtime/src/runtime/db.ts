@@ -0,0 +1,169 @@
+(globalThis => {
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  const toParam = (value: unknown): DatabaseValue => {
+    if (value instanceof ArrayBuffer) {
+      return new Uint8Array(value);
+    }
+
+    if (ArrayBuffer.isView(value)) {
+      return new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+    }
+
+    return value as DatabaseValue;
+  };
+
+  const toObjects = ({ columns, rows }: DatabaseQueryResult) =>
+    rows.map(row => {
+      const object: Record<string, DatabaseValue> = {};
+
+      columns.forEach((column, index) => {
+        object[column] = row[index];
+      });
+
+      return object;
+    });
+
+  const toResult = (result: DatabaseQueryResult): DatabaseResult => ({
+    results: toObjects(result),
+    meta: {
+      changes: result.changes,
+      last_row_id: result.lastRowId,
+      duration: result.duration,
+    },
+  });
+
+  class LagonPreparedStatement implements DatabasePreparedStatement {
+    readonly transactionId?: number;
+    readonly sql: string;
+    readonly params: DatabaseValue[];
+
+    constructor(transactionId: number | undefined, sql: string, params: DatabaseValue[] = []) {
+      this.transactionId = transactionId;
+      this.sql = sql;
+      this.params = params;
+    }
+
+    bind(...values: unknown[]) {
+      return new LagonPreparedStatement(this.transactionId, this.sql, values.map(toParam));
+    }
+
+    private async query() {
+      try {
+        return await LagonAsync.dbQuery(this.transactionId, { q: this.sql, p: this.params });
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+
+    async all() {
+      return toResult(await this.query());
+    }
+
+    async first(column?: string) {
+      const [row] = toObjects(await this.query());
+
+      if (row === undefined) {
+        return null;
+      }
+
+      if (column !== undefined) {
+        if (!(column in row)) {
+          throw new Error(`Column not found: ${column}`);
+        }
+
+        return row[column];
+      }
+
+      return row;
+    }
+
+    async run() {
+      const { meta } = await this.all();
+
+      return { success: true, meta };
+    }
+
+    async raw() {
+      return (await this.query()).rows;
+    }
+  }
+
+  class LagonDatabase implements Database {
+    private readonly transactionId?: number;
+
+    constructor(transactionId?: number) {
+      this.transactionId = transactionId;
+    }
+
+    prepare(sql: string) {
+      return new LagonPreparedStatement(this.transactionId, String(sql));
+    }
+
+    async batch(statements: DatabasePreparedStatement[]) {
+      try {
+        const results = await LagonAsync.dbBatch(
+          this.transactionId,
+          statements.map(statement => ({ q: statement.sql, p: statement.params })),
+        );
+
+        return results.map(toResult);
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+
+    async exec(sql: string) {
+      try {
+        return await LagonAsync.dbExec(this.transactionId, String(sql));
+      } catch (error) {
+        throw toError(error);
+      }
+    }
+
+    // The transaction is committed when the callback resolves,
+    // and rolled back when it throws
+    async transaction<T>(callback: (tx: Database) => Promise<T>) {
+      if (this.transactionId !== undefined) {
+        throw new Error('Transactions cannot be nested');
+      }
+
+      let id: number;
+
+      try {
+        id = await LagonAsync.dbBegin();
+      } catch (error) {
+        throw toError(error);
+      }
+
+      let result: T;
+
+      try {
+        result = await callback(new LagonDatabase(id));
+      } catch (error) {
+        try {
+          await LagonAsync.dbRollback(id);
+        } catch {
+          // The transaction is rolled back either way
+        }
+
+        throw error;
+      }
+
+      try {
+        await LagonAsync.dbCommit(id);
+      } catch (error) {
+        throw toError(error);
+      }
+
+      return result;
+    }
+  }
+
+  const database = new LagonDatabase();
+
+  // Each Function has a single database, shared by all its requests
+  globalThis.__lagon__.createDatabase = () => database;
+})(globalThis);
@@ -182,4 +182,37 @@
       this.total = eventInitDict?.total ?? 0;
     }
   };
+
+  // @ts-expect-error we don't implement the deprecated initMessageEvent()
+  globalThis.MessageEvent = class<T> extends Event {
+    readonly data: T;
+    readonly lastEventId: string;
+    readonly origin: string;
+    readonly ports: ReadonlyArray<MessagePort>;
+    readonly source: MessageEventSource | null;
+
+    constructor(type: string, eventInitDict?: MessageEventInit<T>) {
+      super(type, eventInitDict);
+
+      this.data = eventInitDict?.data ?? (null as T);
+      this.lastEventId = eventInitDict?.lastEventId ?? '';
+      this.origin = eventInitDict?.origin ?? '';
+      this.ports = eventInitDict?.ports ?? [];
+      this.source = eventInitDict?.source ?? null;
+    }
+  };
+
+  globalThis.CloseEvent = class extends Event {
+    readonly code: number;
+    readonly reason: string;
+    readonly wasClean: boolean;
+
+    constructor(type: string, eventInitDict?: CloseEventInit) {
+      super(type, eventInitDict);
+
+      this.code = eventInitDict?.code ?? 0;
+      this.reason = eventInitDict?.reason ?? '';
+      this.wasClean = eventInitDict?.wasClean ?? false;
+    }
+  };
 })(globalThis);
@@ -2,7 +2,9 @@
   globalThis.process = {
     // Spread the process object to make TS happy (even if it doesn't exists yet)
     ...globalThis.process,
-    env: {},
+    // Environment variables are set before the runtime
+    // is evaluated when we don't use a snapshot
+    env: globalThis.process?.env ?? {},
     argv: [],
   };
 })(globalThis);
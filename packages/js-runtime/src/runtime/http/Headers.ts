@@ -135,7 +135,7 @@
     }
 
     *keys(): IterableIterator<string> {
-      for (const [key] of this.h) {
+      for (const [key] of this.entries()) {
         yield key;
       }
     }
@@ -150,7 +150,7 @@
     }
 
     *values(): IterableIterator<string> {
-      for (const [, value] of this.h) {
+      for (const [, value] of this.entries()) {
         yield value;
       }
     }
@@ -13,16 +13,22 @@ import { RequestResponseBody } from './body';
     url: string;
     type: ResponseType;
     redirected: boolean;
+    webSocket: WebSocket | null;
 
     constructor(body?: BodyInit | null, init?: ResponseInit) {
       super(body, init?.headers);
 
+      if (init?.webSocket && init.status !== 101) {
+        throw new RangeError('Responses with a WebSocket must have a 101 status code');
+      }
+
       if (init?.status) {
         if (!!body && NULL_BODY_STATUS.includes(init.status)) {
           throw new TypeError('Response with null body status cannot have body');
         }
 
-        if (init.status < 200 || init.status > 599) {
+        // 101 is only allowed to accept a WebSocket
+        if ((init.status < 200 && !init.webSocket) || init.status > 599) {
           throw new RangeError('Invalid status code');
         }
 
@@ -38,6 +44,7 @@ import { RequestResponseBody } from './body';
       this.url = init?.url || '';
       this.type = 'default';
       this.redirected = false;
+      this.webSocket = init?.webSocket ?? null;
     }
 
     clone(): Response {
@@ -96,7 +96,7 @@ export class RequestResponseBody {
 
     const reader = (this.theBody as ReadableStream<Uint8Array>).getReader();
 
-    return new Promise(resolve => {
+    return new Promise((resolve, reject) => {
       let result = new Uint8Array();
 
       const pull = () => {
@@ -113,7 +113,7 @@ export class RequestResponseBody {
           result = newResult;
 
           pull();
-        });
+        }, reject);
       };
 
       pull();
@@ -183,29 +183,8 @@ export class RequestResponseBody {
       return this.theBody.text();
     }
 
-    const reader = (this.theBody as ReadableStream<Uint8Array>).getReader();
-
-    return new Promise(resolve => {
-      let result = '';
-
-      const pull = () => {
-        reader.read().then(({ done, value }) => {
-          if (done) {
-            this.bodyUsed = true;
-            return resolve(result);
-          }
-
-          if (globalThis.__lagon__.isIterable(value)) {
-            result += globalThis.__lagon__.TEXT_DECODER.decode(value);
-          } else {
-            result += value;
-          }
-
-          pull();
-        });
-      };
-
-      pull();
-    });
+    // Decode the whole body at once, since a multi-byte
+    // character can be split across two chunks
+    return this.arrayBuffer().then(buffer => globalThis.__lagon__.TEXT_DECODER.decode(buffer));
   }
 }
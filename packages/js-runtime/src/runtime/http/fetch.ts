@@ -1,31 +1,43 @@
 (globalThis => {
   const FORCE_0_CONTENT_LENGTH_METHODS = ['POST', 'PUT'];
 
-  globalThis.fetch = async (input, init) => {
+  // Service bindings send their requests through the same
+  // binding, with the name of the binding as a second argument
+  const sendRequest = async (input: RequestInfo | URL, init?: RequestInit, serviceBinding?: string) => {
     const headers = new Headers(init?.headers);
-    let body: string | undefined;
+    let body: Uint8Array | undefined;
 
     const isInputRequest = input instanceof Request;
 
     if (init?.body || (isInputRequest && (input as Request).body)) {
       const paramBody = init?.body || (input as Request).body;
 
-      if (globalThis.__lagon__.isIterable(paramBody)) {
-        body = globalThis.__lagon__.TEXT_DECODER.decode(paramBody);
+      if (typeof paramBody === 'string') {
+        body = globalThis.__lagon__.TEXT_ENCODER.encode(paramBody);
+      } else if (paramBody instanceof ArrayBuffer) {
+        body = new Uint8Array(paramBody);
+      } else if (ArrayBuffer.isView(paramBody)) {
+        body = new Uint8Array(paramBody.buffer, paramBody.byteOffset, paramBody.byteLength);
       } else if (paramBody instanceof ReadableStream) {
-        body = '';
+        const chunks: Uint8Array[] = [];
+        let length = 0;
 
         // @ts-expect-error iterate over the stream
         for await (const chunk of paramBody) {
-          body += globalThis.__lagon__.TEXT_DECODER.decode(chunk);
-        }
-      } else {
-        if (typeof paramBody !== 'string') {
-          // TODO: Support other body types
-          throw new Error('Body must be a string or an iterable');
+          chunks.push(chunk);
+          length += chunk.byteLength;
         }
 
-        body = paramBody;
+        body = new Uint8Array(length);
+        let offset = 0;
+
+        for (const chunk of chunks) {
+          body.set(chunk, offset);
+          offset += chunk.byteLength;
+        }
+      } else {
+        // TODO: Support other body types
+        throw new Error('Body must be a string, a buffer or a stream');
       }
     }
 
@@ -54,17 +66,22 @@
     try {
       checkAborted();
 
-      const response = await LagonAsync.fetch({
-        m: method,
-        u: url,
-        b: body,
-        // @ts-expect-error private property
-        h: headers.h,
-      });
+      const response = await LagonAsync.fetch(
+        {
+          m: method,
+          u: url,
+          b: body,
+          // @ts-expect-error private property
+          h: headers.h,
+        },
+        serviceBinding,
+      );
 
       checkAborted();
 
-      return new Response(response.b, {
+      const responseBody = response.b !== undefined ? globalThis.__lagon__.createBodyStream(response.b) : null;
+
+      return new Response(responseBody, {
         // url: response.init.url,
         headers: response.h,
         status: response.s,
@@ -77,4 +94,10 @@
       throw error;
     }
   };
+
+  globalThis.fetch = (input, init) => sendRequest(input, init);
+
+  globalThis.__lagon__.createServiceBinding = name => ({
+    fetch: (input, init) => sendRequest(input, init, name),
+  });
 })(globalThis);
@@ -0,0 +1,107 @@
+(globalThis => {
+  const getType = (options?: KVValueType | { type?: KVValueType }): KVValueType => {
+    if (typeof options === 'string') {
+      return options;
+    }
+
+    return options?.type ?? 'text';
+  };
+
+  const decodeValue = (value: Uint8Array, type: KVValueType) => {
+    switch (type) {
+      case 'text':
+        return globalThis.__lagon__.TEXT_DECODER.decode(value);
+      case 'json':
+        return JSON.parse(globalThis.__lagon__.TEXT_DECODER.decode(value));
+      case 'arrayBuffer':
+        return value.buffer.slice(value.byteOffset, value.byteOffset + value.byteLength);
+      case 'stream':
+        return new Blob([value]).stream();
+      default:
+        throw new TypeError(`Unknown type: ${type}`);
+    }
+  };
+
+  const encodeValue = async (value: KVValue): Promise<Uint8Array> => {
+    if (typeof value === 'string') {
+      return globalThis.__lagon__.TEXT_ENCODER.encode(value);
+    }
+
+    if (value instanceof ArrayBuffer) {
+      return new Uint8Array(value);
+    }
+
+    if (ArrayBuffer.isView(value)) {
+      return new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+    }
+
+    if (value instanceof ReadableStream) {
+      return new Uint8Array(await new Response(value).arrayBuffer());
+    }
+
+    throw new TypeError('Value must be a string, a buffer or a stream');
+  };
+
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  const getWithMetadata = async (key: string, options?: KVValueType | { type?: KVValueType }) => {
+    try {
+      const entry = await LagonAsync.kvGet(String(key));
+
+      if (entry === null) {
+        return { value: null, metadata: null };
+      }
+
+      return {
+        value: decodeValue(entry.v, getType(options)),
+        metadata: entry.m !== undefined ? JSON.parse(entry.m) : null,
+      };
+    } catch (error) {
+      throw toError(error);
+    }
+  };
+
+  const kv: KVNamespace = {
+    get: async (key, options) => (await getWithMetadata(key, options)).value,
+    getWithMetadata,
+    put: async (key, value, options) => {
+      const bytes = await encodeValue(value);
+      const metadata = options?.metadata !== undefined ? JSON.stringify(options.metadata) : undefined;
+
+      try {
+        await LagonAsync.kvPut(String(key), bytes, metadata, options?.expiration, options?.expirationTtl);
+      } catch (error) {
+        throw toError(error);
+      }
+    },
+    delete: async key => {
+      try {
+        await LagonAsync.kvDelete(String(key));
+      } catch (error) {
+        throw toError(error);
+      }
+    },
+    list: async options => {
+      try {
+        const { keys, cursor } = await LagonAsync.kvList(options?.prefix, options?.limit, options?.cursor);
+
+        return {
+          keys: keys.map(({ name, metadata, expiration }) => ({
+            name,
+            expiration: expiration ?? undefined,
+            metadata: metadata !== null ? JSON.parse(metadata) : undefined,
+          })),
+          list_complete: cursor === null,
+          cursor: cursor ?? undefined,
+        };
+      } catch (error) {
+        throw toError(error);
+      }
+    },
+  };
+
+  // Each Function has a single namespace, shared by all its requests
+  globalThis.__lagon__.createKvNamespace = () => kv;
+})(globalThis);
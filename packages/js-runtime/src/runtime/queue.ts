@@ -0,0 +1,44 @@
+(globalThis => {
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  const queue: Queue = {
+    send: async body => {
+      const json = JSON.stringify(body);
+
+      if (json === undefined) {
+        throw new TypeError('Message body must be serializable to JSON');
+      }
+
+      try {
+        await LagonAsync.queueSend(json);
+      } catch (error) {
+        throw toError(error);
+      }
+    },
+  };
+
+  // Each Function has a single queue, shared by all its requests
+  globalThis.__lagon__.createQueue = () => queue;
+
+  globalThis.__lagon__.createMessageBatch = (name, messages) => {
+    const batchMessages = messages.map(message =>
+      Object.freeze({
+        id: message.id,
+        body: JSON.parse(message.body),
+        timestamp: new Date(message.timestamp),
+        attempts: message.attempts,
+        ack: () => LagonSync.queueSettle(message.id, false),
+        retry: () => LagonSync.queueSettle(message.id, true),
+      }),
+    );
+
+    return Object.freeze({
+      queue: name,
+      messages: Object.freeze(batchMessages),
+      ackAll: () => batchMessages.forEach(message => message.ack()),
+      retryAll: () => batchMessages.forEach(message => message.retry()),
+    });
+  };
+})(globalThis);
@@ -0,0 +1,128 @@
+(globalThis => {
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  const parseAddress = (address: string | SocketAddress): SocketAddress => {
+    if (typeof address !== 'string') {
+      return address;
+    }
+
+    const separator = address.lastIndexOf(':');
+
+    if (separator === -1) {
+      throw new TypeError('Address must be in the format hostname:port');
+    }
+
+    return { hostname: address.slice(0, separator), port: Number(address.slice(separator + 1)) };
+  };
+
+  const toBytes = (chunk: unknown) => {
+    if (typeof chunk === 'string') {
+      return globalThis.__lagon__.TEXT_ENCODER.encode(chunk);
+    }
+
+    if (chunk instanceof ArrayBuffer) {
+      return new Uint8Array(chunk);
+    }
+
+    if (ArrayBuffer.isView(chunk)) {
+      return new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength);
+    }
+
+    throw new TypeError('Chunk must be a string or a buffer');
+  };
+
+  class LagonSocket implements Socket {
+    readonly readable: ReadableStream<Uint8Array>;
+    readonly writable: WritableStream<Uint8Array>;
+    readonly opened: Promise<void>;
+    readonly closed: Promise<void>;
+
+    private readonly connection: Promise<number>;
+    private resolveClosed!: () => void;
+    private isClosed = false;
+
+    constructor(hostname: string, port: number, secure: boolean) {
+      this.connection = LagonAsync.socketConnect(hostname, port, secure).catch(error => {
+        throw toError(error);
+      });
+      this.opened = this.connection.then(() => undefined);
+      this.closed = new Promise((resolve, reject) => {
+        this.resolveClosed = resolve;
+        this.connection.catch(reject);
+      });
+
+      // Avoid unhandled rejections when only using the streams
+      this.opened.catch(() => undefined);
+      this.closed.catch(() => undefined);
+
+      this.readable = new ReadableStream({
+        pull: async controller => {
+          const id = await this.connection;
+          const chunk = await LagonAsync.socketRead(id);
+
+          if (chunk === undefined) {
+            controller.close();
+            await this.close();
+          } else {
+            controller.enqueue(chunk);
+          }
+        },
+        cancel: () => this.close(),
+      });
+
+      this.writable = new WritableStream({
+        write: async chunk => {
+          const id = await this.connection;
+
+          try {
+            await LagonAsync.socketWrite(id, toBytes(chunk));
+          } catch (error) {
+            throw toError(error);
+          }
+        },
+        close: () => this.close(),
+        abort: () => this.close(),
+      });
+    }
+
+    async close() {
+      if (this.isClosed) {
+        return this.closed;
+      }
+
+      this.isClosed = true;
+
+      try {
+        await LagonAsync.socketClose(await this.connection);
+      } catch {
+        // The connection failed, which rejects `closed`
+        return this.closed;
+      }
+
+      this.resolveClosed();
+
+      return this.closed;
+    }
+  }
+
+  globalThis.connect = (address, options) => {
+    const { hostname, port } = parseAddress(address);
+    const secureTransport = options?.secureTransport ?? 'off';
+
+    if (typeof hostname !== 'string' || hostname === '') {
+      throw new TypeError('Hostname must be a non-empty string');
+    }
+
+    if (!Number.isInteger(port) || port < 1 || port > 65535) {
+      throw new TypeError('Port must be an integer between 1 and 65535');
+    }
+
+    if (secureTransport !== 'on' && secureTransport !== 'off') {
+      throw new TypeError('secureTransport must be "on" or "off"');
+    }
+
+    return new LagonSocket(hostname, port, secureTransport === 'on');
+  };
+})(globalThis);
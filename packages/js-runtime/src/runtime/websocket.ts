@@ -0,0 +1,296 @@
+(globalThis => {
+  const CONNECTING = 0;
+  const OPEN = 1;
+  const CLOSING = 2;
+  const CLOSED = 3;
+
+  // https://www.rfc-editor.org/rfc/rfc6455#section-7.4.1
+  const isValidCloseCode = (code: number) => code === 1000 || (code >= 3000 && code <= 4999);
+
+  // The server end of each pair, to dispatch the messages sent by the client
+  const sockets = new Map<number, LagonWebSocket>();
+
+  // Passed to the constructor by `WebSocketPair` instead of a URL
+  const PAIR = Symbol('pair');
+
+  // Bindings reject with a string, which
+  // we convert to a proper error object
+  const toError = (error: unknown) => (typeof error === 'string' ? new Error(error) : error);
+
+  const parseUrl = (url: string | URL) => {
+    const parsed = new URL(url);
+
+    // Like browsers, HTTP URLs are also accepted
+    if (parsed.protocol === 'http:') {
+      parsed.protocol = 'ws:';
+    } else if (parsed.protocol === 'https:') {
+      parsed.protocol = 'wss:';
+    }
+
+    if (parsed.protocol !== 'ws:' && parsed.protocol !== 'wss:') {
+      throw new SyntaxError(`Invalid WebSocket URL scheme: ${parsed.protocol}`);
+    }
+
+    if (parsed.hash) {
+      throw new SyntaxError('WebSocket URLs cannot contain a fragment');
+    }
+
+    return parsed.href;
+  };
+
+  class LagonWebSocket extends EventTarget {
+    static readonly CONNECTING = CONNECTING;
+    static readonly OPEN = OPEN;
+    static readonly CLOSING = CLOSING;
+    static readonly CLOSED = CLOSED;
+
+    readonly CONNECTING = CONNECTING;
+    readonly OPEN = OPEN;
+    readonly CLOSING = CLOSING;
+    readonly CLOSED = CLOSED;
+
+    readyState: number = CONNECTING;
+    binaryType: BinaryType = 'arraybuffer';
+    protocol = '';
+    readonly extensions = '';
+    readonly url: string = '';
+
+    onopen: ((event: Event) => void) | null = null;
+    onmessage: ((event: MessageEvent) => void) | null = null;
+    onclose: ((event: CloseEvent) => void) | null = null;
+    onerror: ((event: Event) => void) | null = null;
+
+    private id = 0;
+    private isClient = false;
+    private accepted = false;
+    // Events received before the socket is accepted
+    private pendingEvents: Event[] = [];
+
+    constructor(
+      url: string | URL | typeof PAIR,
+      protocols: string | string[] | { id: number; isClient: boolean } = [],
+    ) {
+      super();
+
+      if (url === PAIR) {
+        const { id, isClient } = protocols as { id: number; isClient: boolean };
+
+        this.id = id;
+        this.isClient = isClient;
+        return;
+      }
+
+      const requestedProtocols = typeof protocols === 'string' ? [protocols] : (protocols as string[]);
+
+      if (new Set(requestedProtocols).size !== requestedProtocols.length) {
+        throw new SyntaxError('WebSocket protocols must be unique');
+      }
+
+      this.url = parseUrl(url);
+      // Sockets opened to a server don't need to be accepted
+      this.accepted = true;
+
+      LagonAsync.websocketConnect(this.url, requestedProtocols.join(', ')).then(
+        ({ i, p }) => {
+          this.id = i;
+          this.protocol = p;
+
+          // The socket might have been closed while connecting
+          if (this.readyState !== CONNECTING) {
+            LagonSync.websocketClose(i, 1000, '');
+            return;
+          }
+
+          this.readyState = OPEN;
+          this.dispatchEvent(new Event('open'));
+          this.receiveMessages();
+        },
+        error => {
+          if (this.readyState === CLOSED) {
+            return;
+          }
+
+          this.readyState = CLOSED;
+
+          const event = new Event('error');
+          // @ts-expect-error non-standard property, useful when debugging
+          event.error = toError(error);
+
+          this.dispatchEvent(event);
+          this.dispatchEvent(new CloseEvent('close', { code: 1006, reason: '', wasClean: false }));
+        },
+      );
+    }
+
+    dispatchEvent(event: Event): boolean {
+      const handler = this[`on${event.type}` as 'onopen'];
+
+      if (typeof handler === 'function') {
+        handler.call(this, event);
+      }
+
+      return super.dispatchEvent(event);
+    }
+
+    // Messages from the server of a socket opened with `new WebSocket()`
+    // are pulled one by one, until the socket is closed
+    private async receiveMessages() {
+      while (this.readyState === OPEN) {
+        const message = await LagonAsync.websocketReceive(this.id);
+
+        if (this.readyState !== OPEN) {
+          return;
+        }
+
+        if (message === undefined) {
+          this.readyState = CLOSED;
+          this.dispatchEvent(new CloseEvent('close', { code: 1006, reason: '', wasClean: false }));
+        } else if (typeof message === 'string') {
+          this.dispatchEvent(new MessageEvent('message', { data: message }));
+        } else if (message instanceof Uint8Array) {
+          this.dispatchEvent(
+            new MessageEvent('message', {
+              data: message.buffer.slice(message.byteOffset, message.byteOffset + message.byteLength),
+            }),
+          );
+        } else {
+          this.readyState = CLOSED;
+          this.dispatchEvent(new CloseEvent('close', { code: message.c, reason: message.r, wasClean: true }));
+        }
+      }
+    }
+
+    accept() {
+      if (this.isClient || this.url) {
+        throw new TypeError('Only the server end of a WebSocketPair can be accepted');
+      }
+
+      if (this.accepted) {
+        throw new TypeError('WebSocket has already been accepted');
+      }
+
+      this.accepted = true;
+      this.readyState = OPEN;
+
+      for (const event of this.pendingEvents) {
+        this.dispatchEvent(event);
+      }
+
+      this.pendingEvents = [];
+    }
+
+    send(data: string | ArrayBuffer | ArrayBufferView) {
+      if (!this.accepted || this.readyState !== OPEN) {
+        throw new TypeError('WebSocket is not open');
+      }
+
+      if (typeof data === 'string') {
+        LagonSync.websocketSend(this.id, data);
+      } else if (data instanceof ArrayBuffer) {
+        LagonSync.websocketSend(this.id, new Uint8Array(data));
+      } else if (ArrayBuffer.isView(data)) {
+        LagonSync.websocketSend(this.id, new Uint8Array(data.buffer, data.byteOffset, data.byteLength));
+      } else {
+        throw new TypeError('Data must be a string or a buffer');
+      }
+    }
+
+    close(code = 1000, reason = '') {
+      if (!isValidCloseCode(code)) {
+        throw new TypeError(`Invalid close code: ${code}`);
+      }
+
+      if (this.readyState === CLOSING || this.readyState === CLOSED) {
+        return;
+      }
+
+      // The socket is closed once connected
+      if (this.readyState === CONNECTING && this.url) {
+        this.readyState = CLOSED;
+        this.dispatchEvent(new CloseEvent('close', { code, reason: String(reason), wasClean: false }));
+        return;
+      }
+
+      this.readyState = CLOSING;
+      sockets.delete(this.id);
+
+      LagonSync.websocketClose(this.id, code, String(reason));
+
+      // The socket is removed from the isolate right away, which
+      // won't be notified when the client acknowledges the close
+      this.readyState = CLOSED;
+      this.dispatchEvent(new CloseEvent('close', { code, reason: String(reason), wasClean: true }));
+    }
+
+    // Called when the client sends a message or closes the socket
+    receive(event: Event) {
+      if (event.type === 'close') {
+        this.readyState = CLOSED;
+        sockets.delete(this.id);
+      }
+
+      if (this.accepted) {
+        this.dispatchEvent(event);
+      } else {
+        this.pendingEvents.push(event);
+      }
+    }
+
+    // Only the client end can be returned in a response,
+    // which then accepts the socket using its id
+    getResponseId() {
+      if (!this.isClient || this.url) {
+        throw new TypeError('Only the client end of a WebSocketPair can be returned in a Response');
+      }
+
+      return this.id;
+    }
+  }
+
+  globalThis.WebSocketPair = class {
+    0: WebSocket;
+    1: WebSocket;
+
+    constructor() {
+      const id = LagonSync.websocketCreate();
+      const server = new LagonWebSocket(PAIR, { id, isClient: false });
+
+      sockets.set(id, server);
+
+      this[0] = new LagonWebSocket(PAIR, { id, isClient: true }) as unknown as WebSocket;
+      this[1] = server as unknown as WebSocket;
+    }
+  };
+
+  globalThis.WebSocket = LagonWebSocket as unknown as typeof WebSocket;
+
+  globalThis.__lagon__.getWebSocketId = (webSocket: WebSocket) => {
+    if (!(webSocket instanceof LagonWebSocket)) {
+      throw new TypeError('webSocket must be the client end of a WebSocketPair');
+    }
+
+    return webSocket.getResponseId();
+  };
+
+  globalThis.masterWebSocketHandler = (id, type, data, reason) => {
+    const socket = sockets.get(id);
+
+    if (socket === undefined) {
+      return;
+    }
+
+    if (type === 'close') {
+      socket.receive(new CloseEvent('close', { code: data as number, reason, wasClean: true }));
+    } else if (typeof data === 'string') {
+      socket.receive(new MessageEvent('message', { data }));
+    } else {
+      const bytes = data as Uint8Array;
+
+      socket.receive(
+        new MessageEvent('message', {
+          data: bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength),
+        }),
+      );
+    }
+  };
+})(globalThis);